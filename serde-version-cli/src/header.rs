@@ -0,0 +1,220 @@
+//! Read the version header of a file without the types of its contents
+
+use failure::format_err;
+use serde::de::{Deserialize, Deserializer, EnumAccess, IgnoredAny, MapAccess, SeqAccess};
+use serde_version::{
+    AggregateVersionMap, AggregateVersionMapError, AggregatedVersionMap, DeserializeVersioned,
+    Error, RecordingAggregate, StaticVersionGroupResolver, VersionGroupResolver, VersionGroupURIs,
+    VersionMap,
+};
+use std::path::Path;
+use std::str::FromStr;
+
+/// Format of a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Toml,
+    Json,
+    Yaml,
+    Ron,
+    MsgPack,
+    Cbor,
+    Xml,
+}
+
+impl Format {
+    /// Format of a file deduced from its extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        path.extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(|extension| extension.parse().ok())
+    }
+}
+
+impl FromStr for Format {
+    type Err = failure::Error;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "toml" => Ok(Format::Toml),
+            "json" => Ok(Format::Json),
+            "yaml" | "yml" => Ok(Format::Yaml),
+            "ron" => Ok(Format::Ron),
+            "msgpack" | "mp" => Ok(Format::MsgPack),
+            "cbor" => Ok(Format::Cbor),
+            "xml" => Ok(Format::Xml),
+            _ => Err(format_err!(
+                "Unknown format {:?}, expected toml, json, yaml, ron, msgpack, cbor or xml",
+                name
+            )),
+        }
+    }
+}
+
+/// Where to read the version header
+#[derive(Debug, Clone, Default)]
+pub struct HeaderOptions {
+    /// Format of the file, deduced from its extension by default
+    pub format: Option<Format>,
+    /// Key of the version header, the default key of the format if none
+    pub header_key: Option<String>,
+}
+
+/// Read the version header of the file `path`
+///
+/// The file must be well formed, its contents are read but not interpreted.
+pub fn read_header(
+    path: &Path,
+    options: &HeaderOptions,
+) -> Result<VersionGroupURIs, failure::Error> {
+    let format = options
+        .format
+        .or_else(|| Format::from_path(path))
+        .ok_or_else(|| format_err!("Unknown format, use --format"))?;
+    let input = std::fs::read(path)?;
+    let text = || std::str::from_utf8(&input);
+    let key = options.header_key.as_deref();
+
+    let resolver = StaticVersionGroupResolver::new();
+    let aggregate = RecordingAggregate::new(&HeaderOnly);
+    match format {
+        Format::Toml => {
+            let mut toml = serde_version::toml::Options::new();
+            if let Some(key) = key {
+                toml = toml.header_key(key);
+            }
+            toml.deserialize::<Document, _, _>(text()?, &resolver, &aggregate)?;
+        }
+        Format::Json => {
+            let mut json = serde_version::json::Options::new();
+            if let Some(key) = key {
+                json = json.header_key(key);
+            }
+            json.deserialize::<Document, _, _>(text()?, &resolver, &aggregate)?;
+        }
+        Format::Yaml => {
+            let mut yaml = serde_version::yaml::Options::new();
+            if let Some(key) = key {
+                yaml = yaml.header_key(key);
+            }
+            yaml.deserialize::<Document, _, _>(text()?, &resolver, &aggregate)?;
+        }
+        Format::Ron => {
+            let mut ron = serde_version::ron::Options::new();
+            if let Some(key) = key {
+                ron = ron.header_key(key);
+            }
+            ron.deserialize::<Document, _, _>(text()?, &resolver, &aggregate)?;
+        }
+        Format::MsgPack | Format::Cbor if key.is_some() => {
+            return Err(format_err!(
+                "--header-key is not supported by msgpack and cbor"
+            ));
+        }
+        Format::MsgPack => {
+            serde_version::msgpack::deserialize::<Document, _, _>(&input, &resolver, &aggregate)?;
+        }
+        Format::Cbor => {
+            serde_version::cbor::deserialize::<Document, _, _>(&input, &resolver, &aggregate)?;
+        }
+        Format::Xml => {
+            let mut xml = serde_version::xml::Options::new();
+            if let Some(key) = key {
+                xml = xml.header_attribute(key);
+            }
+            xml.deserialize::<Document, _, _>(text()?, &resolver, &aggregate)?;
+        }
+    }
+    aggregate
+        .uris()
+        .ok_or_else(|| format_err!("Missing version header"))
+}
+
+/// Accept any version header, the versions are not needed to skip the contents
+struct HeaderOnly;
+
+impl AggregateVersionMap for HeaderOnly {
+    fn aggregate_version_maps<VMR>(
+        &self,
+        _uris: &VersionGroupURIs,
+        _resolver: &VMR,
+    ) -> Result<AggregatedVersionMap, AggregateVersionMapError>
+    where
+        VMR: VersionGroupResolver,
+    {
+        Ok(AggregatedVersionMap::new())
+    }
+}
+
+/// Contents of any file, skipped
+struct Document;
+
+impl<'de> Deserialize<'de> for Document {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        IgnoredAny::deserialize(deserializer).map(|_| Document)
+    }
+}
+
+impl<'de> DeserializeVersioned<'de> for Document {
+    fn deserialize_versioned<D, VM: VersionMap>(
+        deserializer: D,
+        _version_map: VM,
+    ) -> Result<Self, Error<D::Error>>
+    where
+        D: Deserializer<'de>,
+    {
+        Document::deserialize(deserializer).map_err(Error::DeserializeError)
+    }
+
+    fn next_element<S, VM: VersionMap>(
+        seq_access: &mut S,
+        _version_map: VM,
+    ) -> Result<Option<Self>, Error<S::Error>>
+    where
+        S: SeqAccess<'de>,
+    {
+        seq_access
+            .next_element_seed(std::marker::PhantomData)
+            .map_err(Error::DeserializeError)
+    }
+
+    fn next_value<M, VM: VersionMap>(
+        map_access: &mut M,
+        _version_map: VM,
+    ) -> Result<Self, Error<M::Error>>
+    where
+        M: MapAccess<'de>,
+    {
+        map_access
+            .next_value_seed(std::marker::PhantomData)
+            .map_err(Error::DeserializeError)
+    }
+
+    fn next_key<M, VM: VersionMap>(
+        map_access: &mut M,
+        _version_map: VM,
+    ) -> Result<Option<Self>, Error<M::Error>>
+    where
+        M: MapAccess<'de>,
+    {
+        map_access
+            .next_key_seed(std::marker::PhantomData)
+            .map_err(Error::DeserializeError)
+    }
+
+    fn variant<E, VM: VersionMap>(
+        enum_access: E,
+        _version_map: VM,
+    ) -> Result<(Self, E::Variant), Error<E::Error>>
+    where
+        E: EnumAccess<'de>,
+    {
+        enum_access
+            .variant_seed(std::marker::PhantomData)
+            .map_err(Error::DeserializeError)
+    }
+
+    fn last_version() -> usize {
+        0
+    }
+}
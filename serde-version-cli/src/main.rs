@@ -0,0 +1,169 @@
+//! Inspect and validate the version headers of files, without the types of their contents
+//!
+//! ```text
+//! serde-version header config.toml
+//! serde-version resolve --manifest groups.toml config.toml
+//! serde-version check --manifest groups.toml config/*.toml
+//! ```
+//!
+//! `check` exits with the status 1 when a file has an unknown version group uri, types
+//! with conflicting versions or can't be read, so it can run in pre-commit checks.
+
+mod header;
+mod resolve;
+
+use clap::{Args, Parser, Subcommand};
+use header::{read_header, Format, HeaderOptions};
+use serde_version::manifest::ManifestVersionGroupResolver;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+/// Inspect and validate the version headers of serde-version files
+#[derive(Debug, Parser)]
+#[command(name = "serde-version", version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Print the version group uris of the header of a file
+    Header {
+        #[command(flatten)]
+        header: HeaderArgs,
+        file: PathBuf,
+    },
+    /// Print the version of each type of the header of a file
+    Resolve {
+        #[command(flatten)]
+        header: HeaderArgs,
+        #[command(flatten)]
+        manifests: ManifestArgs,
+        file: PathBuf,
+    },
+    /// Check that the version groups of the headers are known and don't conflict
+    Check {
+        #[command(flatten)]
+        header: HeaderArgs,
+        #[command(flatten)]
+        manifests: ManifestArgs,
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+    },
+}
+
+#[derive(Debug, Args)]
+struct HeaderArgs {
+    /// Format of the files: toml, json, yaml, ron, msgpack, cbor or xml, deduced from their
+    /// extension by default (bincode, postcard and csv are not supported)
+    #[arg(long)]
+    format: Option<Format>,
+    /// Key of the version header, or attribute for xml (not supported by msgpack and cbor)
+    #[arg(long)]
+    header_key: Option<String>,
+}
+
+impl From<HeaderArgs> for HeaderOptions {
+    fn from(args: HeaderArgs) -> Self {
+        HeaderOptions {
+            format: args.format,
+            header_key: args.header_key,
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+struct ManifestArgs {
+    /// Manifest declaring version groups, toml or json, can be repeated
+    #[arg(long = "manifest", short, required = true)]
+    manifests: Vec<PathBuf>,
+}
+
+impl ManifestArgs {
+    fn load(&self) -> Result<ManifestVersionGroupResolver, failure::Error> {
+        let mut resolver = ManifestVersionGroupResolver::new();
+        for path in &self.manifests {
+            resolver
+                .load_file(path.display().to_string(), path)
+                .map_err(|err| failure::format_err!("{}: {}", path.display(), err))?;
+        }
+        Ok(resolver)
+    }
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse().command) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Run a command
+///
+/// # Returns
+/// Whether no problem was found
+fn run(command: Command) -> Result<bool, failure::Error> {
+    match command {
+        Command::Header { header, file } => {
+            let uris = read_file_header(&file, &header.into())?;
+            for uri in uris.iter() {
+                println!("{}", uri);
+            }
+            Ok(true)
+        }
+        Command::Resolve {
+            header,
+            manifests,
+            file,
+        } => {
+            let resolver = manifests.load()?;
+            let uris = read_file_header(&file, &header.into())?;
+            let problems = resolve::problems(&uris, &resolver);
+            if !problems.is_empty() {
+                for problem in &problems {
+                    eprintln!("{}: {}", file.display(), problem);
+                }
+                return Ok(false);
+            }
+            for (type_id, version) in resolve::flat_version_map(&uris, &resolver) {
+                println!("{} = {}", type_id, version);
+            }
+            Ok(true)
+        }
+        Command::Check {
+            header,
+            manifests,
+            files,
+        } => {
+            let resolver = manifests.load()?;
+            let options = header.into();
+            let mut valid = true;
+            for file in &files {
+                let problems = match read_header(file, &options) {
+                    Ok(uris) => resolve::problems(&uris, &resolver)
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect(),
+                    Err(err) => vec![err.to_string()],
+                };
+                for problem in &problems {
+                    eprintln!("{}: {}", file.display(), problem);
+                }
+                valid &= problems.is_empty();
+            }
+            Ok(valid)
+        }
+    }
+}
+
+fn read_file_header(
+    path: &Path,
+    options: &HeaderOptions,
+) -> Result<serde_version::VersionGroupURIs, failure::Error> {
+    read_header(path, options).map_err(|err| failure::format_err!("{}: {}", path.display(), err))
+}
@@ -0,0 +1,72 @@
+//! Resolve version headers against the groups of manifests
+
+use serde_version::manifest::ManifestVersionGroupResolver;
+use serde_version::{
+    AggregateVersionMapError, VersionConflictError, VersionGroupResolver, VersionGroupURI,
+    VersionGroupURIs,
+};
+use std::collections::BTreeMap;
+
+/// Version of each type of a version header, sorted by type
+pub type FlatVersionMap = BTreeMap<String, usize>;
+
+/// Find the unknown uris of a version header and the types with conflicting versions
+///
+/// All the problems are reported, not only the first one.
+pub fn problems(
+    uris: &VersionGroupURIs,
+    resolver: &ManifestVersionGroupResolver,
+) -> Vec<AggregateVersionMapError> {
+    let mut problems = Vec::new();
+    // Version and uri of the first group defining each type
+    let mut versions: BTreeMap<&str, (usize, &VersionGroupURI)> = BTreeMap::new();
+    for uri in uris.iter() {
+        let version_map = match resolver.resolve(uri) {
+            Some(version_map) => version_map,
+            None => {
+                problems.push(AggregateVersionMapError::UnknownVersionGroupURI(
+                    uri.clone(),
+                ));
+                continue;
+            }
+        };
+        let mut entries: Vec<_> = version_map.iter().collect();
+        entries.sort();
+        for (type_id, &version) in entries {
+            match versions.get(type_id.as_str()) {
+                Some(&(first_version, first_uri)) if first_version != version => {
+                    problems.push(AggregateVersionMapError::VersionConflict(Box::new(
+                        VersionConflictError {
+                            type_id: type_id.clone(),
+                            first_version,
+                            first_uri: first_uri.clone(),
+                            second_version: version,
+                            second_uri: uri.clone(),
+                        },
+                    )));
+                }
+                Some(_) => {}
+                None => {
+                    versions.insert(type_id, (version, uri));
+                }
+            }
+        }
+    }
+    problems
+}
+
+/// Version of each type of a version header without problems
+pub fn flat_version_map(
+    uris: &VersionGroupURIs,
+    resolver: &ManifestVersionGroupResolver,
+) -> FlatVersionMap {
+    let mut flat = FlatVersionMap::new();
+    for version_map in uris.iter().filter_map(|uri| resolver.resolve(uri)) {
+        flat.extend(
+            version_map
+                .iter()
+                .map(|(type_id, version)| (type_id.clone(), *version)),
+        );
+    }
+    flat
+}
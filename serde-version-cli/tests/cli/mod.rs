@@ -0,0 +1,163 @@
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+const MANIFEST: &str = r#"
+["app:1"]
+"app::Config" = 1
+"app::Item" = 1
+
+["app:2"]
+extends = "app:1"
+"app::Item" = 2
+
+["plugin:1"]
+"app::Item" = 3
+"plugin::Settings" = 1
+"#;
+
+fn serde_version(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_serde-version"))
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: &Output) -> &str {
+    std::str::from_utf8(&output.stdout).unwrap()
+}
+
+fn stderr(output: &Output) -> &str {
+    std::str::from_utf8(&output.stderr).unwrap()
+}
+
+fn files() -> tempfile::TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("groups.toml"), MANIFEST).unwrap();
+    fs::write(
+        dir.path().join("config.toml"),
+        "v = [\"app:2\"]\nname = \"config\"\n\n[server]\nport = 80\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.path().join("items.json"),
+        r#"{ "v": ["app:1", "plugin:1", "other:1"], "items": [1, 2] }"#,
+    )
+    .unwrap();
+    fs::write(dir.path().join("missing.yaml"), "name: config\n").unwrap();
+    dir
+}
+
+#[test]
+fn header_works() {
+    let dir = files();
+
+    let output = serde_version(dir.path(), &["header", "config.toml"]);
+    assert!(output.status.success());
+    assert_eq!("app:2\n", stdout(&output));
+
+    let output = serde_version(dir.path(), &["header", "items.json"]);
+    assert!(output.status.success());
+    assert_eq!("app:1\nplugin:1\nother:1\n", stdout(&output));
+
+    fs::write(dir.path().join("config.txt"), "{\"versions\": [\"app:1\"]}").unwrap();
+    let output = serde_version(
+        dir.path(),
+        &[
+            "header",
+            "--format",
+            "json",
+            "--header-key",
+            "versions",
+            "config.txt",
+        ],
+    );
+    assert!(output.status.success());
+    assert_eq!("app:1\n", stdout(&output));
+}
+
+#[test]
+fn header_errors() {
+    let dir = files();
+
+    let output = serde_version(dir.path(), &["header", "missing.yaml"]);
+    assert_eq!(Some(1), output.status.code());
+    assert_eq!(
+        "error: missing.yaml: Missing version header 'v'\n",
+        stderr(&output)
+    );
+
+    let output = serde_version(dir.path(), &["header", "groups.txt"]);
+    assert_eq!(Some(1), output.status.code());
+    assert_eq!(
+        "error: groups.txt: Unknown format, use --format\n",
+        stderr(&output)
+    );
+
+    let output = serde_version(dir.path(), &["header", "--format", "ini", "config.toml"]);
+    assert_eq!(Some(2), output.status.code());
+    assert!(stderr(&output).contains("expected toml, json, yaml, ron, msgpack, cbor or xml"));
+
+    fs::write(dir.path().join("config.msgpack"), b"").unwrap();
+    let output = serde_version(
+        dir.path(),
+        &["header", "--header-key", "versions", "config.msgpack"],
+    );
+    assert_eq!(Some(1), output.status.code());
+    assert_eq!(
+        "error: config.msgpack: --header-key is not supported by msgpack and cbor\n",
+        stderr(&output)
+    );
+}
+
+#[test]
+fn resolve_works() {
+    let dir = files();
+
+    let output = serde_version(dir.path(), &["resolve", "-m", "groups.toml", "config.toml"]);
+    assert!(output.status.success());
+    assert_eq!("app::Config = 1\napp::Item = 2\n", stdout(&output));
+
+    let output = serde_version(dir.path(), &["resolve", "-m", "groups.toml", "items.json"]);
+    assert_eq!(Some(1), output.status.code());
+    assert_eq!("", stdout(&output));
+    assert_eq!(
+        "items.json: Conflicting versions for type app::Item: version 1 from app:1 and version 3 from plugin:1\n\
+         items.json: Unknown version group uri other:1\n",
+        stderr(&output)
+    );
+}
+
+#[test]
+fn check_works() {
+    let dir = files();
+
+    let output = serde_version(dir.path(), &["check", "-m", "groups.toml", "config.toml"]);
+    assert!(output.status.success());
+    assert_eq!("", stderr(&output));
+
+    // All the files are checked
+    let output = serde_version(
+        dir.path(),
+        &[
+            "check",
+            "--manifest",
+            "groups.toml",
+            "items.json",
+            "config.toml",
+            "missing.yaml",
+        ],
+    );
+    assert_eq!(Some(1), output.status.code());
+    assert_eq!(
+        "items.json: Conflicting versions for type app::Item: version 1 from app:1 and version 3 from plugin:1\n\
+         items.json: Unknown version group uri other:1\n\
+         missing.yaml: Missing version header 'v'\n",
+        stderr(&output)
+    );
+
+    let output = serde_version(dir.path(), &["check", "-m", "unknown.toml", "config.toml"]);
+    assert_eq!(Some(1), output.status.code());
+    assert!(stderr(&output).starts_with("error: unknown.toml: "));
+}
@@ -5,6 +5,9 @@
 //! to choose the appropriate version.
 //!
 
+// clippy reads the entries of `#[versions(...)]` as duplicated attributes
+#![allow(clippy::duplicated_attributes)]
+
 extern crate serde;
 #[macro_use]
 extern crate serde_version_derive;
@@ -0,0 +1,318 @@
+//! Serialization utilities for binary formats
+//!
+//! Binary formats like bincode or postcard are not self-describing: the bytes of a value
+//! only make sense with the right version of its type. So the version header is written
+//! ahead of the payload, in a compact layout that doesn't depend on the format of the payload.
+//!
+//! # Layout of the version header
+//!
+//! All integers are unsigned LEB128 varints (7 bits per byte, least significant group first,
+//! the high bit is set on every byte but the last one). Strings are a varint byte length
+//! followed by UTF-8 bytes.
+//!
+//! ```text
+//! header  := kind count entry{count}
+//! kind    := 0x01    version group uris, resolved when reading
+//!          | 0x02    version of each type, no resolver needed
+//! count   := varint
+//! entry   := string                for 0x01, the uri "<api_group>:<version>"
+//!          | string varint         for 0x02, the type key and its version
+//! ```
+//!
+//! For instance, the uris `a:1` and `b:2` are written as
+//! `01 02 03 61 3a 31 03 62 3a 32`. The payload starts right after the header.
+//!
+//! The `bincode` (feature gate `bincode-support`) and `postcard` (feature gate
+//! `postcard-support`) modules write the payload with their format.
+
+#[cfg(feature = "bincode-support")]
+pub mod bincode;
+#[cfg(feature = "postcard-support")]
+pub mod postcard;
+
+use crate::version_map::AggregateVersionMap;
+use crate::{
+    AggregateVersionMapError, AggregatedVersionMap, InvalidVersionGroupURIError,
+    VersionGroupResolver, VersionGroupURIs, VersionMap,
+};
+use failure::Fail;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::io::Read;
+
+/// Kind byte of a header with version group uris
+pub const HEADER_KIND_GROUPS: u8 = 0x01;
+/// Kind byte of a header with the version of each type
+pub const HEADER_KIND_VERSIONS: u8 = 0x02;
+
+/// Error raised while decoding a binary version header
+#[derive(Debug, Clone, PartialEq, Eq, Fail)]
+pub enum DecodeHeaderError {
+    #[fail(display = "Unexpected end of the version header")]
+    UnexpectedEnd,
+    #[fail(display = "Unknown version header kind {:#04x}", 0)]
+    UnknownKind(u8),
+    #[fail(display = "Varint overflow in the version header")]
+    VarintOverflow,
+    #[fail(display = "Invalid UTF-8 string in the version header")]
+    InvalidUtf8,
+    #[fail(display = "{}", 0)]
+    InvalidURI(InvalidVersionGroupURIError),
+}
+
+/// Error raised while reading a binary version header from a reader
+#[derive(Debug, Fail)]
+pub enum ReadHeaderError {
+    #[fail(display = "{}", 0)]
+    Io(std::io::Error),
+    #[fail(display = "{}", 0)]
+    Decode(DecodeHeaderError),
+}
+impl_from_enum! {
+    ReadHeaderError::Decode => DecodeHeaderError,
+}
+
+impl From<std::io::Error> for ReadHeaderError {
+    fn from(error: std::io::Error) -> Self {
+        match error.kind() {
+            std::io::ErrorKind::UnexpectedEof => {
+                ReadHeaderError::Decode(DecodeHeaderError::UnexpectedEnd)
+            }
+            _ => ReadHeaderError::Io(error),
+        }
+    }
+}
+
+/// Version header of a binary document
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BinaryHeader {
+    /// Version group uris, aggregated with the resolver when reading
+    Groups(VersionGroupURIs),
+    /// Version of each type, used as is when reading
+    Versions(BTreeMap<String, usize>),
+}
+
+impl From<VersionGroupURIs> for BinaryHeader {
+    fn from(uris: VersionGroupURIs) -> Self {
+        BinaryHeader::Groups(uris)
+    }
+}
+
+impl BinaryHeader {
+    /// Header with the versions of a version map
+    pub fn versions<VM: VersionMap>(version_map: &VM) -> Self {
+        BinaryHeader::Versions(
+            version_map
+                .iter()
+                .map(|(type_id, version)| (type_id.to_owned(), version))
+                .collect(),
+        )
+    }
+
+    /// Append the encoded header to `out`
+    pub fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            BinaryHeader::Groups(uris) => {
+                out.push(HEADER_KIND_GROUPS);
+                write_varint(out, uris.len() as u64);
+                for uri in uris.iter() {
+                    write_str(out, &uri.to_string());
+                }
+            }
+            BinaryHeader::Versions(versions) => {
+                out.push(HEADER_KIND_VERSIONS);
+                write_varint(out, versions.len() as u64);
+                for (type_id, version) in versions {
+                    write_str(out, type_id);
+                    write_varint(out, *version as u64);
+                }
+            }
+        }
+    }
+
+    /// Decode the header at the start of `input`
+    ///
+    /// # Returns
+    /// The header and the bytes after it
+    pub fn decode(input: &[u8]) -> Result<(Self, &[u8]), DecodeHeaderError> {
+        let (kind, mut input) = input
+            .split_first()
+            .ok_or(DecodeHeaderError::UnexpectedEnd)?;
+        let count = read_usize(&mut input)?;
+        let header = match *kind {
+            HEADER_KIND_GROUPS => {
+                let mut uris = VersionGroupURIs::new();
+                for _ in 0..count {
+                    uris.push(
+                        read_str(&mut input)?
+                            .parse()
+                            .map_err(DecodeHeaderError::InvalidURI)?,
+                    );
+                }
+                BinaryHeader::Groups(uris)
+            }
+            HEADER_KIND_VERSIONS => {
+                let mut versions = BTreeMap::new();
+                for _ in 0..count {
+                    let type_id = read_str(&mut input)?.to_owned();
+                    versions.insert(type_id, read_usize(&mut input)?);
+                }
+                BinaryHeader::Versions(versions)
+            }
+            kind => return Err(DecodeHeaderError::UnknownKind(kind)),
+        };
+        Ok((header, input))
+    }
+
+    /// Read the header at the start of `reader`
+    ///
+    /// Only the bytes of the header are consumed, the reader is then at the start of the
+    /// payload.
+    pub fn read_from<R: Read>(reader: &mut R) -> Result<Self, ReadHeaderError> {
+        let mut buffer = Vec::new();
+        copy_bytes(reader, &mut buffer, 1)?;
+        let kind = buffer[0];
+        if kind != HEADER_KIND_GROUPS && kind != HEADER_KIND_VERSIONS {
+            return Err(DecodeHeaderError::UnknownKind(kind).into());
+        }
+        let count = copy_varint(reader, &mut buffer)?;
+        for _ in 0..count {
+            let len = copy_varint(reader, &mut buffer)?;
+            copy_bytes(reader, &mut buffer, len)?;
+            if kind == HEADER_KIND_VERSIONS {
+                copy_varint(reader, &mut buffer)?;
+            }
+        }
+        Ok(Self::decode(&buffer)?.0)
+    }
+
+    /// Version map to deserialize the payload with
+    pub fn version_map<VMR, AGG>(
+        &self,
+        resolver: &VMR,
+        aggregate: &AGG,
+    ) -> Result<AggregatedVersionMap, AggregateVersionMapError>
+    where
+        VMR: VersionGroupResolver,
+        AGG: AggregateVersionMap,
+    {
+        match self {
+            BinaryHeader::Groups(uris) => aggregate.aggregate_version_maps(uris, resolver),
+            BinaryHeader::Versions(versions) => Ok(versions
+                .iter()
+                .map(|(type_id, version)| (type_id.clone(), *version))
+                .collect()),
+        }
+    }
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn write_str(out: &mut Vec<u8>, value: &str) {
+    write_varint(out, value.len() as u64);
+    out.extend_from_slice(value.as_bytes());
+}
+
+/// Append `len` bytes of `reader` to `buffer`
+fn copy_bytes<R: Read>(
+    reader: &mut R,
+    buffer: &mut Vec<u8>,
+    len: u64,
+) -> Result<(), ReadHeaderError> {
+    // The length comes from the input, so the buffer only grows with the bytes actually read
+    if reader.take(len).read_to_end(buffer)? as u64 != len {
+        return Err(DecodeHeaderError::UnexpectedEnd.into());
+    }
+    Ok(())
+}
+
+/// Append a varint of `reader` to `buffer`
+fn copy_varint<R: Read>(reader: &mut R, buffer: &mut Vec<u8>) -> Result<u64, ReadHeaderError> {
+    let start = buffer.len();
+    loop {
+        copy_bytes(reader, buffer, 1)?;
+        if buffer[buffer.len() - 1] & 0x80 == 0 || buffer.len() - start == 10 {
+            return Ok(read_varint(&mut &buffer[start..])?);
+        }
+    }
+}
+
+fn read_varint(input: &mut &[u8]) -> Result<u64, DecodeHeaderError> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (byte, rest) = input
+            .split_first()
+            .ok_or(DecodeHeaderError::UnexpectedEnd)?;
+        *input = rest;
+        let bits = u64::from(byte & 0x7f);
+        if bits << shift >> shift != bits {
+            return Err(DecodeHeaderError::VarintOverflow);
+        }
+        value |= bits << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(DecodeHeaderError::VarintOverflow)
+}
+
+fn read_usize(input: &mut &[u8]) -> Result<usize, DecodeHeaderError> {
+    usize::try_from(read_varint(input)?).map_err(|_| DecodeHeaderError::VarintOverflow)
+}
+
+fn read_str<'a>(input: &mut &'a [u8]) -> Result<&'a str, DecodeHeaderError> {
+    let len = read_usize(input)?;
+    if input.len() < len {
+        return Err(DecodeHeaderError::UnexpectedEnd);
+    }
+    let (value, rest) = input.split_at(len);
+    *input = rest;
+    std::str::from_utf8(value).map_err(|_| DecodeHeaderError::InvalidUtf8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn varint_roundtrip() {
+        for value in &[
+            0,
+            1,
+            0x7f,
+            0x80,
+            0x3fff,
+            0x4000,
+            u64::from(u32::MAX),
+            u64::MAX,
+        ] {
+            let mut out = Vec::new();
+            write_varint(&mut out, *value);
+            let mut input = &out[..];
+            assert_eq!(*value, read_varint(&mut input).unwrap());
+            assert!(input.is_empty());
+        }
+    }
+
+    #[test]
+    fn varint_errors() {
+        assert_eq!(
+            Err(DecodeHeaderError::UnexpectedEnd),
+            read_varint(&mut &[0x80][..])
+        );
+        assert_eq!(
+            Err(DecodeHeaderError::VarintOverflow),
+            read_varint(&mut &[0xff; 10][..])
+        );
+        assert_eq!(
+            Err(DecodeHeaderError::VarintOverflow),
+            read_varint(&mut &[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x02][..])
+        );
+    }
+}
@@ -0,0 +1,144 @@
+//! Bincode payload after a binary version header
+//!
+//! The payload is encoded like `bincode::serialize` does: fixed size integers, little endian.
+
+use super::{BinaryHeader, DecodeHeaderError, ReadHeaderError};
+use crate::version_map::AggregateVersionMap;
+use crate::{
+    AggregateVersionMapError, DeserializeVersioned, Error, VersionGroupResolver,
+    VersionedDeserializer,
+};
+use ::bincode::Options;
+use failure::Fail;
+use serde::Serialize;
+
+/// Deserialization error
+#[derive(Debug, Fail)]
+pub enum DeserializeError {
+    #[fail(display = "{}", 0)]
+    Header(DecodeHeaderError),
+    #[fail(display = "{}", 0)]
+    De(Error<::bincode::Error>),
+    #[fail(display = "{}", 0)]
+    AggregateError(AggregateVersionMapError),
+    #[fail(display = "{}", 0)]
+    Io(std::io::Error),
+}
+impl_from_enum! {
+    DeserializeError::Header => DecodeHeaderError,
+    DeserializeError::De => Error<::bincode::Error>,
+    DeserializeError::AggregateError => AggregateVersionMapError,
+}
+
+impl From<ReadHeaderError> for DeserializeError {
+    fn from(error: ReadHeaderError) -> Self {
+        match error {
+            ReadHeaderError::Io(err) => DeserializeError::Io(err),
+            ReadHeaderError::Decode(err) => DeserializeError::Header(err),
+        }
+    }
+}
+
+/// Deserialize a binary version header followed by a bincode payload
+///
+/// # Generic Parameters
+/// - `T`: type to deserialize
+/// - `VMR`: resolver to find the version groups to use
+/// - `AGG`: aggregator to combine found version groups
+///
+/// # Parameters
+/// - `input`: the version header, then the payload. Trailing bytes after the payload are
+///   ignored, like `bincode::deserialize` does.
+/// - `resolver`: resolver to find the version groups to use
+/// - `aggregate`: aggregator to combine found version groups
+///
+/// # Returns
+/// The deserialized value or the error that occurred
+pub fn deserialize<'de, T, VMR, AGG>(
+    input: &'de [u8],
+    resolver: &VMR,
+    aggregate: &AGG,
+) -> Result<T, DeserializeError>
+where
+    T: DeserializeVersioned<'de>,
+    VMR: VersionGroupResolver,
+    AGG: AggregateVersionMap,
+{
+    let (header, payload) = BinaryHeader::decode(input)?;
+    let version_map = header.version_map(resolver, aggregate)?;
+
+    let mut de = ::bincode::Deserializer::from_slice(payload, options());
+    Ok(DeserializeVersioned::deserialize_versioned(
+        VersionedDeserializer::new(&mut de, &version_map),
+        &version_map,
+    )
+    .map_err(Error::reduce)?)
+}
+
+/// Read a binary version header followed by a bincode payload
+///
+/// The version header is read first, then the value is deserialized while the payload is
+/// read: it is never loaded whole in memory. Only the bytes of the value are consumed.
+///
+/// # Generic Parameters
+/// - `R`: reader of the document
+/// - `T`: type to deserialize
+/// - `VMR`: resolver to find the version groups to use
+/// - `AGG`: aggregator to combine found version groups
+///
+/// # Parameters
+/// - `reader`: reader of the version header, then the payload
+/// - `resolver`: resolver to find the version groups to use
+/// - `aggregate`: aggregator to combine found version groups
+///
+/// # Returns
+/// The deserialized value or the error that occurred
+pub fn from_reader<R, T, VMR, AGG>(
+    mut reader: R,
+    resolver: &VMR,
+    aggregate: &AGG,
+) -> Result<T, DeserializeError>
+where
+    R: std::io::Read,
+    T: for<'de> DeserializeVersioned<'de>,
+    VMR: VersionGroupResolver,
+    AGG: AggregateVersionMap,
+{
+    let header = BinaryHeader::read_from(&mut reader)?;
+    let version_map = header.version_map(resolver, aggregate)?;
+
+    let mut de = ::bincode::Deserializer::with_reader(reader, options());
+    Ok(DeserializeVersioned::deserialize_versioned(
+        VersionedDeserializer::new(&mut de, &version_map),
+        &version_map,
+    )
+    .map_err(Error::reduce)?)
+}
+
+/// Options of `bincode::deserialize`
+fn options() -> impl Options {
+    ::bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+}
+
+/// Serialize a value with bincode after its binary version header
+///
+/// # Generic Parameters
+/// - `T`: type to serialize
+///
+/// # Parameters
+/// - `value`: The value to serialize
+/// - `header`: The version header, see `BinaryHeader`
+///
+/// # Returns
+/// The bytes or the error that occurred, if any.
+pub fn serialize<T: Serialize>(
+    value: &T,
+    header: &BinaryHeader,
+) -> Result<Vec<u8>, ::bincode::Error> {
+    let mut out = Vec::new();
+    header.encode(&mut out);
+    ::bincode::serialize_into(&mut out, value)?;
+    Ok(out)
+}
@@ -0,0 +1,82 @@
+//! Postcard payload after a binary version header
+
+use super::{BinaryHeader, DecodeHeaderError};
+use crate::version_map::AggregateVersionMap;
+use crate::{
+    AggregateVersionMapError, DeserializeVersioned, Error, VersionGroupResolver,
+    VersionedDeserializer,
+};
+use failure::Fail;
+use serde::Serialize;
+
+/// Deserialization error
+#[derive(Debug, Fail)]
+pub enum DeserializeError {
+    #[fail(display = "{}", 0)]
+    Header(DecodeHeaderError),
+    #[fail(display = "{}", 0)]
+    De(Error<::postcard::Error>),
+    #[fail(display = "{}", 0)]
+    AggregateError(AggregateVersionMapError),
+}
+impl_from_enum! {
+    DeserializeError::Header => DecodeHeaderError,
+    DeserializeError::De => Error<::postcard::Error>,
+    DeserializeError::AggregateError => AggregateVersionMapError,
+}
+
+/// Deserialize a binary version header followed by a postcard payload
+///
+/// # Generic Parameters
+/// - `T`: type to deserialize
+/// - `VMR`: resolver to find the version groups to use
+/// - `AGG`: aggregator to combine found version groups
+///
+/// # Parameters
+/// - `input`: the version header, then the payload. Trailing bytes after the payload are
+///   ignored, like `postcard::from_bytes` does.
+/// - `resolver`: resolver to find the version groups to use
+/// - `aggregate`: aggregator to combine found version groups
+///
+/// # Returns
+/// The deserialized value or the error that occurred
+pub fn deserialize<'de, T, VMR, AGG>(
+    input: &'de [u8],
+    resolver: &VMR,
+    aggregate: &AGG,
+) -> Result<T, DeserializeError>
+where
+    T: DeserializeVersioned<'de>,
+    VMR: VersionGroupResolver,
+    AGG: AggregateVersionMap,
+{
+    let (header, payload) = BinaryHeader::decode(input)?;
+    let version_map = header.version_map(resolver, aggregate)?;
+
+    let mut de = ::postcard::Deserializer::from_bytes(payload);
+    Ok(DeserializeVersioned::deserialize_versioned(
+        VersionedDeserializer::new(&mut de, &version_map),
+        &version_map,
+    )
+    .map_err(Error::reduce)?)
+}
+
+/// Serialize a value with postcard after its binary version header
+///
+/// # Generic Parameters
+/// - `T`: type to serialize
+///
+/// # Parameters
+/// - `value`: The value to serialize
+/// - `header`: The version header, see `BinaryHeader`
+///
+/// # Returns
+/// The bytes or the error that occurred, if any.
+pub fn serialize<T: Serialize>(
+    value: &T,
+    header: &BinaryHeader,
+) -> Result<Vec<u8>, ::postcard::Error> {
+    let mut out = Vec::new();
+    header.encode(&mut out);
+    ::postcard::to_extend(value, out)
+}
@@ -0,0 +1,300 @@
+//! Serialization utilities for the CBOR format
+//!
+//! The version header is a semantic tag wrapping an array of the version uris and the value:
+//! ```text
+//! 1398162770(["my.company:1"], {"name": "my config name"})
+//! ```
+//!
+//! Data written before the versioning was introduced has no such tag: with
+//! [`Options::legacy_version_map`], it is read with a fallback version map instead.
+
+use crate::header::{DocumentVisitor, Layout};
+use crate::upgrade::UpgradeFormat;
+use crate::version_map::AggregateVersionMap;
+use crate::{
+    AggregateVersionMapError, AggregatedVersionMap, DeserializeVersioned, Error,
+    VersionGroupResolver, VersionGroupURIs, VersionedDeserializer,
+};
+use failure::Fail;
+use serde::{Deserializer, Serialize};
+use std::marker::PhantomData;
+
+/// Tag of the version header, `"SVER"` in ASCII
+///
+/// **Provisional:** it is in the first come first served range of the IANA registry of CBOR
+/// tags, but it is not registered, so another application may use it for something else.
+/// It may change if a registration is made, use [`Options::tag`] to pin the tag of your data.
+pub const DEFAULT_VERSION_TAG: u64 = 0x5356_4552;
+
+/// Major type of the CBOR tags, in the 3 high bits of their initial byte
+const MAJOR_TYPE_TAG: u8 = 6;
+
+/// Deserialization error
+#[derive(Debug, Fail)]
+pub enum DeserializeError {
+    #[fail(display = "{}", 0)]
+    Cbor(::serde_cbor::Error),
+    #[fail(display = "{}", 0)]
+    De(Error<::serde_cbor::Error>),
+    #[fail(display = "{}", 0)]
+    AggregateError(AggregateVersionMapError),
+    #[fail(display = "Missing the version tag {}", 0)]
+    MissingVersionTag(u64),
+    #[fail(
+        display = "Expected the version tag {}, found the tag {}",
+        expected, found
+    )]
+    UnexpectedTag { expected: u64, found: u64 },
+}
+impl_from_enum! {
+    DeserializeError::Cbor => ::serde_cbor::Error,
+    DeserializeError::De => Error<::serde_cbor::Error>,
+    DeserializeError::AggregateError => AggregateVersionMapError,
+}
+
+/// Error raised while upgrading a CBOR file
+pub type UpgradeError = crate::upgrade::UpgradeError<DeserializeError, ::serde_cbor::Error>;
+
+/// Report of the upgrade of the CBOR files of a directory
+pub type BatchReport = crate::upgrade::BatchReport<DeserializeError, ::serde_cbor::Error>;
+
+// CBOR has no header key, the version header is the first element of the tagged array
+impl_from_header_error!(DeserializeError, |key| DeserializeError::De(
+    Error::Message(format!("Missing version header '{}'", key))
+));
+
+/// Options of the CBOR format
+///
+/// By default, the version tag is `DEFAULT_VERSION_TAG` and untagged data is rejected.
+///
+/// ```
+/// # use std::collections::HashMap;
+/// let mut legacy = HashMap::new();
+/// legacy.insert("my_crate::Config".to_owned(), 1);
+/// let options = serde_version::cbor::Options::new()
+///     .tag(40_000)
+///     .legacy_version_map(legacy);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Options {
+    tag: u64,
+    legacy_version_map: Option<AggregatedVersionMap>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            tag: DEFAULT_VERSION_TAG,
+            legacy_version_map: None,
+        }
+    }
+}
+
+impl Options {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use the semantic tag `tag` for the version header
+    pub fn tag(mut self, tag: u64) -> Self {
+        self.tag = tag;
+        self
+    }
+
+    /// Read the data without the version tag with `version_map`
+    ///
+    /// The data tagged with another tag is still rejected.
+    pub fn legacy_version_map(mut self, version_map: AggregatedVersionMap) -> Self {
+        self.legacy_version_map = Some(version_map);
+        self
+    }
+
+    /// Deserialize CBOR bytes with versioning support
+    ///
+    /// See [`deserialize`](fn.deserialize.html).
+    pub fn deserialize<'de, T, VMR, AGG>(
+        &self,
+        input: &'de [u8],
+        resolver: &VMR,
+        aggregate: &AGG,
+    ) -> Result<T, DeserializeError>
+    where
+        T: DeserializeVersioned<'de>,
+        VMR: VersionGroupResolver,
+        AGG: AggregateVersionMap,
+    {
+        let value = match split_tag(input)? {
+            Some((tag, payload)) if tag == self.tag => {
+                let mut de = ::serde_cbor::Deserializer::from_slice(payload);
+                let mut error = None;
+                let visitor = DocumentVisitor {
+                    layout: Layout::Tuple,
+                    resolver,
+                    aggregate,
+                    error: &mut error,
+                    marker: PhantomData,
+                };
+                let value = match (&mut de).deserialize_tuple(2, visitor) {
+                    Ok(value) => value,
+                    // The visitor keeps the errors that do not come from the CBOR deserializer
+                    Err(err) => return Err(error.map_or(DeserializeError::Cbor(err), Into::into)),
+                };
+                de.end()?;
+                value
+            }
+            Some((found, _)) => {
+                return Err(DeserializeError::UnexpectedTag {
+                    expected: self.tag,
+                    found,
+                })
+            }
+            None => {
+                let version_map = self
+                    .legacy_version_map
+                    .as_ref()
+                    .ok_or(DeserializeError::MissingVersionTag(self.tag))?;
+                let mut de = ::serde_cbor::Deserializer::from_slice(input);
+                let value = DeserializeVersioned::deserialize_versioned(
+                    VersionedDeserializer::new(&mut de, version_map),
+                    version_map,
+                )
+                .map_err(Error::reduce)?;
+                de.end()?;
+                value
+            }
+        };
+        Ok(value)
+    }
+
+    /// Serialize a value formatted in CBOR with its version tag
+    ///
+    /// See [`serialize`](fn.serialize.html).
+    pub fn serialize<T: Serialize>(
+        &self,
+        value: &T,
+        uris: &VersionGroupURIs,
+    ) -> Result<Vec<u8>, ::serde_cbor::Error> {
+        let mut out = Vec::new();
+        write_tag(&mut out, self.tag);
+        ::serde_cbor::to_writer(&mut out, &(&**uris, value))?;
+        Ok(out)
+    }
+}
+
+impl UpgradeFormat for Options {
+    type DeserializeError = DeserializeError;
+    type SerializeError = ::serde_cbor::Error;
+
+    fn read_document<T, VMR, AGG>(
+        &self,
+        input: &[u8],
+        resolver: &VMR,
+        aggregate: &AGG,
+    ) -> Result<T, UpgradeError>
+    where
+        T: for<'de> DeserializeVersioned<'de>,
+        VMR: VersionGroupResolver,
+        AGG: AggregateVersionMap,
+    {
+        self.deserialize(input, resolver, aggregate)
+            .map_err(UpgradeError::Deserialize)
+    }
+
+    fn write_document<T: Serialize>(
+        &self,
+        value: &T,
+        uris: &VersionGroupURIs,
+    ) -> Result<Vec<u8>, UpgradeError> {
+        self.serialize(value, uris).map_err(UpgradeError::Serialize)
+    }
+}
+
+/// Write the initial bytes of the tag `tag`
+fn write_tag(out: &mut Vec<u8>, tag: u64) {
+    let major = MAJOR_TYPE_TAG << 5;
+    if tag < 24 {
+        out.push(major | tag as u8);
+    } else if let Ok(tag) = u8::try_from(tag) {
+        out.extend_from_slice(&[major | 24, tag]);
+    } else if let Ok(tag) = u16::try_from(tag) {
+        out.push(major | 25);
+        out.extend_from_slice(&tag.to_be_bytes());
+    } else if let Ok(tag) = u32::try_from(tag) {
+        out.push(major | 26);
+        out.extend_from_slice(&tag.to_be_bytes());
+    } else {
+        out.push(major | 27);
+        out.extend_from_slice(&tag.to_be_bytes());
+    }
+}
+
+/// Split the leading tag of a CBOR data item, if any, from its content
+fn split_tag(input: &[u8]) -> Result<Option<(u64, &[u8])>, ::serde_cbor::Error> {
+    let initial = match input.first() {
+        Some(initial) if initial >> 5 == MAJOR_TYPE_TAG => initial & 0x1f,
+        _ => return Ok(None),
+    };
+    let len = match initial {
+        0..=23 => return Ok(Some((u64::from(initial), &input[1..]))),
+        24 => 1,
+        25 => 2,
+        26 => 4,
+        27 => 8,
+        _ => return Err(serde::de::Error::custom("invalid tag in the CBOR data")),
+    };
+    if input.len() < 1 + len {
+        return Err(serde::de::Error::custom("truncated tag in the CBOR data"));
+    }
+    let mut bytes = [0; 8];
+    bytes[8 - len..].copy_from_slice(&input[1..=len]);
+    Ok(Some((u64::from_be_bytes(bytes), &input[1 + len..])))
+}
+
+/// Deserialize CBOR bytes with versioning support
+///
+/// # Generic Parameters
+/// - `T`: type to deserialize
+/// - `VMR`: resolver to find the version groups to use
+/// - `AGG`: aggregator to combine found version groups
+///
+/// # Parameters
+/// - `input`: CBOR bytes, the version tag wrapping an array of the version uris and the value
+/// - `resolver`: resolver to find the version groups to use
+/// - `aggregate`: aggregator to combine found version groups
+///
+/// # Returns
+/// The deserialized value or the error that occurred
+pub fn deserialize<'de, T, VMR, AGG>(
+    input: &'de [u8],
+    resolver: &VMR,
+    aggregate: &AGG,
+) -> Result<T, DeserializeError>
+where
+    T: DeserializeVersioned<'de>,
+    VMR: VersionGroupResolver,
+    AGG: AggregateVersionMap,
+{
+    Options::default().deserialize(input, resolver, aggregate)
+}
+
+/// Serialize a value formatted in CBOR with its version tag.
+///
+/// The version tag wraps an array of the uris and the value.
+///
+/// # Generic Parameters
+/// - `T`: type to serialize
+///
+/// # Parameters
+/// - `value`: The value to serialize
+/// - `uris`: The uris to serialize in the version header
+///
+/// # Returns
+/// The bytes or the error that occurred, if any.
+pub fn serialize<T: Serialize>(
+    value: &T,
+    uris: &VersionGroupURIs,
+) -> Result<Vec<u8>, ::serde_cbor::Error> {
+    Options::default().serialize(value, uris)
+}
+
+upgrade_functions!("CBOR", "cbor");
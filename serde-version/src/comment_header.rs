@@ -0,0 +1,64 @@
+//! Version header in the leading comments of a text document: `# <marker>: a:1, b:2`
+//!
+//! The leading comments are the first lines of the document that are empty or start
+//! with `#`.
+
+use crate::{InvalidVersionGroupURIError, VersionGroupURIs};
+use std::ops::Range;
+
+/// Find the version header in the leading comments of a document
+///
+/// # Returns
+/// The range of the uris in `input`, after the `# <marker>:` prefix and without the
+/// trailing spaces of the line
+pub(crate) fn find(input: &str, marker: &str) -> Option<Range<usize>> {
+    let mut offset = 0;
+    for line in input.split_inclusive('\n') {
+        let start = offset;
+        offset += line.len();
+        let comment = match line.trim_start().strip_prefix('#') {
+            Some(comment) => comment.trim_start(),
+            None if line.trim().is_empty() => continue,
+            None => return None,
+        };
+        if let Some(uris) = comment
+            .strip_prefix(marker)
+            .and_then(|comment| comment.strip_prefix(':'))
+        {
+            let uris_start = start + line.len() - uris.len();
+            return Some(uris_start..(start + line.trim_end().len()).max(uris_start));
+        }
+    }
+    None
+}
+
+/// Read the version header in the leading comments of a document
+pub(crate) fn read(
+    input: &str,
+    marker: &str,
+) -> Option<Result<VersionGroupURIs, InvalidVersionGroupURIError>> {
+    find(input, marker).map(|uris| parse_uris(&input[uris]))
+}
+
+/// Parse the comma separated uris of a version header
+pub(crate) fn parse_uris(uris: &str) -> Result<VersionGroupURIs, InvalidVersionGroupURIError> {
+    uris.split(',')
+        .map(str::trim)
+        .filter(|uri| !uri.is_empty())
+        .map(str::parse)
+        .collect()
+}
+
+/// Offset of the end of the leading comments of a document
+#[cfg_attr(not(feature = "csv-support"), allow(dead_code))]
+pub(crate) fn leading_comments_end(input: &str) -> usize {
+    let mut offset = 0;
+    for line in input.split_inclusive('\n') {
+        let trimmed = line.trim();
+        if !trimmed.is_empty() && !trimmed.starts_with('#') {
+            break;
+        }
+        offset += line.len();
+    }
+    offset
+}
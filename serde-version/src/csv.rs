@@ -0,0 +1,567 @@
+//! Serialization utilities for the CSV format
+//!
+//! The version header is a leading comment by default:
+//! ```text
+//! # serde-version: my.company:1
+//! name,size
+//! my config name,5
+//! ```
+//!
+//! [`Options::metadata_row`] reads it from a first row before the header row instead:
+//! ```text
+//! v,my.company:1
+//! name,size
+//! my config name,5
+//! ```
+//!
+//! And [`Options::version_column`] reads the version header of each row from a column, so
+//! rows written at different versions can live in the same file:
+//! ```text
+//! v,name,size
+//! my.company:1,my config name,5
+//! my.company:2,my other config,8
+//! ```
+//!
+//! Each row is deserialized as a record through the versioned machinery, so every row
+//! migrates to the current record type. With a header row, a record is read as a map of
+//! the column names to the cells, otherwise as a sequence of the cells.
+
+use crate::comment_header;
+use crate::version_map::AggregateVersionMap;
+use crate::{
+    AggregateVersionMapError, AggregatedVersionMap, DeserializeVersioned, Error,
+    InvalidVersionGroupURIError, VersionGroupResolver, VersionGroupURIs, VersionedDeserializer,
+};
+use failure::Fail;
+use serde::de::value::{MapDeserializer, SeqDeserializer};
+use serde::de::{self, IntoDeserializer, Visitor};
+use serde::{forward_to_deserialize_any, Serialize};
+use std::collections::HashMap;
+use std::iter;
+
+/// Key of the default metadata row and name of the default version column
+pub const DEFAULT_VERSION_HEADER_KEY: &str = "v";
+
+/// Marker of the default comment version header: `# serde-version: a:1, b:2`
+pub const DEFAULT_COMMENT_HEADER_MARKER: &str = "serde-version";
+
+/// Error of the cells deserializer
+pub type CellError = de::value::Error;
+
+/// Deserialization error
+#[derive(Debug, Fail)]
+pub enum DeserializeError {
+    #[fail(display = "{}", 0)]
+    Csv(::csv::Error),
+    #[fail(display = "Record at line {}: {}", line, error)]
+    De { line: u64, error: Error<CellError> },
+    #[fail(display = "{}", 0)]
+    AggregateError(AggregateVersionMapError),
+    #[fail(display = "Missing version header '{}'", 0)]
+    MissingVersionHeader(String),
+    #[fail(display = "Invalid version header: {}", 0)]
+    InvalidVersionHeader(InvalidVersionGroupURIError),
+    #[fail(
+        display = "Record at line {} has {} fields, expected {}",
+        line, found, expected
+    )]
+    UnequalLengths {
+        line: u64,
+        expected: usize,
+        found: usize,
+    },
+}
+impl_from_enum! {
+    DeserializeError::Csv => ::csv::Error,
+    DeserializeError::AggregateError => AggregateVersionMapError,
+    DeserializeError::InvalidVersionHeader => InvalidVersionGroupURIError,
+}
+
+/// Where the version header is written in a CSV document
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VersionHeaderLocation {
+    /// A comment in the leading comments of the document: `# <marker>: a:1, b:2`
+    ///
+    /// The leading lines starting with `#` are skipped, the cells starting with `#` are
+    /// quoted.
+    Comment { marker: String },
+    /// A first row before the header row: `<key>,a:1,b:2`
+    MetadataRow { key: String },
+    /// The column `name` of each row: `a:1, b:2`
+    ///
+    /// Without a header row, the version column is the first column.
+    Column { name: String },
+}
+
+impl VersionHeaderLocation {
+    fn missing(&self) -> DeserializeError {
+        DeserializeError::MissingVersionHeader(match self {
+            VersionHeaderLocation::Comment { marker } => format!("# {}:", marker),
+            VersionHeaderLocation::MetadataRow { key }
+            | VersionHeaderLocation::Column { name: key } => key.clone(),
+        })
+    }
+}
+
+/// Options of the CSV format
+///
+/// By default, the version header is a leading comment, the fields are delimited by commas
+/// and the document has a header row.
+///
+/// ```
+/// let options = serde_version::csv::Options::new()
+///     .version_column("version")
+///     .delimiter(b';');
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Options {
+    header: VersionHeaderLocation,
+    delimiter: u8,
+    has_headers: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            header: VersionHeaderLocation::Comment {
+                marker: DEFAULT_COMMENT_HEADER_MARKER.to_owned(),
+            },
+            delimiter: b',',
+            has_headers: true,
+        }
+    }
+}
+
+impl Options {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn header(&self) -> &VersionHeaderLocation {
+        &self.header
+    }
+
+    /// Write the version header in a comment: `# <marker>: a:1, b:2`
+    ///
+    /// See `DEFAULT_COMMENT_HEADER_MARKER`.
+    pub fn comment_header<M: Into<String>>(mut self, marker: M) -> Self {
+        self.header = VersionHeaderLocation::Comment {
+            marker: marker.into(),
+        };
+        self
+    }
+
+    /// Write the version header in a first row: `<key>,a:1,b:2`
+    ///
+    /// See `DEFAULT_VERSION_HEADER_KEY`.
+    pub fn metadata_row<K: Into<String>>(mut self, key: K) -> Self {
+        self.header = VersionHeaderLocation::MetadataRow { key: key.into() };
+        self
+    }
+
+    /// Write the version header of each row in the column `name`
+    ///
+    /// See `DEFAULT_VERSION_HEADER_KEY`.
+    pub fn version_column<N: Into<String>>(mut self, name: N) -> Self {
+        self.header = VersionHeaderLocation::Column { name: name.into() };
+        self
+    }
+
+    /// Delimit the fields with `delimiter`
+    pub fn delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Whether the document has a header row with the column names
+    pub fn has_headers(mut self, has_headers: bool) -> Self {
+        self.has_headers = has_headers;
+        self
+    }
+
+    /// Deserialize the records of a CSV string with versioning support
+    ///
+    /// See [`deserialize`](fn.deserialize.html).
+    pub fn deserialize<T, VMR, AGG>(
+        &self,
+        input: &str,
+        resolver: &VMR,
+        aggregate: &AGG,
+    ) -> Result<Vec<T>, DeserializeError>
+    where
+        T: for<'de> DeserializeVersioned<'de>,
+        VMR: VersionGroupResolver,
+        AGG: AggregateVersionMap,
+    {
+        // Only the leading comments are skipped, a row starting with `#` is a record
+        let body_start = match &self.header {
+            VersionHeaderLocation::Comment { .. } => comment_header::leading_comments_end(input),
+            _ => 0,
+        };
+        let skipped_lines = input[..body_start].matches('\n').count() as u64;
+        let mut reader = ::csv::ReaderBuilder::new()
+            .delimiter(self.delimiter)
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(&input.as_bytes()[body_start..]);
+        let mut records = reader.records();
+
+        let document_version_map = match &self.header {
+            VersionHeaderLocation::Comment { marker } => {
+                let uris =
+                    comment_header::read(input, marker).ok_or_else(|| self.header.missing())??;
+                Some(aggregate.aggregate_version_maps(&uris, resolver)?)
+            }
+            VersionHeaderLocation::MetadataRow { key } => {
+                let row = records.next().transpose()?;
+                let uris = match &row {
+                    Some(row) if row.get(0) == Some(key.as_str()) => row
+                        .iter()
+                        .skip(1)
+                        .map(str::trim)
+                        .filter(|uri| !uri.is_empty())
+                        .map(str::parse)
+                        .collect::<Result<VersionGroupURIs, _>>()?,
+                    _ => return Err(self.header.missing()),
+                };
+                Some(aggregate.aggregate_version_maps(&uris, resolver)?)
+            }
+            VersionHeaderLocation::Column { .. } => None,
+        };
+
+        let headers = if self.has_headers {
+            match records.next().transpose()? {
+                Some(headers) => Some(headers),
+                None => return Ok(Vec::new()),
+            }
+        } else {
+            None
+        };
+        let version_column = match (&self.header, &headers) {
+            (VersionHeaderLocation::Column { name }, Some(headers)) => Some(
+                headers
+                    .iter()
+                    .position(|column| column == name)
+                    .ok_or_else(|| self.header.missing())?,
+            ),
+            (VersionHeaderLocation::Column { .. }, None) => Some(0),
+            _ => None,
+        };
+
+        let mut expected_len = headers.as_ref().map(::csv::StringRecord::len);
+        let mut version_maps = HashMap::new();
+        let mut values = Vec::new();
+        for record in records {
+            let record = record?;
+            let line = record.position().map_or(0, ::csv::Position::line) + skipped_lines;
+            match expected_len {
+                Some(expected) if expected != record.len() => {
+                    return Err(DeserializeError::UnequalLengths {
+                        line,
+                        expected,
+                        found: record.len(),
+                    })
+                }
+                Some(_) => {}
+                None => expected_len = Some(record.len()),
+            }
+
+            let version_map = match (&document_version_map, version_column) {
+                (Some(version_map), _) => version_map,
+                (None, Some(column)) => {
+                    let header = &record[column];
+                    if !version_maps.contains_key(header) {
+                        let uris = comment_header::parse_uris(header)?;
+                        let version_map = aggregate.aggregate_version_maps(&uris, resolver)?;
+                        version_maps.insert(header.to_owned(), version_map);
+                    }
+                    &version_maps[header]
+                }
+                (None, None) => {
+                    unreachable!("the version header is either per document or per row")
+                }
+            };
+
+            let cells = record
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| Some(*index) != version_column)
+                .map(|(index, cell)| (index, Cell(cell)));
+            let value = match &headers {
+                Some(headers) => deserialize_record(
+                    MapDeserializer::new(cells.map(|(index, cell)| (&headers[index], cell))),
+                    version_map,
+                ),
+                None => deserialize_record(
+                    SeqDeserializer::new(cells.map(|(_, cell)| cell)),
+                    version_map,
+                ),
+            };
+            values.push(value.map_err(|error| DeserializeError::De { line, error })?);
+        }
+        Ok(values)
+    }
+
+    /// Serialize records formatted in CSV with their version header
+    ///
+    /// See [`serialize`](fn.serialize.html).
+    pub fn serialize<I>(&self, records: I, uris: &VersionGroupURIs) -> Result<String, ::csv::Error>
+    where
+        I: IntoIterator,
+        I::Item: Serialize,
+    {
+        // A leading `#` is quoted, so the first row is not read as a comment
+        let comment = match &self.header {
+            VersionHeaderLocation::Comment { .. } => Some(b'#'),
+            _ => None,
+        };
+        let mut body = ::csv::WriterBuilder::new()
+            .delimiter(self.delimiter)
+            .has_headers(self.has_headers)
+            .comment(comment)
+            .from_writer(Vec::new());
+        for record in records {
+            body.serialize(record)?;
+        }
+        let body = body.into_inner().map_err(|err| err.into_error())?;
+
+        let uris = uris.iter().map(ToString::to_string).collect::<Vec<_>>();
+        let mut writer = ::csv::WriterBuilder::new()
+            .delimiter(self.delimiter)
+            .flexible(true)
+            .from_writer(Vec::new());
+        match &self.header {
+            VersionHeaderLocation::Comment { marker } => {
+                let mut out = format!("# {}: {}\n", marker, uris.join(", ")).into_bytes();
+                out.extend(body);
+                return into_string(out);
+            }
+            VersionHeaderLocation::MetadataRow { key } => {
+                writer.write_record(iter::once(key).chain(&uris))?;
+                let mut out = writer.into_inner().map_err(|err| err.into_error())?;
+                out.extend(body);
+                return into_string(out);
+            }
+            VersionHeaderLocation::Column { name } => {
+                let header = uris.join(", ");
+                let mut reader = ::csv::ReaderBuilder::new()
+                    .delimiter(self.delimiter)
+                    .has_headers(false)
+                    .from_reader(&body[..]);
+                for (index, record) in reader.records().enumerate() {
+                    let column = if index == 0 && self.has_headers {
+                        name
+                    } else {
+                        &header
+                    };
+                    writer.write_record(iter::once(column.as_str()).chain(&record?))?;
+                }
+            }
+        }
+        into_string(writer.into_inner().map_err(|err| err.into_error())?)
+    }
+}
+
+/// Deserialize a record with a version map
+fn deserialize_record<'de, T, D>(
+    deserializer: D,
+    version_map: &AggregatedVersionMap,
+) -> Result<T, Error<CellError>>
+where
+    T: DeserializeVersioned<'de>,
+    D: de::Deserializer<'de, Error = CellError>,
+{
+    DeserializeVersioned::deserialize_versioned(
+        VersionedDeserializer::new(deserializer, version_map),
+        version_map,
+    )
+    .map_err(Error::reduce)
+}
+
+fn into_string(bytes: Vec<u8>) -> Result<String, ::csv::Error> {
+    String::from_utf8(bytes).map_err(|err| {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, err.utf8_error()).into()
+    })
+}
+
+/// Deserializer of a cell, parsing the scalars from its text like the `csv` crate does
+struct Cell<'a>(&'a str);
+
+impl<'a> Cell<'a> {
+    fn parse<T: std::str::FromStr>(&self, expected: &str) -> Result<T, CellError> {
+        self.0
+            .trim()
+            .parse()
+            .map_err(|_| de::Error::invalid_value(de::Unexpected::Str(self.0), &expected))
+    }
+}
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident($expected:expr),)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CellError> {
+                visitor.$visit(self.parse($expected)?)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Cell<'de> {
+    type Error = CellError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CellError> {
+        if let Ok(value) = self.0.parse() {
+            visitor.visit_bool(value)
+        } else if let Ok(value) = self.0.parse() {
+            visitor.visit_u64(value)
+        } else if let Ok(value) = self.0.parse() {
+            visitor.visit_i64(value)
+        } else if let Ok(value) = self.0.parse() {
+            visitor.visit_f64(value)
+        } else {
+            visitor.visit_borrowed_str(self.0)
+        }
+    }
+
+    deserialize_parsed! {
+        deserialize_bool => visit_bool("a boolean"),
+        deserialize_i8 => visit_i8("an i8"),
+        deserialize_i16 => visit_i16("an i16"),
+        deserialize_i32 => visit_i32("an i32"),
+        deserialize_i64 => visit_i64("an i64"),
+        deserialize_u8 => visit_u8("a u8"),
+        deserialize_u16 => visit_u16("a u16"),
+        deserialize_u32 => visit_u32("a u32"),
+        deserialize_u64 => visit_u64("a u64"),
+        deserialize_f32 => visit_f32("an f32"),
+        deserialize_f64 => visit_f64("an f64"),
+        deserialize_char => visit_char("a character"),
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CellError> {
+        visitor.visit_borrowed_str(self.0)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CellError> {
+        visitor.visit_borrowed_str(self.0)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CellError> {
+        visitor.visit_borrowed_bytes(self.0.as_bytes())
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CellError> {
+        visitor.visit_borrowed_bytes(self.0.as_bytes())
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CellError> {
+        if self.0.is_empty() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CellError> {
+        if self.0.is_empty() {
+            visitor.visit_unit()
+        } else {
+            Err(de::Error::invalid_value(
+                de::Unexpected::Str(self.0),
+                &"an empty cell",
+            ))
+        }
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, CellError> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, CellError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, CellError> {
+        visitor.visit_enum(self.0.into_deserializer())
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CellError> {
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        seq tuple tuple_struct map struct identifier
+    }
+}
+
+impl<'de> IntoDeserializer<'de, CellError> for Cell<'de> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+/// Deserialize the records of a CSV string with versioning support
+///
+/// The version header is read from the leading comments of the document.
+///
+/// # Generic Parameters
+/// - `T`: type of the records
+/// - `VMR`: resolver to find the version groups to use
+/// - `AGG`: aggregator to combine found version groups
+///
+/// # Parameters
+/// - `input`: CSV string, the version header comment, the header row, then the records
+/// - `resolver`: resolver to find the version groups to use
+/// - `aggregate`: aggregator to combine found version groups
+///
+/// # Returns
+/// The deserialized records or the error that occurred
+pub fn deserialize<T, VMR, AGG>(
+    input: &str,
+    resolver: &VMR,
+    aggregate: &AGG,
+) -> Result<Vec<T>, DeserializeError>
+where
+    T: for<'de> DeserializeVersioned<'de>,
+    VMR: VersionGroupResolver,
+    AGG: AggregateVersionMap,
+{
+    Options::default().deserialize(input, resolver, aggregate)
+}
+
+/// Serialize records formatted in CSV with their version header.
+///
+/// The version header will be written in a leading comment, then the header row and
+/// the records.
+///
+/// # Generic Parameters
+/// - `I`: records to serialize
+///
+/// # Parameters
+/// - `records`: The records to serialize
+/// - `uris`: The uris to serialize in the version header
+///
+/// # Returns
+/// The string or the error that occurred, if any.
+pub fn serialize<I>(records: I, uris: &VersionGroupURIs) -> Result<String, ::csv::Error>
+where
+    I: IntoIterator,
+    I::Item: Serialize,
+{
+    Options::default().serialize(records, uris)
+}
@@ -83,4 +83,8 @@ impl<'de, D: Deserializer<'de>, VM: VersionMap> Deserializer<'de>
                          name => &'static str,
                          variants => &'static [&'static str]);
     forward_deserialize!(deserialize_ignored_any);
+
+    fn is_human_readable(&self) -> bool {
+        self.deserializer.is_human_readable()
+    }
 }
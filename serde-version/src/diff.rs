@@ -0,0 +1,416 @@
+//! Dry-run migrations: what an upgrade would change in a document
+//!
+//! [`diff`] deserializes a document with versioning support, serializes it again with the
+//! current version header and compares both documents without writing anything. It works with
+//! the options of the text formats, `toml`, `json`, `yaml` and `ron`:
+//! ```rust,ignore
+//! let options = serde_version::toml::Options::new();
+//! let diff = diff::<Config, _, _, _>(&options, &input, &resolver, &(), &current_uris)?;
+//! for change in &diff.changes {
+//!     println!("{}", change);
+//! }
+//! print!("{}", diff.unified_diff);
+//! ```
+//!
+//! The changes are listed by path in the document: `server.port` for the key `port` of the
+//! table `server`, `servers[1]` for the second element of the array `servers`.
+
+use crate::upgrade::{UpgradeError, UpgradeFormat};
+use crate::version_map::AggregateVersionMap;
+use crate::{DeserializeVersioned, VersionGroupResolver, VersionGroupURIs};
+use serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::{ser, Serialize};
+use std::fmt;
+
+/// Error raised while computing the diff of a migration
+///
+/// A diff is an upgrade that writes nothing, so it fails like one.
+pub type DiffError<D, S> = UpgradeError<D, S>;
+
+/// A text format whose migrations can be compared, see [`diff`]
+pub trait DiffFormat: UpgradeFormat {
+    /// Read a document as a generic value
+    fn read_value(&self, input: &str) -> Result<Value, Self::DeserializeError>;
+}
+
+/// Compute what the migration of a document to the current version changes
+///
+/// The document is deserialized with versioning support, then serialized again with
+/// `current_uris` as version header. Nothing is written: the changed values and the unified
+/// diff of both documents are returned, to be confirmed before an upgrade.
+///
+/// # Generic Parameters
+/// - `T`: type of the document
+/// - `F`: format of the document
+/// - `VMR`: resolver to find the version groups to use
+/// - `AGG`: aggregator to combine found version groups
+///
+/// # Parameters
+/// - `format`: options of the format of the document, like `toml::Options`
+/// - `input`: text of the document to migrate
+/// - `resolver`: resolver to find the version groups to use
+/// - `aggregate`: aggregator to combine found version groups
+/// - `current_uris`: The uris to serialize in the version header of the migrated document
+///
+/// # Returns
+/// The diff of the migration or the error that occurred
+pub fn diff<T, F, VMR, AGG>(
+    format: &F,
+    input: &str,
+    resolver: &VMR,
+    aggregate: &AGG,
+    current_uris: &VersionGroupURIs,
+) -> Result<MigrationDiff, DiffError<F::DeserializeError, F::SerializeError>>
+where
+    T: Serialize + for<'de> DeserializeVersioned<'de>,
+    F: DiffFormat,
+    F::DeserializeError: fmt::Display,
+    F::SerializeError: ser::Error,
+    VMR: VersionGroupResolver,
+    AGG: AggregateVersionMap,
+{
+    let value: T = format.read_document(input.as_bytes(), resolver, aggregate)?;
+    let output = format.write_document(&value, current_uris)?;
+    let output = crate::upgrade::as_str(&output)?;
+    let old = format
+        .read_value(input)
+        .map_err(UpgradeError::Deserialize)?;
+    let new = format
+        .read_value(output)
+        .map_err(|err| UpgradeError::Serialize(ser::Error::custom(err)))?;
+    Ok(MigrationDiff::new(input, output, &old, &new))
+}
+
+/// Value of a document, whatever its format
+///
+/// The entries of the maps are in the order of the document.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Integer(i128),
+    Float(f64),
+    String(String),
+    Bytes(Vec<u8>),
+    Seq(Vec<Value>),
+    Map(Vec<(String, Value)>),
+}
+
+impl Value {
+    fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Map(entries) => entries
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Null => write!(f, "null"),
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::Integer(value) => write!(f, "{}", value),
+            Value::Float(value) => write!(f, "{:?}", value),
+            Value::String(value) => write!(f, "{:?}", value),
+            Value::Bytes(value) => write!(f, "b\"{}\"", value.escape_ascii()),
+            Value::Seq(values) => {
+                write!(f, "[")?;
+                for (index, value) in values.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")
+            }
+            Value::Map(entries) => {
+                write!(f, "{{")?;
+                for (index, (key, value)) in entries.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, " {} = {}", key, value)?;
+                }
+                if !entries.is_empty() {
+                    write!(f, " ")?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("any value")
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<Value, E> {
+        Ok(Value::Bool(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Value, E> {
+        Ok(Value::Integer(v.into()))
+    }
+
+    fn visit_i128<E: de::Error>(self, v: i128) -> Result<Value, E> {
+        Ok(Value::Integer(v))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Value, E> {
+        Ok(Value::Integer(v.into()))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Value, E> {
+        Ok(Value::Float(v))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Value, E> {
+        Ok(Value::String(v.to_owned()))
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Value, E> {
+        Ok(Value::String(v))
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Value, E> {
+        Ok(Value::Bytes(v.to_owned()))
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Value, E> {
+        Ok(Value::Bytes(v))
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        Value::deserialize(deserializer)
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_newtype_struct<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<Value, D::Error> {
+        Value::deserialize(deserializer)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        let mut values = Vec::new();
+        while let Some(value) = seq.next_element()? {
+            values.push(value);
+        }
+        Ok(Value::Seq(values))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+        let mut entries = Vec::new();
+        while let Some((key, value)) = map.next_entry::<Value, Value>()? {
+            let key = match key {
+                Value::String(key) => key,
+                key => key.to_string(),
+            };
+            entries.push((key, value));
+        }
+        Ok(Value::Map(entries))
+    }
+}
+
+/// Change of a value at a path of the document
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    Added {
+        path: String,
+        value: Value,
+    },
+    Removed {
+        path: String,
+        value: Value,
+    },
+    Changed {
+        path: String,
+        old: Value,
+        new: Value,
+    },
+}
+
+impl Change {
+    /// Path of the changed value in the document
+    pub fn path(&self) -> &str {
+        match self {
+            Change::Added { path, .. }
+            | Change::Removed { path, .. }
+            | Change::Changed { path, .. } => path,
+        }
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Change::Added { path, value } => write!(f, "+ {} = {}", path, value),
+            Change::Removed { path, value } => write!(f, "- {} = {}", path, value),
+            Change::Changed { path, old, new } => write!(f, "~ {}: {} -> {}", path, old, new),
+        }
+    }
+}
+
+/// What a migration changes in a document
+#[derive(Debug, Clone, PartialEq)]
+pub struct MigrationDiff {
+    /// The changed values, in the order of the documents
+    pub changes: Vec<Change>,
+    /// Unified diff of the text of the documents, empty if they are the same
+    pub unified_diff: String,
+}
+
+impl MigrationDiff {
+    /// Compare the original document and the migrated one
+    ///
+    /// # Parameters
+    /// - `old_text`, `new_text`: text of the documents, for the unified diff
+    /// - `old`, `new`: values of the documents, for the changes
+    pub fn new(old_text: &str, new_text: &str, old: &Value, new: &Value) -> Self {
+        let mut changes = Vec::new();
+        diff_values(&mut String::new(), old, new, &mut changes);
+        let unified_diff = if old_text == new_text {
+            String::new()
+        } else {
+            ::similar::TextDiff::from_lines(old_text, new_text)
+                .unified_diff()
+                .header("original", "migrated")
+                .to_string()
+        };
+        Self {
+            changes,
+            unified_diff,
+        }
+    }
+
+    /// Whether the migration changes any value
+    pub fn has_changes(&self) -> bool {
+        !self.changes.is_empty()
+    }
+
+    /// Paths of the added values
+    pub fn added(&self) -> impl Iterator<Item = &str> {
+        self.changes
+            .iter()
+            .filter(|change| matches!(change, Change::Added { .. }))
+            .map(Change::path)
+    }
+
+    /// Paths of the removed values
+    pub fn removed(&self) -> impl Iterator<Item = &str> {
+        self.changes
+            .iter()
+            .filter(|change| matches!(change, Change::Removed { .. }))
+            .map(Change::path)
+    }
+
+    /// Paths of the changed values
+    pub fn changed(&self) -> impl Iterator<Item = &str> {
+        self.changes
+            .iter()
+            .filter(|change| matches!(change, Change::Changed { .. }))
+            .map(Change::path)
+    }
+}
+
+fn diff_values(path: &mut String, old: &Value, new: &Value, changes: &mut Vec<Change>) {
+    match (old, new) {
+        (Value::Map(old_entries), Value::Map(_)) => {
+            for (key, old_value) in old_entries {
+                let len = push_key(path, key);
+                match new.get(key) {
+                    Some(new_value) => diff_values(path, old_value, new_value, changes),
+                    None => changes.push(Change::Removed {
+                        path: path.clone(),
+                        value: old_value.clone(),
+                    }),
+                }
+                path.truncate(len);
+            }
+            if let Value::Map(new_entries) = new {
+                for (key, new_value) in new_entries {
+                    if old.get(key).is_none() {
+                        let len = push_key(path, key);
+                        changes.push(Change::Added {
+                            path: path.clone(),
+                            value: new_value.clone(),
+                        });
+                        path.truncate(len);
+                    }
+                }
+            }
+        }
+        (Value::Seq(old_values), Value::Seq(new_values)) => {
+            for index in 0..old_values.len().max(new_values.len()) {
+                let len = path.len();
+                path.push_str(&format!("[{}]", index));
+                match (old_values.get(index), new_values.get(index)) {
+                    (Some(old_value), Some(new_value)) => {
+                        diff_values(path, old_value, new_value, changes)
+                    }
+                    (Some(old_value), None) => changes.push(Change::Removed {
+                        path: path.clone(),
+                        value: old_value.clone(),
+                    }),
+                    (None, Some(new_value)) => changes.push(Change::Added {
+                        path: path.clone(),
+                        value: new_value.clone(),
+                    }),
+                    (None, None) => {}
+                }
+                path.truncate(len);
+            }
+        }
+        // An unchanged NaN is not a change
+        (Value::Float(old), Value::Float(new)) if old.is_nan() && new.is_nan() => {}
+        (old, new) if old != new => changes.push(Change::Changed {
+            path: path.clone(),
+            old: old.clone(),
+            new: new.clone(),
+        }),
+        _ => {}
+    }
+}
+
+/// Append a key to a path, quoted if it is not a bare key
+///
+/// Returns the length of the path before the key.
+fn push_key(path: &mut String, key: &str) -> usize {
+    let len = path.len();
+    if !path.is_empty() {
+        path.push('.');
+    }
+    let bare = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if bare {
+        path.push_str(key);
+    } else {
+        path.push_str(&format!("{:?}", key));
+    }
+    len
+}
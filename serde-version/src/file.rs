@@ -0,0 +1,363 @@
+//! Self-identifying file envelope
+//!
+//! An envelope tells which format its document is written in, so a file can be loaded
+//! without knowing its format beforehand:
+//!
+//! ```text
+//! envelope := magic version format document
+//! magic    := 53 56 45 52    "SVER"
+//! version  := u8             version of the envelope layout, ENVELOPE_VERSION
+//! format   := u8             format of the document, see PayloadFormat
+//! document := the value and its version header, written by the module of the format
+//! ```
+//!
+//! The document is read and written by the module of its format with its default options:
+//! the version header is a root field in Json, a binary version header in bincode, etc.
+//! The formats whose feature gate is not enabled are rejected with
+//! `LoadError::UnsupportedFormat`.
+
+use crate::version_map::AggregateVersionMap;
+use crate::{DeserializeVersioned, VersionGroupResolver, VersionGroupURIs};
+use failure::Fail;
+use serde::Serialize;
+use std::path::Path;
+
+/// First bytes of an envelope
+pub const MAGIC: [u8; 4] = *b"SVER";
+
+/// Version of the envelope layout written by this crate
+pub const ENVELOPE_VERSION: u8 = 1;
+
+/// Format of the payload of an envelope
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PayloadFormat {
+    Toml = 1,
+    Json = 2,
+    Yaml = 3,
+    Ron = 4,
+    Bincode = 5,
+    Postcard = 6,
+    MsgPack = 7,
+    Cbor = 8,
+}
+
+impl PayloadFormat {
+    /// Format with the identifier `tag` in an envelope
+    pub fn from_tag(tag: u8) -> Option<Self> {
+        Some(match tag {
+            1 => PayloadFormat::Toml,
+            2 => PayloadFormat::Json,
+            3 => PayloadFormat::Yaml,
+            4 => PayloadFormat::Ron,
+            5 => PayloadFormat::Bincode,
+            6 => PayloadFormat::Postcard,
+            7 => PayloadFormat::MsgPack,
+            8 => PayloadFormat::Cbor,
+            _ => return None,
+        })
+    }
+
+    /// Identifier of the format in an envelope
+    pub fn tag(self) -> u8 {
+        self as u8
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PayloadFormat::Toml => "toml",
+            PayloadFormat::Json => "json",
+            PayloadFormat::Yaml => "yaml",
+            PayloadFormat::Ron => "ron",
+            PayloadFormat::Bincode => "bincode",
+            PayloadFormat::Postcard => "postcard",
+            PayloadFormat::MsgPack => "msgpack",
+            PayloadFormat::Cbor => "cbor",
+        }
+    }
+
+    /// Whether the feature gate of the format is enabled
+    pub fn is_supported(self) -> bool {
+        match self {
+            PayloadFormat::Toml => cfg!(feature = "toml-support"),
+            PayloadFormat::Json => cfg!(feature = "json-support"),
+            PayloadFormat::Yaml => cfg!(feature = "yaml-support"),
+            PayloadFormat::Ron => cfg!(feature = "ron-support"),
+            PayloadFormat::Bincode => cfg!(feature = "bincode-support"),
+            PayloadFormat::Postcard => cfg!(feature = "postcard-support"),
+            PayloadFormat::MsgPack => cfg!(feature = "msgpack-support"),
+            PayloadFormat::Cbor => cfg!(feature = "cbor-support"),
+        }
+    }
+}
+
+impl std::fmt::Display for PayloadFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Error raised while loading an envelope
+#[derive(Debug, Fail)]
+pub enum LoadError {
+    #[fail(display = "{}", 0)]
+    Io(std::io::Error),
+    #[fail(display = "Not a serde-version envelope: the magic bytes are missing")]
+    NotAnEnvelope,
+    #[fail(display = "Unexpected end of the envelope")]
+    UnexpectedEnd,
+    #[fail(display = "Unsupported envelope version {}", 0)]
+    UnsupportedEnvelopeVersion(u8),
+    #[fail(display = "Unknown payload format {}", 0)]
+    UnknownFormat(u8),
+    #[fail(
+        display = "The {} payload format is not supported, its feature gate is disabled",
+        0
+    )]
+    UnsupportedFormat(PayloadFormat),
+    /// Error of the module of the format, like `json::DeserializeError`
+    #[fail(display = "Invalid {} payload: {}", format, error)]
+    Payload {
+        format: PayloadFormat,
+        error: failure::Error,
+    },
+}
+impl_from_enum! {
+    LoadError::Io => std::io::Error,
+}
+
+/// Error raised while saving an envelope
+#[derive(Debug, Fail)]
+pub enum SaveError {
+    #[fail(display = "{}", 0)]
+    Io(std::io::Error),
+    #[fail(
+        display = "The {} payload format is not supported, its feature gate is disabled",
+        0
+    )]
+    UnsupportedFormat(PayloadFormat),
+    /// Error of the module of the format, like `json::Error`
+    #[fail(display = "Failed to serialize the {} payload: {}", format, error)]
+    Payload {
+        format: PayloadFormat,
+        error: failure::Error,
+    },
+}
+impl_from_enum! {
+    SaveError::Io => std::io::Error,
+}
+
+/// Load the envelope in the file at `path`
+///
+/// The versions groups of the header are merged in order, see
+/// [`from_slice`](fn.from_slice.html) to aggregate them otherwise.
+///
+/// # Generic Parameters
+/// - `T`: type to deserialize
+/// - `P`: path of the file
+/// - `VMR`: resolver to find the version groups to use
+///
+/// # Parameters
+/// - `path`: path of the file
+/// - `resolver`: resolver to find the version groups to use
+///
+/// # Returns
+/// The deserialized value or the error that occurred
+pub fn load<T, P, VMR>(path: P, resolver: &VMR) -> Result<T, LoadError>
+where
+    T: for<'de> DeserializeVersioned<'de>,
+    P: AsRef<Path>,
+    VMR: VersionGroupResolver,
+{
+    from_slice(&std::fs::read(path)?, resolver, &())
+}
+
+/// Deserialize an envelope
+///
+/// # Generic Parameters
+/// - `T`: type to deserialize
+/// - `VMR`: resolver to find the version groups to use
+/// - `AGG`: aggregator to combine found version groups
+///
+/// # Parameters
+/// - `input`: the envelope, see the module documentation for its layout
+/// - `resolver`: resolver to find the version groups to use
+/// - `aggregate`: aggregator to combine found version groups
+///
+/// # Returns
+/// The deserialized value or the error that occurred
+pub fn from_slice<'de, T, VMR, AGG>(
+    input: &'de [u8],
+    resolver: &VMR,
+    aggregate: &AGG,
+) -> Result<T, LoadError>
+where
+    T: DeserializeVersioned<'de>,
+    VMR: VersionGroupResolver,
+    AGG: AggregateVersionMap,
+{
+    let input = input
+        .strip_prefix(&MAGIC[..])
+        .ok_or(LoadError::NotAnEnvelope)?;
+    let (version, format, document) = match input {
+        [version, format, document @ ..] => (*version, *format, document),
+        _ => return Err(LoadError::UnexpectedEnd),
+    };
+    if version != ENVELOPE_VERSION {
+        return Err(LoadError::UnsupportedEnvelopeVersion(version));
+    }
+    let format = PayloadFormat::from_tag(format).ok_or(LoadError::UnknownFormat(format))?;
+    let deserialize = deserializer(format).ok_or(LoadError::UnsupportedFormat(format))?;
+    deserialize(document, resolver, aggregate).map_err(|error| LoadError::Payload { format, error })
+}
+
+type DeserializeFn<'de, T, VMR, AGG> = fn(&'de [u8], &VMR, &AGG) -> Result<T, failure::Error>;
+
+/// Deserializer of the module of `format`, if its feature gate is enabled
+fn deserializer<'de, T, VMR, AGG>(format: PayloadFormat) -> Option<DeserializeFn<'de, T, VMR, AGG>>
+where
+    T: DeserializeVersioned<'de>,
+    VMR: VersionGroupResolver,
+    AGG: AggregateVersionMap,
+{
+    match format {
+        #[cfg(feature = "toml-support")]
+        PayloadFormat::Toml => Some(|input, resolver, aggregate| {
+            let input = std::str::from_utf8(input)?;
+            Ok(crate::toml::deserialize(input, resolver, aggregate)?)
+        }),
+        #[cfg(feature = "json-support")]
+        PayloadFormat::Json => Some(|input, resolver, aggregate| {
+            let input = std::str::from_utf8(input)?;
+            Ok(crate::json::deserialize(input, resolver, aggregate)?)
+        }),
+        #[cfg(feature = "yaml-support")]
+        PayloadFormat::Yaml => Some(|input, resolver, aggregate| {
+            let input = std::str::from_utf8(input)?;
+            Ok(crate::yaml::deserialize(input, resolver, aggregate)?)
+        }),
+        #[cfg(feature = "ron-support")]
+        PayloadFormat::Ron => Some(|input, resolver, aggregate| {
+            let input = std::str::from_utf8(input)?;
+            Ok(crate::ron::deserialize(input, resolver, aggregate)?)
+        }),
+        #[cfg(feature = "bincode-support")]
+        PayloadFormat::Bincode => Some(|input, resolver, aggregate| {
+            Ok(crate::binary::bincode::deserialize(
+                input, resolver, aggregate,
+            )?)
+        }),
+        #[cfg(feature = "postcard-support")]
+        PayloadFormat::Postcard => Some(|input, resolver, aggregate| {
+            Ok(crate::binary::postcard::deserialize(
+                input, resolver, aggregate,
+            )?)
+        }),
+        #[cfg(feature = "msgpack-support")]
+        PayloadFormat::MsgPack => Some(|input, resolver, aggregate| {
+            Ok(crate::msgpack::deserialize(input, resolver, aggregate)?)
+        }),
+        #[cfg(feature = "cbor-support")]
+        PayloadFormat::Cbor => Some(|input, resolver, aggregate| {
+            Ok(crate::cbor::deserialize(input, resolver, aggregate)?)
+        }),
+        // Reached by the formats whose feature gate is disabled
+        #[allow(unreachable_patterns)]
+        _ => None,
+    }
+}
+
+/// Save a value in an envelope, in the file at `path`
+///
+/// The file is written atomically, see `upgrade::write_atomic`.
+///
+/// # Generic Parameters
+/// - `T`: type to serialize
+/// - `P`: path of the file
+///
+/// # Parameters
+/// - `path`: path of the file, replaced if it exists
+/// - `value`: The value to serialize
+/// - `format`: The format of the document
+/// - `uris`: The version group uris of the version header
+///
+/// # Returns
+/// The error that occurred, if any.
+pub fn save<T, P>(
+    path: P,
+    value: &T,
+    format: PayloadFormat,
+    uris: &VersionGroupURIs,
+) -> Result<(), SaveError>
+where
+    T: Serialize,
+    P: AsRef<Path>,
+{
+    crate::upgrade::write_atomic(path, &to_vec(value, format, uris)?)?;
+    Ok(())
+}
+
+/// Serialize a value in an envelope
+///
+/// # Generic Parameters
+/// - `T`: type to serialize
+///
+/// # Parameters
+/// - `value`: The value to serialize
+/// - `format`: The format of the document
+/// - `uris`: The version group uris of the version header
+///
+/// # Returns
+/// The bytes or the error that occurred, if any.
+pub fn to_vec<T: Serialize>(
+    value: &T,
+    format: PayloadFormat,
+    uris: &VersionGroupURIs,
+) -> Result<Vec<u8>, SaveError> {
+    let serialize = serializer(format).ok_or(SaveError::UnsupportedFormat(format))?;
+    let mut out = MAGIC.to_vec();
+    out.push(ENVELOPE_VERSION);
+    out.push(format.tag());
+    out.extend(serialize(value, uris).map_err(|error| SaveError::Payload { format, error })?);
+    Ok(out)
+}
+
+type SerializeFn<T> = fn(&T, &VersionGroupURIs) -> Result<Vec<u8>, failure::Error>;
+
+/// Serializer of the module of `format`, if its feature gate is enabled
+fn serializer<T: Serialize>(format: PayloadFormat) -> Option<SerializeFn<T>> {
+    match format {
+        #[cfg(feature = "toml-support")]
+        PayloadFormat::Toml => {
+            Some(|value, uris| Ok(crate::toml::serialize(value, uris)?.into_bytes()))
+        }
+        #[cfg(feature = "json-support")]
+        PayloadFormat::Json => {
+            Some(|value, uris| Ok(crate::json::serialize(value, uris)?.into_bytes()))
+        }
+        #[cfg(feature = "yaml-support")]
+        PayloadFormat::Yaml => {
+            Some(|value, uris| Ok(crate::yaml::serialize(value, uris)?.into_bytes()))
+        }
+        #[cfg(feature = "ron-support")]
+        PayloadFormat::Ron => {
+            Some(|value, uris| Ok(crate::ron::serialize(value, uris)?.into_bytes()))
+        }
+        #[cfg(feature = "bincode-support")]
+        PayloadFormat::Bincode => Some(|value, uris| {
+            let header = crate::binary::BinaryHeader::Groups(uris.clone());
+            Ok(crate::binary::bincode::serialize(value, &header)?)
+        }),
+        #[cfg(feature = "postcard-support")]
+        PayloadFormat::Postcard => Some(|value, uris| {
+            let header = crate::binary::BinaryHeader::Groups(uris.clone());
+            Ok(crate::binary::postcard::serialize(value, &header)?)
+        }),
+        #[cfg(feature = "msgpack-support")]
+        PayloadFormat::MsgPack => Some(|value, uris| Ok(crate::msgpack::serialize(value, uris)?)),
+        #[cfg(feature = "cbor-support")]
+        PayloadFormat::Cbor => Some(|value, uris| Ok(crate::cbor::serialize(value, uris)?)),
+        // Reached by the formats whose feature gate is disabled
+        #[allow(unreachable_patterns)]
+        _ => None,
+    }
+}
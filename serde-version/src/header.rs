@@ -0,0 +1,460 @@
+//! Documents starting with their version header, deserialized in a single pass
+//!
+//! The version header is read from the root of the document, then the value is deserialized
+//! with the aggregated version map, without an intermediate representation of the document.
+//! It works with the deserializer of any self-describing format, see
+//! [`deserialize_with_header`] and [`VersionHeader`]:
+//! ```
+//! # use serde_version::version_group_resolver_static;
+//! # use serde_version_derive::DeserializeVersioned;
+//! # #[derive(serde::Deserialize, DeserializeVersioned)]
+//! # #[versions(v(index = 1, self))]
+//! # struct Config { name: String }
+//! # version_group_resolver_static! {
+//! #     pub VERSIONS = { ("my.company", "1") => { Config => 1, }, }
+//! # }
+//! let input = r#"(v: ["my.company:1"], name: "my config name")"#;
+//! let mut de = ron::de::Deserializer::from_str(input).unwrap();
+//! let config: Config = serde_version::deserialize_with_header(&mut de, &*VERSIONS, &()).unwrap();
+//! # assert_eq!("my config name", config.name);
+//! ```
+
+use crate::version_map::AggregateVersionMap;
+use crate::{
+    AggregateVersionMapError, AggregatedVersionMap, DeserializeVersioned, Error,
+    InvalidVersionError, VersionGroupResolver, VersionGroupURI, VersionGroupURIs, VersionMap,
+    VersionedDeserializer,
+};
+use serde::de::{DeserializeSeed, IgnoredAny, MapAccess, SeqAccess, Unexpected, Visitor};
+use serde::ser::SerializeMap;
+use serde::{Deserializer, Serialize, Serializer};
+use std::marker::PhantomData;
+
+/// Key of the default version header, in the root map
+pub const DEFAULT_VERSION_HEADER_KEY: &str = "v";
+
+/// Where the version header is in the root of a document
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VersionHeader {
+    /// The first entry of the root map under `key`, or the first element of a two
+    /// elements sequence of the version header and the value
+    KeyOrSequence { key: String },
+    /// The first entry of the root map under `key`, the other entries belong to the value
+    LeadingField { key: String },
+    /// A root map of the version header under `header_key`, then the value under `data_key`
+    Envelope {
+        header_key: String,
+        data_key: String,
+    },
+    /// A two elements sequence of the version header and the value
+    Sequence,
+}
+
+impl Default for VersionHeader {
+    fn default() -> Self {
+        VersionHeader::KeyOrSequence {
+            key: DEFAULT_VERSION_HEADER_KEY.to_owned(),
+        }
+    }
+}
+
+impl VersionHeader {
+    /// Deserialize a document with its version header
+    ///
+    /// See [`deserialize_with_header`](fn.deserialize_with_header.html).
+    pub fn deserialize<'de, T, D, VMR, AGG>(
+        &self,
+        deserializer: D,
+        resolver: &VMR,
+        aggregate: &AGG,
+    ) -> Result<T, DeserializeError<D::Error>>
+    where
+        T: DeserializeVersioned<'de>,
+        D: Deserializer<'de>,
+        VMR: VersionGroupResolver,
+        AGG: AggregateVersionMap,
+    {
+        let layout = match self {
+            VersionHeader::KeyOrSequence { key } => Layout::LeadingFieldOrTuple { key },
+            VersionHeader::LeadingField { key } => Layout::LeadingField { key },
+            VersionHeader::Envelope {
+                header_key,
+                data_key,
+            } => Layout::Envelope {
+                header_key,
+                data_key,
+            },
+            VersionHeader::Sequence => Layout::Tuple,
+        };
+        let mut error = None;
+        let visitor = DocumentVisitor {
+            layout,
+            resolver,
+            aggregate,
+            error: &mut error,
+            marker: PhantomData,
+        };
+        let value = match layout {
+            Layout::Tuple => deserializer.deserialize_tuple(2, visitor),
+            _ => deserializer.deserialize_any(visitor),
+        };
+        // The visitor keeps the errors that do not come from the deserializer
+        value.map_err(|err| {
+            error.map_or(
+                DeserializeError::De(Error::DeserializeError(err)),
+                Into::into,
+            )
+        })
+    }
+}
+
+/// Deserialization error of a document with its version header
+#[derive(Debug)]
+pub enum DeserializeError<E> {
+    De(Error<E>),
+    AggregateError(AggregateVersionMapError),
+    MissingVersionHeader(String),
+}
+
+impl<E> From<HeaderError> for DeserializeError<E> {
+    fn from(error: HeaderError) -> Self {
+        match error {
+            HeaderError::MissingVersionHeader(key) => DeserializeError::MissingVersionHeader(key),
+            HeaderError::AggregateError(err) => DeserializeError::AggregateError(err),
+            HeaderError::InvalidVersionError(err) => {
+                DeserializeError::De(Error::InvalidVersionError(err))
+            }
+            HeaderError::Message(message) => DeserializeError::De(Error::Message(message)),
+        }
+    }
+}
+
+impl<E> std::fmt::Display for DeserializeError<E>
+where
+    E: std::fmt::Display,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            DeserializeError::De(err) => write!(f, "{}", err),
+            DeserializeError::AggregateError(err) => write!(f, "{}", err),
+            DeserializeError::MissingVersionHeader(key) => {
+                write!(f, "Missing version header '{}' as first key", key)
+            }
+        }
+    }
+}
+
+impl<E> std::error::Error for DeserializeError<E> where E: std::error::Error {}
+
+/// Deserialize a document with its version header, with the deserializer of any
+/// self-describing format
+///
+/// The root of the document is either a map with the version header under the `v` key as first
+/// entry, the other entries belonging to the value, or a two elements sequence of the version
+/// header and the value. See [`VersionHeader`] for the other layouts.
+///
+/// # Generic Parameters
+/// - `T`: type to deserialize
+/// - `D`: deserializer of the document
+/// - `VMR`: resolver to find the version groups to use
+/// - `AGG`: aggregator to combine found version groups
+///
+/// # Parameters
+/// - `deserializer`: deserializer of the document
+/// - `resolver`: resolver to find the version groups to use
+/// - `aggregate`: aggregator to combine found version groups
+///
+/// # Returns
+/// The deserialized value or the error that occurred
+pub fn deserialize_with_header<'de, T, D, VMR, AGG>(
+    deserializer: D,
+    resolver: &VMR,
+    aggregate: &AGG,
+) -> Result<T, DeserializeError<D::Error>>
+where
+    T: DeserializeVersioned<'de>,
+    D: Deserializer<'de>,
+    VMR: VersionGroupResolver,
+    AGG: AggregateVersionMap,
+{
+    VersionHeader::default().deserialize(deserializer, resolver, aggregate)
+}
+
+/// Error that the deserializer of the document can't represent
+#[derive(Debug)]
+pub(crate) enum HeaderError {
+    MissingVersionHeader(String),
+    AggregateError(AggregateVersionMapError),
+    InvalidVersionError(InvalidVersionError),
+    Message(String),
+}
+
+impl std::fmt::Display for HeaderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            HeaderError::MissingVersionHeader(key) => {
+                write!(f, "Missing version header '{}' as first field", key)
+            }
+            HeaderError::AggregateError(err) => write!(f, "{}", err),
+            HeaderError::InvalidVersionError(err) => write!(f, "{}", err),
+            HeaderError::Message(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+/// Where the version header is in the root map
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Layout<'a> {
+    /// The first entry is the version header, the others belong to the value
+    LeadingField { key: &'a str },
+    /// `LeadingField` for a map, `Tuple` for a sequence
+    LeadingFieldOrTuple { key: &'a str },
+    /// The version header then the value under `data_key`
+    Envelope {
+        header_key: &'a str,
+        data_key: &'a str,
+    },
+    /// A sequence of the version header and the value
+    Tuple,
+}
+
+impl<'a> Layout<'a> {
+    fn header_key(&self) -> Option<&'a str> {
+        match self {
+            Layout::LeadingField { key } | Layout::LeadingFieldOrTuple { key } => Some(key),
+            Layout::Envelope { header_key, .. } => Some(header_key),
+            Layout::Tuple => None,
+        }
+    }
+}
+
+/// Read the version header, then the value with the aggregated version map
+///
+/// The errors that are not raised by the deserializer of the document are kept in `error`.
+pub(crate) struct DocumentVisitor<'a, T, VMR, AGG> {
+    pub layout: Layout<'a>,
+    pub resolver: &'a VMR,
+    pub aggregate: &'a AGG,
+    pub error: &'a mut Option<HeaderError>,
+    pub marker: PhantomData<T>,
+}
+
+impl<'de, 'a, T, VMR, AGG> Visitor<'de> for DocumentVisitor<'a, T, VMR, AGG>
+where
+    T: DeserializeVersioned<'de>,
+    VMR: VersionGroupResolver,
+    AGG: AggregateVersionMap,
+{
+    type Value = T;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.layout.header_key() {
+            Some(key) if matches!(self.layout, Layout::LeadingFieldOrTuple { .. }) => write!(
+                formatter,
+                "a map starting with the version header '{}' or a sequence of the version \
+                 header and the value",
+                key
+            ),
+            Some(key) => write!(
+                formatter,
+                "a map starting with the version header '{}'",
+                key
+            ),
+            None => write!(formatter, "a sequence of the version header and the value"),
+        }
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<T, A::Error> {
+        if !matches!(
+            self.layout,
+            Layout::Tuple | Layout::LeadingFieldOrTuple { .. }
+        ) {
+            return Err(serde::de::Error::invalid_type(Unexpected::Seq, &self));
+        }
+        let uris: VersionGroupURIs = seq
+            .next_element::<Vec<VersionGroupURI>>()?
+            .ok_or_else(|| serde::de::Error::invalid_length(0, &self))?
+            .into();
+        let error = self.error;
+        let value = self
+            .aggregate
+            .with_version_map(&uris, self.resolver, |version_map| {
+                seq.next_element_seed(ValueSeed {
+                    version_map,
+                    error: &mut *error,
+                    marker: PhantomData,
+                })
+            })
+            .map_err(|err| keep_error(error, HeaderError::AggregateError(err)))??;
+        match value {
+            Some(value) if seq.next_element::<IgnoredAny>()?.is_none() => Ok(value),
+            _ => Err(serde::de::Error::custom(
+                "expected a sequence of the version header and the value",
+            )),
+        }
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<T, A::Error> {
+        let (header_key, data_key) = match self.layout {
+            Layout::LeadingField { key } | Layout::LeadingFieldOrTuple { key } => (key, None),
+            Layout::Envelope {
+                header_key,
+                data_key,
+            } => (header_key, Some(data_key)),
+            Layout::Tuple => return Err(serde::de::Error::invalid_type(Unexpected::Map, &self)),
+        };
+        if map.next_key_seed(KeySeed)?.as_deref() != Some(header_key) {
+            return Err(keep_error(
+                self.error,
+                HeaderError::MissingVersionHeader(header_key.to_owned()),
+            ));
+        }
+        let uris: VersionGroupURIs = map.next_value::<Vec<VersionGroupURI>>()?.into();
+        let error = self.error;
+        self.aggregate
+            .with_version_map(&uris, self.resolver, |version_map| {
+                visit_body(map, data_key, version_map, &mut *error)
+            })
+            .map_err(|err| keep_error(error, HeaderError::AggregateError(err)))?
+    }
+}
+
+/// Deserialize the value after the version header of a map
+///
+/// The value is the other entries of the map, or the entry `data_key` when set.
+fn visit_body<'de, T, A>(
+    mut map: A,
+    data_key: Option<&str>,
+    version_map: &AggregatedVersionMap,
+    error: &mut Option<HeaderError>,
+) -> Result<T, A::Error>
+where
+    T: DeserializeVersioned<'de>,
+    A: MapAccess<'de>,
+{
+    let data_key = match data_key {
+        // The other entries of the map are the value
+        None => {
+            return deserialize_value(
+                serde::de::value::MapAccessDeserializer::new(map),
+                version_map,
+                error,
+            )
+        }
+        Some(data_key) => data_key,
+    };
+    match map.next_key_seed(KeySeed)? {
+        Some(key) if key == data_key => {}
+        key => {
+            return Err(serde::de::Error::custom(format!(
+                "expected the key '{}' after the version header, found {:?}",
+                data_key, key
+            )))
+        }
+    }
+    let value = map.next_value_seed(ValueSeed {
+        version_map,
+        error,
+        marker: PhantomData,
+    })?;
+    if let Some(key) = map.next_key_seed(KeySeed)? {
+        return Err(serde::de::Error::custom(format!(
+            "unexpected key '{}' after '{}'",
+            key, data_key
+        )));
+    }
+    Ok(value)
+}
+
+/// Deserialize a key as an identifier, the formats with struct syntax only provide those
+struct KeySeed;
+
+impl<'de> DeserializeSeed<'de> for KeySeed {
+    type Value = String;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<String, D::Error> {
+        deserializer.deserialize_identifier(self)
+    }
+}
+
+impl<'de> Visitor<'de> for KeySeed {
+    type Value = String;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(formatter, "a key")
+    }
+
+    fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<String, E> {
+        Ok(v.to_owned())
+    }
+
+    fn visit_string<E: serde::de::Error>(self, v: String) -> Result<String, E> {
+        Ok(v)
+    }
+}
+
+struct ValueSeed<'a, T, VM> {
+    version_map: VM,
+    error: &'a mut Option<HeaderError>,
+    marker: PhantomData<T>,
+}
+
+impl<'de, 'a, T, VM> DeserializeSeed<'de> for ValueSeed<'a, T, VM>
+where
+    T: DeserializeVersioned<'de>,
+    VM: VersionMap,
+{
+    type Value = T;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<T, D::Error> {
+        deserialize_value(deserializer, self.version_map, self.error)
+    }
+}
+
+fn deserialize_value<'de, T, D, VM>(
+    deserializer: D,
+    version_map: VM,
+    error: &mut Option<HeaderError>,
+) -> Result<T, D::Error>
+where
+    T: DeserializeVersioned<'de>,
+    D: Deserializer<'de>,
+    VM: VersionMap,
+{
+    let de = VersionedDeserializer::new(deserializer, version_map.clone());
+    match T::deserialize_versioned(de, version_map).map_err(Error::reduce) {
+        Ok(value) => Ok(value),
+        Err(Error::DeserializeError(err)) => Err(err),
+        Err(Error::InvalidVersionError(err)) => {
+            Err(keep_error(error, HeaderError::InvalidVersionError(err)))
+        }
+        Err(Error::Message(message)) => Err(keep_error(error, HeaderError::Message(message))),
+    }
+}
+
+fn keep_error<E: serde::de::Error>(slot: &mut Option<HeaderError>, error: HeaderError) -> E {
+    let message = error.to_string();
+    *slot = Some(error);
+    E::custom(message)
+}
+
+/// Serialize the version header, then the value under `data_key`
+#[cfg_attr(
+    not(any(feature = "json-support", feature = "msgpack-support")),
+    allow(dead_code)
+)]
+pub(crate) struct Envelope<'a, T> {
+    pub header_key: &'a str,
+    pub uris: &'a VersionGroupURIs,
+    pub data_key: &'a str,
+    pub value: &'a T,
+}
+
+impl<'a, T: Serialize> Serialize for Envelope<'a, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(2))?;
+        map.serialize_entry(self.header_key, &**self.uris)?;
+        map.serialize_entry(self.data_key, self.value)?;
+        map.end()
+    }
+}
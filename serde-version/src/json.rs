@@ -0,0 +1,556 @@
+//! Serialization utilities for the Json format
+//!
+//! The version header is the `v` field of the root object by default:
+//! ```json
+//! { "v": ["my.company:1"], "name": "my config name" }
+//! ```
+//!
+//! [`Options::envelope`] reads and writes the value in an envelope instead:
+//! ```json
+//! { "v": ["my.company:1"], "data": { "name": "my config name" } }
+//! ```
+//!
+//! The document is deserialized in a single pass, so the version header must be the first
+//! field of the root object.
+//!
+//! [`records`] reads newline-delimited Json, each record with its own version header:
+//! ```json
+//! { "v": ["my.company:1"], "message": "started" }
+//! { "v": ["my.company:2"], "message": "stopped", "code": 0 }
+//! ```
+
+use crate::header::{DocumentVisitor, Envelope, Layout};
+use crate::upgrade::UpgradeFormat;
+use crate::version_map::AggregateVersionMap;
+use crate::{
+    AggregateVersionMapError, CachedAggregate, DeserializeVersioned, Error, VersionGroupResolver,
+    VersionGroupURI, VersionGroupURIs,
+};
+use failure::Fail;
+use serde::{Deserializer, Serialize};
+use std::collections::BTreeMap;
+use std::io::{self, BufRead};
+use std::marker::PhantomData;
+
+/// Key of the default version header, in the root object
+pub const DEFAULT_VERSION_HEADER_KEY: &str = "v";
+
+/// Key of the value in the default envelope
+pub const DEFAULT_DATA_KEY: &str = "data";
+
+/// Deserialization error
+#[derive(Debug, Fail)]
+pub enum DeserializeError {
+    #[fail(display = "{}", 0)]
+    Json(::serde_json::Error),
+    #[fail(display = "{}", 0)]
+    De(Error<::serde_json::Error>),
+    #[fail(display = "{}", 0)]
+    AggregateError(AggregateVersionMapError),
+    #[fail(display = "Missing version header '{}' as first field", 0)]
+    MissingVersionHeader(String),
+}
+impl_from_enum! {
+    DeserializeError::Json => ::serde_json::Error,
+    DeserializeError::De => Error<::serde_json::Error>,
+    DeserializeError::AggregateError => AggregateVersionMapError,
+}
+
+/// Error raised while upgrading a Json file
+pub type UpgradeError = crate::upgrade::UpgradeError<DeserializeError, ::serde_json::Error>;
+
+/// Report of the upgrade of the Json files of a directory
+pub type BatchReport = crate::upgrade::BatchReport<DeserializeError, ::serde_json::Error>;
+
+/// Error raised while computing the diff of a migration
+#[cfg(feature = "diff-support")]
+pub type DiffError = crate::diff::DiffError<DeserializeError, ::serde_json::Error>;
+
+/// Error of a record of a newline-delimited Json stream
+#[derive(Debug, Fail)]
+#[fail(display = "Record at line {}: {}", line, error)]
+pub struct RecordError {
+    /// Line of the record, starting at 1
+    pub line: usize,
+    pub error: DeserializeError,
+}
+
+/// Where the version header is written in a Json document
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VersionHeaderLayout {
+    /// The first field of the root object is the version header, the other fields
+    /// belong to the value
+    RootField { key: String },
+    /// The root object contains the version header, then the value under `data_key`
+    Envelope {
+        header_key: String,
+        data_key: String,
+    },
+}
+
+/// Options of the Json format
+///
+/// By default, the version header is the `v` field of the root object.
+///
+/// ```
+/// let options = serde_version::json::Options::new()
+///     .header_key("versions")
+///     .envelope("payload");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Options {
+    layout: VersionHeaderLayout,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            layout: VersionHeaderLayout::RootField {
+                key: DEFAULT_VERSION_HEADER_KEY.to_owned(),
+            },
+        }
+    }
+}
+
+impl Options {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn layout(&self) -> &VersionHeaderLayout {
+        &self.layout
+    }
+
+    /// Use the key `key` for the version header
+    pub fn header_key<K: Into<String>>(mut self, key: K) -> Self {
+        let key = key.into();
+        self.layout = match self.layout {
+            VersionHeaderLayout::RootField { .. } => VersionHeaderLayout::RootField { key },
+            VersionHeaderLayout::Envelope { data_key, .. } => VersionHeaderLayout::Envelope {
+                header_key: key,
+                data_key,
+            },
+        };
+        self
+    }
+
+    /// Wrap the value in an envelope, under the key `data_key`
+    ///
+    /// See `DEFAULT_DATA_KEY`.
+    pub fn envelope<K: Into<String>>(mut self, data_key: K) -> Self {
+        let header_key = self.header_key_str().to_owned();
+        self.layout = VersionHeaderLayout::Envelope {
+            header_key,
+            data_key: data_key.into(),
+        };
+        self
+    }
+
+    fn header_key_str(&self) -> &str {
+        match &self.layout {
+            VersionHeaderLayout::RootField { key } => key,
+            VersionHeaderLayout::Envelope { header_key, .. } => header_key,
+        }
+    }
+
+    /// Deserialize a Json string with versioning support
+    ///
+    /// See [`deserialize`](fn.deserialize.html).
+    pub fn deserialize<'de, T, VMR, AGG>(
+        &self,
+        input: &'de str,
+        resolver: &VMR,
+        aggregate: &AGG,
+    ) -> Result<T, DeserializeError>
+    where
+        T: DeserializeVersioned<'de>,
+        VMR: VersionGroupResolver,
+        AGG: AggregateVersionMap,
+    {
+        let mut de = ::serde_json::Deserializer::from_str(input);
+        let value = self.deserialize_document(&mut de, resolver, aggregate)?;
+        de.end()?;
+        Ok(value)
+    }
+
+    /// Deserialize a Json document from a reader with versioning support
+    ///
+    /// See [`from_reader`](fn.from_reader.html).
+    pub fn from_reader<R, T, VMR, AGG>(
+        &self,
+        reader: R,
+        resolver: &VMR,
+        aggregate: &AGG,
+    ) -> Result<T, DeserializeError>
+    where
+        R: std::io::Read,
+        T: for<'de> DeserializeVersioned<'de>,
+        VMR: VersionGroupResolver,
+        AGG: AggregateVersionMap,
+    {
+        let mut de = ::serde_json::Deserializer::from_reader(reader);
+        let value = self.deserialize_document(&mut de, resolver, aggregate)?;
+        de.end()?;
+        Ok(value)
+    }
+
+    /// Iterate over the records of a newline-delimited Json stream with versioning support
+    ///
+    /// See [`records`](fn.records.html).
+    pub fn records<'a, R, T, VMR, AGG>(
+        &self,
+        reader: R,
+        resolver: &'a VMR,
+        aggregate: &'a AGG,
+    ) -> Records<'a, R, T, VMR, AGG>
+    where
+        R: BufRead,
+        T: for<'de> DeserializeVersioned<'de>,
+        VMR: VersionGroupResolver,
+        AGG: AggregateVersionMap,
+    {
+        Records {
+            options: self.clone(),
+            reader,
+            resolver,
+            aggregate: CachedAggregate::new(aggregate),
+            buffer: String::new(),
+            line: 0,
+            done: false,
+            marker: PhantomData,
+        }
+    }
+
+    fn deserialize_document<'de, T, D, VMR, AGG>(
+        &self,
+        deserializer: D,
+        resolver: &VMR,
+        aggregate: &AGG,
+    ) -> Result<T, DeserializeError>
+    where
+        T: DeserializeVersioned<'de>,
+        D: Deserializer<'de, Error = ::serde_json::Error>,
+        VMR: VersionGroupResolver,
+        AGG: AggregateVersionMap,
+    {
+        let mut error = None;
+        let layout = match &self.layout {
+            VersionHeaderLayout::RootField { key } => Layout::LeadingField { key },
+            VersionHeaderLayout::Envelope {
+                header_key,
+                data_key,
+            } => Layout::Envelope {
+                header_key,
+                data_key,
+            },
+        };
+        let visitor = DocumentVisitor {
+            layout,
+            resolver,
+            aggregate,
+            error: &mut error,
+            marker: PhantomData,
+        };
+        deserializer
+            .deserialize_map(visitor)
+            // The visitor keeps the errors that do not come from the Json deserializer
+            .map_err(|err| error.map_or(DeserializeError::Json(err), Into::into))
+    }
+
+    /// Serialize in a string a value formatted in Json with its version header
+    ///
+    /// See [`serialize_inplace`](fn.serialize_inplace.html).
+    pub fn serialize_inplace<T: Serialize>(
+        &self,
+        str: &mut String,
+        value: &T,
+        uris: &VersionGroupURIs,
+    ) -> Result<(), ::serde_json::Error> {
+        let document = match &self.layout {
+            VersionHeaderLayout::RootField { key } => {
+                let mut header = BTreeMap::new();
+                header.insert(key.as_str(), &**uris);
+                ::serde_json::to_string(&RootFieldDocument { header, value })?
+            }
+            VersionHeaderLayout::Envelope {
+                header_key,
+                data_key,
+            } => ::serde_json::to_string(&Envelope {
+                header_key,
+                uris,
+                data_key,
+                value,
+            })?,
+        };
+        str.push_str(&document);
+        Ok(())
+    }
+
+    /// Serialize a value formatted in Json with its version header
+    ///
+    /// See [`serialize`](fn.serialize.html).
+    pub fn serialize<T: Serialize>(
+        &self,
+        value: &T,
+        uris: &VersionGroupURIs,
+    ) -> Result<String, ::serde_json::Error> {
+        let mut str = String::new();
+        self.serialize_inplace(&mut str, value, uris)?;
+        Ok(str)
+    }
+}
+
+impl UpgradeFormat for Options {
+    type DeserializeError = DeserializeError;
+    type SerializeError = ::serde_json::Error;
+
+    fn read_document<T, VMR, AGG>(
+        &self,
+        input: &[u8],
+        resolver: &VMR,
+        aggregate: &AGG,
+    ) -> Result<T, UpgradeError>
+    where
+        T: for<'de> DeserializeVersioned<'de>,
+        VMR: VersionGroupResolver,
+        AGG: AggregateVersionMap,
+    {
+        let input = crate::upgrade::as_str(input)?;
+        self.deserialize(input, resolver, aggregate)
+            .map_err(UpgradeError::Deserialize)
+    }
+
+    fn write_document<T: Serialize>(
+        &self,
+        value: &T,
+        uris: &VersionGroupURIs,
+    ) -> Result<Vec<u8>, UpgradeError> {
+        Ok(self
+            .serialize(value, uris)
+            .map_err(UpgradeError::Serialize)?
+            .into_bytes())
+    }
+}
+
+/// Iterator over the records of a newline-delimited Json stream
+///
+/// Each record is deserialized with the version map of its own version header. The version
+/// maps are cached by header, so a header is aggregated only once.
+///
+/// An invalid record yields an error and the iteration goes on with the next line. The
+/// iteration stops after an error of the reader.
+pub struct Records<'a, R, T, VMR, AGG> {
+    options: Options,
+    reader: R,
+    resolver: &'a VMR,
+    aggregate: CachedAggregate<'a, AGG>,
+    buffer: String,
+    line: usize,
+    done: bool,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<'a, R, T, VMR, AGG> Records<'a, R, T, VMR, AGG> {
+    /// Number of distinct version headers read so far
+    pub fn version_headers(&self) -> usize {
+        self.aggregate.len()
+    }
+}
+
+impl<'a, R, T, VMR, AGG> Iterator for Records<'a, R, T, VMR, AGG>
+where
+    R: BufRead,
+    T: for<'de> DeserializeVersioned<'de>,
+    VMR: VersionGroupResolver,
+    AGG: AggregateVersionMap,
+{
+    type Item = Result<T, RecordError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            self.buffer.clear();
+            self.line += 1;
+            match self.reader.read_line(&mut self.buffer) {
+                Ok(0) => self.done = true,
+                Ok(_) if self.buffer.trim().is_empty() => {}
+                Ok(_) => {
+                    return Some(
+                        self.options
+                            .deserialize(&self.buffer, self.resolver, &self.aggregate)
+                            .map_err(|error| RecordError {
+                                line: self.line,
+                                error,
+                            }),
+                    )
+                }
+                Err(err) => {
+                    // A line that is not valid UTF-8 is consumed, the next ones can be read
+                    self.done = err.kind() != io::ErrorKind::InvalidData;
+                    return Some(Err(RecordError {
+                        line: self.line,
+                        error: DeserializeError::Json(::serde_json::Error::io(err)),
+                    }));
+                }
+            }
+        }
+        None
+    }
+}
+
+#[derive(Serialize)]
+struct RootFieldDocument<'a, T> {
+    #[serde(flatten)]
+    header: BTreeMap<&'a str, &'a [VersionGroupURI]>,
+    #[serde(flatten)]
+    value: &'a T,
+}
+
+impl_from_header_error!(DeserializeError);
+
+#[cfg(feature = "diff-support")]
+impl crate::diff::DiffFormat for Options {
+    fn read_value(&self, input: &str) -> Result<crate::diff::Value, DeserializeError> {
+        Ok(::serde_json::from_str(input)?)
+    }
+}
+
+/// Deserialize a Json string with versioning support
+///
+/// # Generic Parameters
+/// - `T`: type to deserialize
+/// - `VMR`: resolver to find the version groups to use
+/// - `AGG`: aggregator to combine found version groups
+///
+/// # Parameters
+/// - `input`: Json formatted string, the version header is the first field `v` of the
+///   root object
+/// - `resolver`: resolver to find the version groups to use
+/// - `aggregate`: aggregator to combine found version groups
+///
+/// # Returns
+/// The deserialized value or the error that occurred
+pub fn deserialize<'de, T, VMR, AGG>(
+    input: &'de str,
+    resolver: &VMR,
+    aggregate: &AGG,
+) -> Result<T, DeserializeError>
+where
+    T: DeserializeVersioned<'de>,
+    VMR: VersionGroupResolver,
+    AGG: AggregateVersionMap,
+{
+    Options::default().deserialize(input, resolver, aggregate)
+}
+
+/// Deserialize a Json document from a reader with versioning support
+///
+/// The version header is read first, then the value is deserialized while the document is
+/// read: it is never loaded whole in memory. The reader is read byte by byte, so wrap
+/// unbuffered readers like files in a `std::io::BufReader`.
+///
+/// # Generic Parameters
+/// - `R`: reader of the document
+/// - `T`: type to deserialize
+/// - `VMR`: resolver to find the version groups to use
+/// - `AGG`: aggregator to combine found version groups
+///
+/// # Parameters
+/// - `reader`: reader of a Json document, the version header is the first field `v` of the
+///   root object
+/// - `resolver`: resolver to find the version groups to use
+/// - `aggregate`: aggregator to combine found version groups
+///
+/// # Returns
+/// The deserialized value or the error that occurred
+pub fn from_reader<R, T, VMR, AGG>(
+    reader: R,
+    resolver: &VMR,
+    aggregate: &AGG,
+) -> Result<T, DeserializeError>
+where
+    R: std::io::Read,
+    T: for<'de> DeserializeVersioned<'de>,
+    VMR: VersionGroupResolver,
+    AGG: AggregateVersionMap,
+{
+    Options::default().from_reader(reader, resolver, aggregate)
+}
+
+/// Iterate over the records of a newline-delimited Json stream with versioning support
+///
+/// Each line is a Json document with its own version header, blank lines are skipped. The
+/// records written by different versions of an application can be interleaved: the version
+/// maps are cached by version header, so a header is aggregated only once.
+///
+/// Use [`serialize`](fn.serialize.html) to write a record, it is written on a single line.
+///
+/// # Generic Parameters
+/// - `R`: reader of the stream
+/// - `T`: type of the records
+/// - `VMR`: resolver to find the version groups to use
+/// - `AGG`: aggregator to combine found version groups
+///
+/// # Parameters
+/// - `reader`: reader of the stream, the version header is the first field `v` of each record
+/// - `resolver`: resolver to find the version groups to use
+/// - `aggregate`: aggregator to combine found version groups
+///
+/// # Returns
+/// An iterator over the deserialized records or the error that occurred for each of them
+pub fn records<'a, R, T, VMR, AGG>(
+    reader: R,
+    resolver: &'a VMR,
+    aggregate: &'a AGG,
+) -> Records<'a, R, T, VMR, AGG>
+where
+    R: BufRead,
+    T: for<'de> DeserializeVersioned<'de>,
+    VMR: VersionGroupResolver,
+    AGG: AggregateVersionMap,
+{
+    Options::default().records(reader, resolver, aggregate)
+}
+
+/// Serialize in a string a value formatted in Json with its version header.
+///
+/// The version header will be serialized as the first field of the value.
+///
+/// # Generic Parameters
+/// - `T`: type to serialize
+///
+/// # Parameters
+/// - `str`: The string the will receive the data
+/// - `value`: The value to serialize
+/// - `uris`: The uris to serialize in the version header
+///
+/// # Returns
+/// The error that occurred, if any.
+pub fn serialize_inplace<T: Serialize>(
+    str: &mut String,
+    value: &T,
+    uris: &VersionGroupURIs,
+) -> Result<(), ::serde_json::Error> {
+    Options::default().serialize_inplace(str, value, uris)
+}
+
+/// Serialize a value formatted in Json with its version header.
+///
+/// The version header will be serialized as the first field of the value.
+///
+/// # Generic Parameters
+/// - `T`: type to serialize
+///
+/// # Parameters
+/// - `value`: The value to serialize
+/// - `uris`: The uris to serialize in the version header
+///
+/// # Returns
+/// The formatted string or the error that occurred, if any.
+pub fn serialize<T: Serialize>(
+    value: &T,
+    uris: &VersionGroupURIs,
+) -> Result<String, ::serde_json::Error> {
+    Options::default().serialize(value, uris)
+}
+
+upgrade_functions!("Json", "json");
@@ -31,6 +31,8 @@
 // Some doc test needs external crates
 // In that case, we need the main function
 #![allow(clippy::needless_doctest_main)]
+// `#[derive(Fail)]` implements the traits inside an anonymous constant
+#![allow(non_local_definitions)]
 
 // Re-export #[derive(Serialize, Deserialize)].
 //
@@ -61,21 +63,70 @@ extern crate quickcheck;
 #[macro_use(quickcheck)]
 extern crate quickcheck_macros;
 
+#[cfg(any(feature = "toml-support", feature = "csv-support"))]
+mod comment_header;
 mod deserializer;
 mod seed;
+#[cfg(any(
+    feature = "toml-support",
+    feature = "xml-support",
+    feature = "yaml-support"
+))]
+mod skip_entries;
+mod version_groups;
 mod version_map;
 mod visitor;
 
 #[macro_use]
 mod utils;
-
+#[cfg(feature = "binary-support")]
+pub mod binary;
+#[cfg(feature = "cbor-support")]
+pub mod cbor;
+#[cfg(feature = "csv-support")]
+pub mod csv;
+#[cfg(feature = "diff-support")]
+pub mod diff;
+#[cfg(feature = "file-support")]
+pub mod file;
+pub mod header;
+#[cfg(feature = "json-support")]
+pub mod json;
+
+pub mod manifest;
+#[cfg(feature = "msgpack-support")]
+pub mod msgpack;
+#[cfg(feature = "ron-support")]
+pub mod ron;
+#[cfg(feature = "semver-support")]
+pub mod semver;
 #[cfg(feature = "toml-support")]
 pub mod toml;
+pub mod upgrade;
+#[cfg(feature = "xml-support")]
+pub mod xml;
+#[cfg(feature = "yaml-support")]
+pub mod yaml;
 
 pub use deserializer::VersionedDeserializer;
+pub use header::deserialize_with_header;
 use serde::de::{EnumAccess, MapAccess, SeqAccess};
 use std::{fmt::Display, marker::PhantomData};
-pub use version_map::{DefaultVersionMap, VersionMap};
+#[doc(hidden)]
+pub use version_groups::{
+    collect_static_resolver, collect_static_version_map, static_version_group_parent,
+    static_version_group_uri,
+};
+pub use version_groups::{
+    flatten_version_groups, InvalidVersionGroupURIError, StaticVersionGroupResolver,
+    VersionGroupCycle, VersionGroupInheritanceError, VersionGroupResolver, VersionGroupURI,
+    VersionGroupURIs, VERSION_GROUP_URI_SEPARATOR,
+};
+pub use version_map::{
+    AggregatePolicy, AggregateVersionMap, AggregateVersionMapError, AggregatedVersionMap,
+    CachedAggregate, DefaultVersionMap, RecordingAggregate, VersionConflictError, VersionMap,
+    VersionMapIter,
+};
 
 /// Error used when a provided version number is not handled by current code
 #[derive(Debug, Hash, PartialEq, Eq, Fail)]
@@ -218,6 +269,7 @@ pub trait DeserializeVersionedSeed<'de>: serde::de::DeserializeSeed<'de> {
     /// Implement this method to specialize the deserialization for a particular type.
     ///
     /// The default implementation ignore the versioning
+    #[allow(clippy::type_complexity)]
     fn variant<E, VM: VersionMap>(
         self,
         enum_access: E,
@@ -227,6 +279,27 @@ pub trait DeserializeVersionedSeed<'de>: serde::de::DeserializeSeed<'de> {
         E: EnumAccess<'de>;
 }
 
+/// Version indices declared by a versioned type
+///
+/// `#[derive(DeserializeVersioned)]` implements it with the indices of the `#[versions(...)]`
+/// attribute.
+pub trait DeclaredVersions {
+    const VERSIONS: &'static [usize];
+}
+
+/// Whether `index` is in `versions`, usable in const contexts
+#[doc(hidden)]
+pub const fn declares_version(versions: &[usize], index: usize) -> bool {
+    let mut i = 0;
+    while i < versions.len() {
+        if versions[i] == index {
+            return true;
+        }
+        i += 1;
+    }
+    false
+}
+
 /// Trait for versioning support during deserialization
 ///
 /// Use the `derive` feature to generate the implementation from `#[derive(DeserializeVersioned)]`
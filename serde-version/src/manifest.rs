@@ -0,0 +1,363 @@
+//! Version groups loaded at runtime from manifests
+//!
+//! A manifest maps version group uris to the version of each type of the group.
+//! The type keys are the ones used by `#[derive(DeserializeVersioned)]`, the
+//! `std::any::type_name` of the types.
+//!
+//! ```toml
+//! ["org.my.plugin:1.0"]
+//! "my_plugin::Config" = 1
+//! "my_plugin::Item" = 2
+//! ```
+//!
+//! A group can extend a parent group, possibly declared by another manifest, and list only
+//! the types whose version changed:
+//! ```toml
+//! ["org.my.plugin:1.1"]
+//! extends = "org.my.plugin:1.0"
+//! "my_plugin::Item" = 3
+//! ```
+//!
+//! `include_version_groups!` reads the same manifests at compile time, with type paths
+//! resolved where the macro is used as keys (`"config::Config"`). They are converted to the
+//! `type_name` of the types (`"my_app::config::Config"`), so a runtime manifest declaring
+//! the same groups uses these full names.
+//!
+//! A [`ManifestVersionGroupResolver`] serves the groups of several manifests, each registered
+//! under a source name (a plugin for instance), so a source can be replaced or removed
+//! when its manifest is reloaded.
+
+use crate::{
+    flatten_version_groups, VersionGroupInheritanceError, VersionGroupResolver, VersionGroupURI,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+/// Version map of a group declared in a manifest
+pub type ManifestVersionMap = HashMap<String, usize>;
+
+/// A group declared in a manifest
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestGroup {
+    /// Group whose versions are inherited
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extends: Option<VersionGroupURI>,
+    /// Versions declared by the group, they override the inherited ones
+    #[serde(flatten)]
+    pub versions: ManifestVersionMap,
+}
+
+impl From<ManifestVersionMap> for ManifestGroup {
+    fn from(versions: ManifestVersionMap) -> Self {
+        Self {
+            extends: None,
+            versions,
+        }
+    }
+}
+
+/// Version groups declared in a manifest file
+///
+/// A manifest is not a `VersionGroupResolver`: a group lists only the versions changed from
+/// its parent, which may be declared in another manifest. Resolve uris with the map returned
+/// by `flatten` or with a `ManifestVersionGroupResolver`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct VersionGroupManifest {
+    groups: BTreeMap<VersionGroupURI, ManifestGroup>,
+}
+
+/// Error raised while loading a manifest
+#[derive(Debug, Fail)]
+pub enum ManifestError {
+    #[fail(display = "{}", 0)]
+    Io(std::io::Error),
+    #[cfg(feature = "toml-support")]
+    #[fail(display = "{}", 0)]
+    Toml(::toml::de::Error),
+    #[cfg(feature = "json-support")]
+    #[fail(display = "{}", 0)]
+    Json(::serde_json::Error),
+    #[fail(display = "Unknown manifest format for file {:?}", 0)]
+    UnknownFormat(PathBuf),
+    #[fail(display = "Invalid type key {:?} in version group {}", type_id, uri)]
+    InvalidTypeKey {
+        uri: VersionGroupURI,
+        type_id: String,
+    },
+    #[fail(
+        display = "Version group {} is declared by both {} and {}",
+        uri, first_source, second_source
+    )]
+    DuplicateGroup {
+        uri: VersionGroupURI,
+        first_source: String,
+        second_source: String,
+    },
+    #[fail(display = "{}", 0)]
+    Inheritance(VersionGroupInheritanceError),
+}
+impl_from_enum! {
+    ManifestError::Io => std::io::Error,
+    ManifestError::Inheritance => VersionGroupInheritanceError,
+}
+#[cfg(feature = "toml-support")]
+impl_from_enum! {
+    ManifestError::Toml => ::toml::de::Error,
+}
+#[cfg(feature = "json-support")]
+impl_from_enum! {
+    ManifestError::Json => ::serde_json::Error,
+}
+
+impl VersionGroupManifest {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read a manifest with any serde format
+    pub fn from_deserializer<'de, D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let manifest: Self = Deserialize::deserialize(deserializer)?;
+        manifest.validate().map_err(serde::de::Error::custom)?;
+        Ok(manifest)
+    }
+
+    #[cfg(feature = "toml-support")]
+    pub fn from_toml_str(input: &str) -> Result<Self, ManifestError> {
+        let manifest: Self = ::toml::from_str(input)?;
+        manifest.validate()?;
+        Ok(manifest)
+    }
+
+    #[cfg(feature = "json-support")]
+    pub fn from_json_str(input: &str) -> Result<Self, ManifestError> {
+        let manifest: Self = ::serde_json::from_str(input)?;
+        manifest.validate()?;
+        Ok(manifest)
+    }
+
+    /// Read a manifest file, its format is deduced from its extension (`toml` or `json`)
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ManifestError> {
+        let path = path.as_ref();
+        let extension = path.extension().and_then(|extension| extension.to_str());
+        match extension {
+            #[cfg(feature = "toml-support")]
+            Some("toml") => Self::from_toml_str(&std::fs::read_to_string(path)?),
+            #[cfg(feature = "json-support")]
+            Some("json") => Self::from_json_str(&std::fs::read_to_string(path)?),
+            _ => Err(ManifestError::UnknownFormat(path.to_owned())),
+        }
+    }
+
+    /// Check that the type keys are not empty
+    ///
+    /// The keys are `std::any::type_name` strings, which may contain spaces and punctuation
+    /// like `my_plugin::Pair<(u8, &str)>`.
+    pub fn validate(&self) -> Result<(), ManifestError> {
+        for (uri, group) in &self.groups {
+            if let Some(type_id) = group.versions.keys().find(|type_id| type_id.is_empty()) {
+                return Err(ManifestError::InvalidTypeKey {
+                    uri: uri.clone(),
+                    type_id: type_id.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Declare a group
+    ///
+    /// # Returns
+    /// The group previously declared with the same uri, if any.
+    pub fn insert<G: Into<ManifestGroup>>(
+        &mut self,
+        uri: VersionGroupURI,
+        group: G,
+    ) -> Option<ManifestGroup> {
+        self.groups.insert(uri, group.into())
+    }
+
+    pub fn get(&self, uri: &VersionGroupURI) -> Option<&ManifestGroup> {
+        self.groups.get(uri)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&VersionGroupURI, &ManifestGroup)> {
+        self.groups.iter()
+    }
+
+    /// Version maps of the groups, with the versions inherited from their parents
+    ///
+    /// The parents must be declared in this manifest, use a `ManifestVersionGroupResolver`
+    /// to extend the groups of other manifests.
+    pub fn flatten(
+        &self,
+    ) -> Result<HashMap<VersionGroupURI, ManifestVersionMap>, VersionGroupInheritanceError> {
+        flatten_groups(self.groups.iter())
+    }
+
+    pub fn len(&self) -> usize {
+        self.groups.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+}
+
+impl<G: Into<ManifestGroup>> std::iter::FromIterator<(VersionGroupURI, G)>
+    for VersionGroupManifest
+{
+    fn from_iter<I: IntoIterator<Item = (VersionGroupURI, G)>>(iter: I) -> Self {
+        Self {
+            groups: iter
+                .into_iter()
+                .map(|(uri, group)| (uri, group.into()))
+                .collect(),
+        }
+    }
+}
+
+fn flatten_groups<'a, I>(
+    groups: I,
+) -> Result<HashMap<VersionGroupURI, ManifestVersionMap>, VersionGroupInheritanceError>
+where
+    I: IntoIterator<Item = (&'a VersionGroupURI, &'a ManifestGroup)>,
+{
+    flatten_version_groups(
+        groups
+            .into_iter()
+            .map(|(uri, group)| (uri.clone(), group.extends.clone(), group.versions.clone())),
+    )
+}
+
+/// Resolve the version groups declared by manifests
+///
+/// A group can be declared by a single source and can extend the groups of any source.
+#[derive(Debug, Clone, Default)]
+pub struct ManifestVersionGroupResolver {
+    sources: BTreeMap<String, VersionGroupManifest>,
+    // Source of each group
+    groups: HashMap<VersionGroupURI, String>,
+    // Version maps with the inherited versions
+    flattened: HashMap<VersionGroupURI, ManifestVersionMap>,
+}
+
+impl ManifestVersionGroupResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register the manifest of a source, replacing the previous manifest of this source
+    ///
+    /// The groups of all the sources are flattened again, so a parent must be declared by
+    /// a registered source. On error, the resolver is left unchanged.
+    ///
+    /// # Returns
+    /// The previous manifest of the source, if any.
+    pub fn insert_manifest<S: Into<String>>(
+        &mut self,
+        source: S,
+        manifest: VersionGroupManifest,
+    ) -> Result<Option<VersionGroupManifest>, ManifestError> {
+        let source = source.into();
+        manifest.validate()?;
+        for uri in manifest.groups.keys() {
+            match self.groups.get(uri) {
+                Some(first_source) if first_source != &source => {
+                    return Err(ManifestError::DuplicateGroup {
+                        uri: uri.clone(),
+                        first_source: first_source.clone(),
+                        second_source: source,
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        let flattened = flatten_groups(
+            self.sources
+                .iter()
+                .filter(|(other, _)| *other != &source)
+                .flat_map(|(_, other)| other.iter())
+                .chain(manifest.iter()),
+        )?;
+
+        let previous = self.sources.remove(&source);
+        if let Some(previous) = &previous {
+            for uri in previous.groups.keys() {
+                self.groups.remove(uri);
+            }
+        }
+        for uri in manifest.groups.keys() {
+            self.groups.insert(uri.clone(), source.clone());
+        }
+        self.sources.insert(source, manifest);
+        self.flattened = flattened;
+        Ok(previous)
+    }
+
+    /// Read a manifest file and register it for a source
+    ///
+    /// See `insert_manifest`.
+    pub fn load_file<S: Into<String>, P: AsRef<Path>>(
+        &mut self,
+        source: S,
+        path: P,
+    ) -> Result<Option<VersionGroupManifest>, ManifestError> {
+        let manifest = VersionGroupManifest::from_file(path)?;
+        self.insert_manifest(source, manifest)
+    }
+
+    /// Unregister the manifest of a source
+    ///
+    /// Fails if a group of another source extends a group of this source, the resolver is
+    /// then left unchanged.
+    pub fn remove_manifest(
+        &mut self,
+        source: &str,
+    ) -> Result<Option<VersionGroupManifest>, ManifestError> {
+        if !self.sources.contains_key(source) {
+            return Ok(None);
+        }
+        let flattened = flatten_groups(
+            self.sources
+                .iter()
+                .filter(|(other, _)| *other != source)
+                .flat_map(|(_, other)| other.iter()),
+        )?;
+
+        let manifest = self.sources.remove(source);
+        if let Some(manifest) = &manifest {
+            for uri in manifest.groups.keys() {
+                self.groups.remove(uri);
+            }
+        }
+        self.flattened = flattened;
+        Ok(manifest)
+    }
+
+    pub fn manifest(&self, source: &str) -> Option<&VersionGroupManifest> {
+        self.sources.get(source)
+    }
+
+    /// Source of the manifest declaring a group
+    pub fn source_of(&self, uri: &VersionGroupURI) -> Option<&str> {
+        self.groups.get(uri).map(String::as_str)
+    }
+
+    pub fn sources(&self) -> impl Iterator<Item = &str> {
+        self.sources.keys().map(String::as_str)
+    }
+}
+
+impl VersionGroupResolver for ManifestVersionGroupResolver {
+    type VM = ManifestVersionMap;
+
+    fn resolve(&self, uri: &VersionGroupURI) -> Option<&ManifestVersionMap> {
+        self.flattened.get(uri)
+    }
+}
@@ -0,0 +1,358 @@
+//! Serialization utilities for the MessagePack format
+//!
+//! The value is wrapped with its version header in a two elements array by default:
+//! ```text
+//! [["my.company:1"], ["my config name"]]
+//! ```
+//!
+//! [`Options::map_envelope`] wraps it in a map instead:
+//! ```text
+//! {"v": ["my.company:1"], "data": ["my config name"]}
+//! ```
+//!
+//! The envelope is deserialized in a single pass, without an intermediate `rmpv::Value`.
+//! The structs of the value can be encoded as arrays (like `rmp_serde::to_vec`) or as maps
+//! (like `rmp_serde::to_vec_named`, see [`Options::struct_map`]), both are read.
+
+use crate::header::{DocumentVisitor, Envelope, Layout};
+use crate::upgrade::UpgradeFormat;
+use crate::version_map::AggregateVersionMap;
+use crate::{
+    AggregateVersionMapError, DeserializeVersioned, Error, VersionGroupResolver, VersionGroupURIs,
+};
+use failure::Fail;
+use serde::{Deserializer, Serialize};
+use std::marker::PhantomData;
+
+/// Key of the version header in the default map envelope
+pub const DEFAULT_VERSION_HEADER_KEY: &str = "v";
+
+/// Key of the value in the default map envelope
+pub const DEFAULT_DATA_KEY: &str = "data";
+
+/// Deserialization error
+#[derive(Debug, Fail)]
+pub enum DeserializeError {
+    #[fail(display = "{}", 0)]
+    MsgPack(::rmp_serde::decode::Error),
+    #[fail(display = "{}", 0)]
+    De(Error<::rmp_serde::decode::Error>),
+    #[fail(display = "{}", 0)]
+    AggregateError(AggregateVersionMapError),
+    #[fail(display = "Missing version header '{}' as first key", 0)]
+    MissingVersionHeader(String),
+}
+impl_from_enum! {
+    DeserializeError::MsgPack => ::rmp_serde::decode::Error,
+    DeserializeError::De => Error<::rmp_serde::decode::Error>,
+    DeserializeError::AggregateError => AggregateVersionMapError,
+}
+
+/// Error raised while upgrading a MessagePack file
+pub type UpgradeError = crate::upgrade::UpgradeError<DeserializeError, ::rmp_serde::encode::Error>;
+
+/// Report of the upgrade of the MessagePack files of a directory
+pub type BatchReport = crate::upgrade::BatchReport<DeserializeError, ::rmp_serde::encode::Error>;
+
+impl_from_header_error!(DeserializeError);
+
+/// How the value is wrapped with its version header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VersionHeaderLayout {
+    /// An array of the version header and the value
+    Array,
+    /// A map of the version header under `header_key`, then the value under `data_key`
+    Map {
+        header_key: String,
+        data_key: String,
+    },
+}
+
+/// Options of the MessagePack format
+///
+/// By default, the envelope is an array and the structs are encoded as arrays.
+///
+/// ```
+/// let options = serde_version::msgpack::Options::new()
+///     .map_envelope("v", "data")
+///     .struct_map(true);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Options {
+    layout: VersionHeaderLayout,
+    struct_map: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            layout: VersionHeaderLayout::Array,
+            struct_map: false,
+        }
+    }
+}
+
+impl Options {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn layout(&self) -> &VersionHeaderLayout {
+        &self.layout
+    }
+
+    /// Wrap the value in an array with its version header
+    pub fn array_envelope(mut self) -> Self {
+        self.layout = VersionHeaderLayout::Array;
+        self
+    }
+
+    /// Wrap the value in a map, the version header under `header_key` and the value
+    /// under `data_key`
+    ///
+    /// See `DEFAULT_VERSION_HEADER_KEY` and `DEFAULT_DATA_KEY`.
+    pub fn map_envelope<H: Into<String>, D: Into<String>>(
+        mut self,
+        header_key: H,
+        data_key: D,
+    ) -> Self {
+        self.layout = VersionHeaderLayout::Map {
+            header_key: header_key.into(),
+            data_key: data_key.into(),
+        };
+        self
+    }
+
+    /// Encode the structs of the value as maps with their field names, instead of arrays
+    pub fn struct_map(mut self, struct_map: bool) -> Self {
+        self.struct_map = struct_map;
+        self
+    }
+
+    /// Deserialize MessagePack bytes with versioning support
+    ///
+    /// See [`deserialize`](fn.deserialize.html).
+    pub fn deserialize<'de, T, VMR, AGG>(
+        &self,
+        input: &'de [u8],
+        resolver: &VMR,
+        aggregate: &AGG,
+    ) -> Result<T, DeserializeError>
+    where
+        T: DeserializeVersioned<'de>,
+        VMR: VersionGroupResolver,
+        AGG: AggregateVersionMap,
+    {
+        self.deserialize_document(
+            &mut ::rmp_serde::Deserializer::from_read_ref(input),
+            resolver,
+            aggregate,
+        )
+    }
+
+    /// Deserialize a MessagePack document from a reader with versioning support
+    ///
+    /// See [`from_reader`](fn.from_reader.html).
+    pub fn from_reader<R, T, VMR, AGG>(
+        &self,
+        reader: R,
+        resolver: &VMR,
+        aggregate: &AGG,
+    ) -> Result<T, DeserializeError>
+    where
+        R: std::io::Read,
+        T: for<'de> DeserializeVersioned<'de>,
+        VMR: VersionGroupResolver,
+        AGG: AggregateVersionMap,
+    {
+        self.deserialize_document(
+            &mut ::rmp_serde::Deserializer::new(reader),
+            resolver,
+            aggregate,
+        )
+    }
+
+    fn deserialize_document<'de, T, D, VMR, AGG>(
+        &self,
+        deserializer: D,
+        resolver: &VMR,
+        aggregate: &AGG,
+    ) -> Result<T, DeserializeError>
+    where
+        T: DeserializeVersioned<'de>,
+        D: Deserializer<'de, Error = ::rmp_serde::decode::Error>,
+        VMR: VersionGroupResolver,
+        AGG: AggregateVersionMap,
+    {
+        let mut error = None;
+        let layout = match &self.layout {
+            VersionHeaderLayout::Array => Layout::Tuple,
+            VersionHeaderLayout::Map {
+                header_key,
+                data_key,
+            } => Layout::Envelope {
+                header_key,
+                data_key,
+            },
+        };
+        let visitor = DocumentVisitor {
+            layout,
+            resolver,
+            aggregate,
+            error: &mut error,
+            marker: PhantomData,
+        };
+        let value = match layout {
+            Layout::Tuple => deserializer.deserialize_tuple(2, visitor),
+            _ => deserializer.deserialize_map(visitor),
+        };
+        // The visitor keeps the errors that do not come from the MessagePack deserializer
+        value.map_err(|err| error.map_or(DeserializeError::MsgPack(err), Into::into))
+    }
+
+    /// Serialize a value formatted in MessagePack with its version header
+    ///
+    /// See [`serialize`](fn.serialize.html).
+    pub fn serialize<T: Serialize>(
+        &self,
+        value: &T,
+        uris: &VersionGroupURIs,
+    ) -> Result<Vec<u8>, ::rmp_serde::encode::Error> {
+        match &self.layout {
+            VersionHeaderLayout::Array => self.encode(&(&**uris, value)),
+            VersionHeaderLayout::Map {
+                header_key,
+                data_key,
+            } => self.encode(&Envelope {
+                header_key,
+                uris,
+                data_key,
+                value,
+            }),
+        }
+    }
+
+    fn encode<T: Serialize>(&self, document: &T) -> Result<Vec<u8>, ::rmp_serde::encode::Error> {
+        let mut out = Vec::new();
+        let serializer = ::rmp_serde::Serializer::new(&mut out);
+        if self.struct_map {
+            document.serialize(&mut serializer.with_struct_map())?;
+        } else {
+            document.serialize(&mut serializer.with_struct_tuple())?;
+        }
+        Ok(out)
+    }
+}
+
+impl UpgradeFormat for Options {
+    type DeserializeError = DeserializeError;
+    type SerializeError = ::rmp_serde::encode::Error;
+
+    fn read_document<T, VMR, AGG>(
+        &self,
+        input: &[u8],
+        resolver: &VMR,
+        aggregate: &AGG,
+    ) -> Result<T, UpgradeError>
+    where
+        T: for<'de> DeserializeVersioned<'de>,
+        VMR: VersionGroupResolver,
+        AGG: AggregateVersionMap,
+    {
+        self.deserialize(input, resolver, aggregate)
+            .map_err(UpgradeError::Deserialize)
+    }
+
+    fn write_document<T: Serialize>(
+        &self,
+        value: &T,
+        uris: &VersionGroupURIs,
+    ) -> Result<Vec<u8>, UpgradeError> {
+        self.serialize(value, uris).map_err(UpgradeError::Serialize)
+    }
+}
+
+/// Deserialize MessagePack bytes with versioning support
+///
+/// Trailing bytes after the envelope are ignored, like `rmp_serde::from_slice` does.
+///
+/// # Generic Parameters
+/// - `T`: type to deserialize
+/// - `VMR`: resolver to find the version groups to use
+/// - `AGG`: aggregator to combine found version groups
+///
+/// # Parameters
+/// - `input`: MessagePack bytes, an array of the version header and the value
+/// - `resolver`: resolver to find the version groups to use
+/// - `aggregate`: aggregator to combine found version groups
+///
+/// # Returns
+/// The deserialized value or the error that occurred
+pub fn deserialize<'de, T, VMR, AGG>(
+    input: &'de [u8],
+    resolver: &VMR,
+    aggregate: &AGG,
+) -> Result<T, DeserializeError>
+where
+    T: DeserializeVersioned<'de>,
+    VMR: VersionGroupResolver,
+    AGG: AggregateVersionMap,
+{
+    Options::default().deserialize(input, resolver, aggregate)
+}
+
+/// Deserialize a MessagePack document from a reader with versioning support
+///
+/// The version header is read first, then the value is deserialized while the document is
+/// read: it is never loaded whole in memory. Wrap unbuffered readers like files in a
+/// `std::io::BufReader`.
+///
+/// # Generic Parameters
+/// - `R`: reader of the document
+/// - `T`: type to deserialize
+/// - `VMR`: resolver to find the version groups to use
+/// - `AGG`: aggregator to combine found version groups
+///
+/// # Parameters
+/// - `reader`: reader of MessagePack bytes, an array of the version header and the value
+/// - `resolver`: resolver to find the version groups to use
+/// - `aggregate`: aggregator to combine found version groups
+///
+/// # Returns
+/// The deserialized value or the error that occurred
+pub fn from_reader<R, T, VMR, AGG>(
+    reader: R,
+    resolver: &VMR,
+    aggregate: &AGG,
+) -> Result<T, DeserializeError>
+where
+    R: std::io::Read,
+    T: for<'de> DeserializeVersioned<'de>,
+    VMR: VersionGroupResolver,
+    AGG: AggregateVersionMap,
+{
+    Options::default().from_reader(reader, resolver, aggregate)
+}
+
+/// Serialize a value formatted in MessagePack with its version header.
+///
+/// The value and its version header will be serialized in an array, the structs of the
+/// value as arrays.
+///
+/// # Generic Parameters
+/// - `T`: type to serialize
+///
+/// # Parameters
+/// - `value`: The value to serialize
+/// - `uris`: The uris to serialize in the version header
+///
+/// # Returns
+/// The bytes or the error that occurred, if any.
+pub fn serialize<T: Serialize>(
+    value: &T,
+    uris: &VersionGroupURIs,
+) -> Result<Vec<u8>, ::rmp_serde::encode::Error> {
+    Options::default().serialize(value, uris)
+}
+
+upgrade_functions!("MessagePack", "msgpack");
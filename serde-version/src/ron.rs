@@ -0,0 +1,534 @@
+//! Serialization utilities for the Ron format
+//!
+//! The version header is the leading field `v` of the root struct by default:
+//! ```ron
+//! (v: ["my.company:1"], name: "my config name")
+//! ```
+//!
+//! [`Options::prelude`] reads and writes it in an attribute before the value instead, like
+//! the Ron extensions:
+//! ```ron
+//! #![versions("my.company:1")]
+//! (name: "my config name")
+//! ```
+
+use crate::header::{DocumentVisitor, Layout};
+use crate::upgrade::UpgradeFormat;
+use crate::version_map::AggregateVersionMap;
+use crate::{
+    AggregateVersionMapError, DeserializeVersioned, Error, VersionGroupResolver, VersionGroupURI,
+    VersionGroupURIs, VersionedDeserializer,
+};
+use ::ron::ser::PrettyConfig;
+use failure::Fail;
+use serde::ser::{Impossible, SerializeStruct};
+use serde::{Deserializer, Serialize, Serializer};
+use std::marker::PhantomData;
+
+/// Key of the default version header, in the root struct
+pub const DEFAULT_VERSION_HEADER_KEY: &str = "v";
+
+/// Attribute of the default version header prelude: `#![versions("a:1", "b:2")]`
+pub const DEFAULT_PRELUDE_ATTRIBUTE: &str = "versions";
+
+/// Deserialization error
+#[derive(Debug, Fail)]
+pub enum DeserializeError {
+    #[fail(display = "{}", 0)]
+    Ron(::ron::de::Error),
+    #[fail(display = "{}", 0)]
+    De(Error<::ron::de::Error>),
+    #[fail(display = "{}", 0)]
+    AggregateError(AggregateVersionMapError),
+    #[fail(display = "Missing version header '{}'", 0)]
+    MissingVersionHeader(String),
+    #[fail(display = "Invalid version header prelude: {}", 0)]
+    InvalidPrelude(String),
+}
+impl_from_enum! {
+    DeserializeError::Ron => ::ron::de::Error,
+    DeserializeError::De => Error<::ron::de::Error>,
+    DeserializeError::AggregateError => AggregateVersionMapError,
+}
+
+/// Error raised while upgrading a Ron file
+pub type UpgradeError = crate::upgrade::UpgradeError<DeserializeError, ::ron::ser::Error>;
+
+/// Report of the upgrade of the Ron files of a directory
+pub type BatchReport = crate::upgrade::BatchReport<DeserializeError, ::ron::ser::Error>;
+
+/// Error raised while computing the diff of a migration
+#[cfg(feature = "diff-support")]
+pub type DiffError = crate::diff::DiffError<DeserializeError, ::ron::ser::Error>;
+
+impl_from_header_error!(DeserializeError);
+
+/// Where the version header is written in a Ron document
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VersionHeaderLayout {
+    /// The first field of the root struct is the version header, the other fields
+    /// belong to the value
+    ///
+    /// The key is an identifier, like the names of the other fields.
+    LeadingField { key: String },
+    /// An attribute before the value: `#![<attribute>("a:1", "b:2")]`
+    Prelude { attribute: String },
+}
+
+/// Options of the Ron format
+///
+/// By default, the version header is the leading field `v` of the root struct and
+/// the value is serialized without pretty printing.
+///
+/// ```
+/// let options = serde_version::ron::Options::new()
+///     .prelude("versions")
+///     .pretty(ron::ser::PrettyConfig::default());
+/// ```
+#[derive(Debug, Clone)]
+pub struct Options {
+    layout: VersionHeaderLayout,
+    pretty: Option<PrettyConfig>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            layout: VersionHeaderLayout::LeadingField {
+                key: DEFAULT_VERSION_HEADER_KEY.to_owned(),
+            },
+            pretty: None,
+        }
+    }
+}
+
+impl Options {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn layout(&self) -> &VersionHeaderLayout {
+        &self.layout
+    }
+
+    /// Use the leading field `key` for the version header
+    pub fn header_key<K: Into<String>>(mut self, key: K) -> Self {
+        self.layout = VersionHeaderLayout::LeadingField { key: key.into() };
+        self
+    }
+
+    /// Write the version header in the prelude attribute `attribute`
+    ///
+    /// See `DEFAULT_PRELUDE_ATTRIBUTE`.
+    pub fn prelude<A: Into<String>>(mut self, attribute: A) -> Self {
+        self.layout = VersionHeaderLayout::Prelude {
+            attribute: attribute.into(),
+        };
+        self
+    }
+
+    /// Pretty print the serialized documents
+    pub fn pretty(mut self, config: PrettyConfig) -> Self {
+        self.pretty = Some(config);
+        self
+    }
+
+    /// Deserialize a Ron string with versioning support
+    ///
+    /// See [`deserialize`](fn.deserialize.html).
+    pub fn deserialize<'de, T, VMR, AGG>(
+        &self,
+        input: &'de str,
+        resolver: &VMR,
+        aggregate: &AGG,
+    ) -> Result<T, DeserializeError>
+    where
+        T: DeserializeVersioned<'de>,
+        VMR: VersionGroupResolver,
+        AGG: AggregateVersionMap,
+    {
+        let value = match &self.layout {
+            VersionHeaderLayout::LeadingField { key } => {
+                let mut de = ::ron::de::Deserializer::from_str(input)?;
+                let mut error = None;
+                let visitor = DocumentVisitor {
+                    layout: Layout::LeadingField { key },
+                    resolver,
+                    aggregate,
+                    error: &mut error,
+                    marker: PhantomData,
+                };
+                let value = match (&mut de).deserialize_any(visitor) {
+                    Ok(value) => value,
+                    // The visitor keeps the errors that do not come from the Ron deserializer
+                    Err(err) => return Err(error.map_or(DeserializeError::Ron(err), Into::into)),
+                };
+                de.end()?;
+                value
+            }
+            VersionHeaderLayout::Prelude { attribute } => {
+                let (uris, input) = split_prelude(input, attribute)?;
+                let version_map = aggregate.aggregate_version_maps(&uris, resolver)?;
+
+                let mut de = ::ron::de::Deserializer::from_str(input)?;
+                let value = DeserializeVersioned::deserialize_versioned(
+                    VersionedDeserializer::new(&mut de, &version_map),
+                    &version_map,
+                )
+                .map_err(Error::reduce)?;
+                de.end()?;
+                value
+            }
+        };
+        Ok(value)
+    }
+
+    /// Serialize a value formatted in Ron with its version header
+    ///
+    /// See [`serialize`](fn.serialize.html).
+    pub fn serialize<T: Serialize>(
+        &self,
+        value: &T,
+        uris: &VersionGroupURIs,
+    ) -> Result<String, ::ron::ser::Error> {
+        let mut serializer = ::ron::ser::Serializer::new(self.pretty.clone(), false);
+        match &self.layout {
+            VersionHeaderLayout::LeadingField { key } => {
+                if !is_identifier(key) {
+                    return Err(serde::ser::Error::custom(format!(
+                        "the version header key '{}' is not an identifier",
+                        key
+                    )));
+                }
+                LeadingField { uris, value }.serialize(&mut serializer)?;
+                // The header is serialized as the field `v`, the first one of the root struct
+                let output = serializer.into_output_string();
+                let name = output
+                    .find(|c: char| c != '(' && !c.is_whitespace())
+                    .unwrap_or(output.len());
+                Ok(format!(
+                    "{}{}{}",
+                    &output[..name],
+                    key,
+                    &output[name + DEFAULT_VERSION_HEADER_KEY.len()..]
+                ))
+            }
+            VersionHeaderLayout::Prelude { attribute } => {
+                value.serialize(&mut serializer)?;
+                let uris = uris
+                    .iter()
+                    .map(|uri| ::ron::ser::to_string(&uri.to_string()))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let new_line = self.pretty.as_ref().map_or("", |config| &config.new_line);
+                Ok(format!(
+                    "#![{}({})]\n{}{}",
+                    attribute,
+                    uris.join(", "),
+                    serializer.into_output_string(),
+                    new_line
+                ))
+            }
+        }
+    }
+}
+
+impl UpgradeFormat for Options {
+    type DeserializeError = DeserializeError;
+    type SerializeError = ::ron::ser::Error;
+
+    fn read_document<T, VMR, AGG>(
+        &self,
+        input: &[u8],
+        resolver: &VMR,
+        aggregate: &AGG,
+    ) -> Result<T, UpgradeError>
+    where
+        T: for<'de> DeserializeVersioned<'de>,
+        VMR: VersionGroupResolver,
+        AGG: AggregateVersionMap,
+    {
+        let input = crate::upgrade::as_str(input)?;
+        self.deserialize(input, resolver, aggregate)
+            .map_err(UpgradeError::Deserialize)
+    }
+
+    fn write_document<T: Serialize>(
+        &self,
+        value: &T,
+        uris: &VersionGroupURIs,
+    ) -> Result<Vec<u8>, UpgradeError> {
+        Ok(self
+            .serialize(value, uris)
+            .map_err(UpgradeError::Serialize)?
+            .into_bytes())
+    }
+}
+
+/// Serialize the version header as the leading field `v` of the root struct
+struct LeadingField<'a, T> {
+    uris: &'a VersionGroupURIs,
+    value: &'a T,
+}
+
+impl<'a, T: Serialize> Serialize for LeadingField<'a, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.value.serialize(LeadingFieldSerializer {
+            serializer,
+            uris: self.uris,
+        })
+    }
+}
+
+/// Serializer of the root struct, which starts with the version header field
+struct LeadingFieldSerializer<'a, S> {
+    serializer: S,
+    uris: &'a VersionGroupURIs,
+}
+
+fn not_a_struct<E: serde::ser::Error>() -> E {
+    E::custom("the version header can only be added to a struct")
+}
+
+macro_rules! not_a_struct {
+    ($($name:ident($($arg:ident: $ty:ty),*) -> $ret:ty;)*) => {
+        $(
+            fn $name(self, $(_: $ty),*) -> Result<$ret, S::Error> {
+                Err(not_a_struct())
+            }
+        )*
+    };
+}
+
+impl<'a, S: Serializer> Serializer for LeadingFieldSerializer<'a, S> {
+    type Ok = S::Ok;
+    type Error = S::Error;
+    type SerializeSeq = Impossible<S::Ok, S::Error>;
+    type SerializeTuple = Impossible<S::Ok, S::Error>;
+    type SerializeTupleStruct = Impossible<S::Ok, S::Error>;
+    type SerializeTupleVariant = Impossible<S::Ok, S::Error>;
+    type SerializeMap = Impossible<S::Ok, S::Error>;
+    type SerializeStruct = S::SerializeStruct;
+    type SerializeStructVariant = Impossible<S::Ok, S::Error>;
+
+    fn serialize_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, S::Error> {
+        let mut fields = self.serializer.serialize_struct(name, len + 1)?;
+        fields.serialize_field(DEFAULT_VERSION_HEADER_KEY, &**self.uris)?;
+        Ok(fields)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _value: &T,
+    ) -> Result<S::Ok, S::Error> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<S::Ok, S::Error> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, _value: &T) -> Result<S::Ok, S::Error> {
+        Err(not_a_struct())
+    }
+
+    not_a_struct! {
+        serialize_bool(v: bool) -> S::Ok;
+        serialize_i8(v: i8) -> S::Ok;
+        serialize_i16(v: i16) -> S::Ok;
+        serialize_i32(v: i32) -> S::Ok;
+        serialize_i64(v: i64) -> S::Ok;
+        serialize_u8(v: u8) -> S::Ok;
+        serialize_u16(v: u16) -> S::Ok;
+        serialize_u32(v: u32) -> S::Ok;
+        serialize_u64(v: u64) -> S::Ok;
+        serialize_f32(v: f32) -> S::Ok;
+        serialize_f64(v: f64) -> S::Ok;
+        serialize_char(v: char) -> S::Ok;
+        serialize_str(v: &str) -> S::Ok;
+        serialize_bytes(v: &[u8]) -> S::Ok;
+        serialize_none() -> S::Ok;
+        serialize_unit() -> S::Ok;
+        serialize_unit_struct(name: &'static str) -> S::Ok;
+        serialize_unit_variant(name: &'static str, index: u32, variant: &'static str) -> S::Ok;
+        serialize_seq(len: Option<usize>) -> Self::SerializeSeq;
+        serialize_tuple(len: usize) -> Self::SerializeTuple;
+        serialize_tuple_struct(name: &'static str, len: usize) -> Self::SerializeTupleStruct;
+        serialize_tuple_variant(
+            name: &'static str,
+            index: u32,
+            variant: &'static str,
+            len: usize
+        ) -> Self::SerializeTupleVariant;
+        serialize_map(len: Option<usize>) -> Self::SerializeMap;
+        serialize_struct_variant(
+            name: &'static str,
+            index: u32,
+            variant: &'static str,
+            len: usize
+        ) -> Self::SerializeStructVariant;
+    }
+}
+
+/// Split the version header prelude `#![<attribute>("a:1", "b:2")]` from the value
+fn split_prelude<'a>(
+    input: &'a str,
+    attribute: &str,
+) -> Result<(VersionGroupURIs, &'a str), DeserializeError> {
+    let missing = || DeserializeError::MissingVersionHeader(format!("#![{}(...)]", attribute));
+    let invalid = |message: &str| DeserializeError::InvalidPrelude(message.to_owned());
+
+    let mut rest = input
+        .trim_start()
+        .strip_prefix("#![")
+        .and_then(|rest| rest.trim_start().strip_prefix(attribute))
+        // Another attribute may start with the same name
+        .filter(|rest| !rest.starts_with(is_identifier_char))
+        .ok_or_else(missing)?
+        .trim_start()
+        .strip_prefix('(')
+        .ok_or_else(|| invalid("expected '('"))?;
+
+    let mut uris = VersionGroupURIs::new();
+    loop {
+        rest = rest.trim_start();
+        if let Some(after) = rest.strip_prefix(')') {
+            rest = after;
+            break;
+        }
+        let (literal, after) = split_string(rest).ok_or_else(|| invalid("expected a string"))?;
+        let uri: String = ::ron::de::from_str(literal).map_err(|err| invalid(&err.to_string()))?;
+        let uri: VersionGroupURI = uri
+            .parse()
+            .map_err(|err: crate::InvalidVersionGroupURIError| invalid(&err.to_string()))?;
+        uris.push(uri);
+
+        rest = after.trim_start();
+        if let Some(after) = rest.strip_prefix(',') {
+            rest = after;
+        } else if !rest.starts_with(')') {
+            return Err(invalid("expected ',' or ')'"));
+        }
+    }
+
+    let rest = rest
+        .trim_start()
+        .strip_prefix(']')
+        .ok_or_else(|| invalid("expected ']'"))?;
+    // Ron extensions must start the remaining document
+    Ok((uris, rest.trim_start()))
+}
+
+/// Split a leading string literal, with its quotes, from the rest of the input
+///
+/// The literal is decoded by the Ron parser, with its escapes.
+fn split_string(input: &str) -> Option<(&str, &str)> {
+    let mut chars = input.strip_prefix('"')?.char_indices();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '"' => return Some(input.split_at(index + 2)),
+            // An escaped quote doesn't end the literal
+            '\\' => {
+                chars.next()?;
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Whether `name` is a Ron identifier, like the name of a field
+fn is_identifier(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(is_identifier_char)
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// With the prelude layout, the version header is the leading entry `<attribute>` of the value
+#[cfg(feature = "diff-support")]
+impl crate::diff::DiffFormat for Options {
+    fn read_value(&self, input: &str) -> Result<crate::diff::Value, DeserializeError> {
+        use crate::diff::Value;
+
+        match &self.layout {
+            VersionHeaderLayout::LeadingField { .. } => Ok(::ron::de::from_str(input)?),
+            VersionHeaderLayout::Prelude { attribute } => {
+                let (uris, input) = split_prelude(input, attribute)?;
+                let header = Value::Seq(
+                    uris.iter()
+                        .map(|uri| Value::String(uri.to_string()))
+                        .collect(),
+                );
+                let mut entries = vec![(attribute.clone(), header)];
+                match ::ron::de::from_str(input)? {
+                    Value::Map(value) => entries.extend(value),
+                    value => entries.push((String::new(), value)),
+                }
+                Ok(Value::Map(entries))
+            }
+        }
+    }
+}
+
+/// Deserialize a Ron string with versioning support
+///
+/// # Generic Parameters
+/// - `T`: type to deserialize
+/// - `VMR`: resolver to find the version groups to use
+/// - `AGG`: aggregator to combine found version groups
+///
+/// # Parameters
+/// - `input`: Ron formatted string, the version header is the leading field `v` of the
+///   root struct
+/// - `resolver`: resolver to find the version groups to use
+/// - `aggregate`: aggregator to combine found version groups
+///
+/// # Returns
+/// The deserialized value or the error that occurred
+pub fn deserialize<'de, T, VMR, AGG>(
+    input: &'de str,
+    resolver: &VMR,
+    aggregate: &AGG,
+) -> Result<T, DeserializeError>
+where
+    T: DeserializeVersioned<'de>,
+    VMR: VersionGroupResolver,
+    AGG: AggregateVersionMap,
+{
+    Options::default().deserialize(input, resolver, aggregate)
+}
+
+/// Serialize a value formatted in Ron with its version header.
+///
+/// The version header will be serialized as the leading field of the value.
+///
+/// # Generic Parameters
+/// - `T`: type to serialize
+///
+/// # Parameters
+/// - `value`: The value to serialize
+/// - `uris`: The uris to serialize in the version header
+///
+/// # Returns
+/// The formatted string or the error that occurred, if any.
+pub fn serialize<T: Serialize>(
+    value: &T,
+    uris: &VersionGroupURIs,
+) -> Result<String, ::ron::ser::Error> {
+    Options::default().serialize(value, uris)
+}
+
+upgrade_functions!("Ron", "ron");
@@ -21,7 +21,7 @@ impl<'v, S, VM> VersionedSeed<'v, S, VM> {
     }
 }
 
-impl<'de, 'v, S, VM> DeserializeSeed<'de> for VersionedSeed<'de, S, VM>
+impl<'de, S, VM> DeserializeSeed<'de> for VersionedSeed<'de, S, VM>
 where
     S: DeserializeSeed<'de>,
     VM: VersionMap,
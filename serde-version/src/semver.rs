@@ -0,0 +1,172 @@
+//! Version groups identified by a semantic version
+//!
+//! The version of a [`VersionGroupURI`] is parsed as a semantic version, partial versions
+//! like `1` or `1.4` are accepted and completed with zeros.
+//!
+//! A group is declared for the release that changed the schema of its types, so
+//! [`SemverVersionGroupResolver`] resolves `org.my.company:1.4.7` to the closest lower group
+//! declared with the same major version, like `org.my.company:1.4`.
+
+use crate::{VersionGroupResolver, VersionGroupURI, VersionMap};
+use ::semver::Version;
+use std::collections::{BTreeMap, HashMap};
+
+/// Error used when the version of a uri is not a semantic version
+#[derive(Debug, Fail)]
+#[fail(
+    display = "Invalid semantic version in version group uri {}: {}",
+    uri, error
+)]
+pub struct InvalidSemverError {
+    pub uri: VersionGroupURI,
+    pub error: ::semver::Error,
+}
+
+/// What to do when a uri requests a minor version newer than the groups declared
+/// for its major version
+#[derive(Debug, Clone, Copy, Default)]
+pub enum NewerVersionPolicy {
+    /// The uri is not resolved
+    #[default]
+    Error,
+    /// The uri resolves to the closest lower group
+    ClosestLower,
+    /// The uri resolves to the closest lower group, the callback receives the requested uri
+    /// and the version of the group used instead
+    Warn(fn(&VersionGroupURI, &Version)),
+}
+
+/// Parse the version of a version group uri as a semantic version
+pub fn parse_version(uri: &VersionGroupURI) -> Result<Version, InvalidSemverError> {
+    let version = uri.version();
+    let end_of_numbers = version.find(['-', '+']).unwrap_or(version.len());
+    let (numbers, suffix) = version.split_at(end_of_numbers);
+    let completion = match numbers.matches('.').count() {
+        0 => ".0.0",
+        1 => ".0",
+        _ => "",
+    };
+
+    Version::parse(&format!("{}{}{}", numbers, completion, suffix)).map_err(|error| {
+        InvalidSemverError {
+            uri: uri.clone(),
+            error,
+        }
+    })
+}
+
+/// Resolve version group uris with semantic versions
+///
+/// See the [module documentation](index.html) for the resolution rules.
+#[derive(Debug, Clone)]
+pub struct SemverVersionGroupResolver<VM> {
+    groups: HashMap<String, BTreeMap<Version, VM>>,
+    policy: NewerVersionPolicy,
+}
+
+impl<VM> Default for SemverVersionGroupResolver<VM> {
+    fn default() -> Self {
+        Self {
+            groups: HashMap::new(),
+            policy: NewerVersionPolicy::default(),
+        }
+    }
+}
+
+impl<VM> SemverVersionGroupResolver<VM> {
+    pub fn new(policy: NewerVersionPolicy) -> Self {
+        Self {
+            groups: HashMap::new(),
+            policy,
+        }
+    }
+
+    /// Build a resolver from declared groups, like a `StaticVersionGroupResolver`
+    pub fn from_groups<I>(groups: I, policy: NewerVersionPolicy) -> Result<Self, InvalidSemverError>
+    where
+        I: IntoIterator<Item = (VersionGroupURI, VM)>,
+    {
+        let mut resolver = Self::new(policy);
+        for (uri, version_map) in groups {
+            resolver.insert(&uri, version_map)?;
+        }
+        Ok(resolver)
+    }
+
+    pub fn policy(&self) -> NewerVersionPolicy {
+        self.policy
+    }
+
+    pub fn set_policy(&mut self, policy: NewerVersionPolicy) {
+        self.policy = policy;
+    }
+
+    /// Declare the version map of a group
+    ///
+    /// # Returns
+    /// The version map previously declared for the same semantic version, if any.
+    pub fn insert(
+        &mut self,
+        uri: &VersionGroupURI,
+        version_map: VM,
+    ) -> Result<Option<VM>, InvalidSemverError> {
+        let version = parse_version(uri)?;
+        Ok(self
+            .groups
+            .entry(uri.api_group().to_owned())
+            .or_default()
+            .insert(version, version_map))
+    }
+
+    /// Find the group to use for a uri
+    ///
+    /// # Returns
+    /// The version of the group used and its version map, if any.
+    #[allow(clippy::type_complexity)]
+    pub fn resolve_semver(
+        &self,
+        uri: &VersionGroupURI,
+    ) -> Result<Option<(&Version, &VM)>, InvalidSemverError> {
+        let requested = parse_version(uri)?;
+        let versions = match self.groups.get(uri.api_group()) {
+            Some(versions) => versions,
+            None => return Ok(None),
+        };
+        let (version, version_map) = match versions.range(..=&requested).next_back() {
+            Some(entry) if entry.0.major == requested.major => entry,
+            _ => return Ok(None),
+        };
+        if version.minor == requested.minor {
+            return Ok(Some((version, version_map)));
+        }
+
+        // A minor release without a declared group did not change the schemas
+        let is_newer_than_declared = !matches!(
+            versions.range(&requested..).next(),
+            Some((next, _)) if next.major == requested.major
+        );
+        if !is_newer_than_declared {
+            return Ok(Some((version, version_map)));
+        }
+
+        Ok(match self.policy {
+            NewerVersionPolicy::Error => None,
+            NewerVersionPolicy::ClosestLower => Some((version, version_map)),
+            NewerVersionPolicy::Warn(warn) => {
+                warn(uri, version);
+                Some((version, version_map))
+            }
+        })
+    }
+}
+
+impl<VM: VersionMap> VersionGroupResolver for SemverVersionGroupResolver<VM> {
+    type VM = VM;
+
+    fn resolve(&self, uri: &VersionGroupURI) -> Option<&VM> {
+        self.resolve_semver(uri)
+            .ok()
+            .flatten()
+            .map(|(_, version_map)| version_map)
+    }
+}
@@ -0,0 +1,307 @@
+//! Skip the version header while deserializing the body of a document
+//!
+//! The body is read with the deserializer of the whole document, so its values can borrow
+//! from the input, and the entries of the version header are skipped on the way.
+
+use serde::de::value::{BorrowedStrDeserializer, StringDeserializer};
+use serde::de::{DeserializeSeed, EnumAccess, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::Deserializer;
+
+/// Wrap a deserializer to skip the entries `keys` of the map at `path`
+///
+/// `path` is the keys of the maps containing the skipped entries, from the root map.
+pub(crate) struct SkipEntries<'p, D> {
+    deserializer: D,
+    path: &'p [String],
+    keys: &'p [String],
+}
+
+impl<'p, D> SkipEntries<'p, D> {
+    pub fn new(deserializer: D, path: &'p [String], keys: &'p [String]) -> Self {
+        Self {
+            deserializer,
+            path,
+            keys,
+        }
+    }
+}
+
+macro_rules! forward_deserialize {
+    ($name:ident) => {forward_deserialize!($name, );};
+    ($name:ident, $($arg:tt => $ty:ty),*) => {
+        fn $name<V>(self, $($arg: $ty,)* visitor: V) -> Result<V::Value, D::Error>
+            where V: Visitor<'de>
+        {
+            let visitor = SkipEntriesVisitor {
+                visitor,
+                path: self.path,
+                keys: self.keys,
+            };
+            self.deserializer.$name($($arg,)* visitor)
+        }
+    }
+}
+
+impl<'de, 'p, D: Deserializer<'de>> Deserializer<'de> for SkipEntries<'p, D> {
+    type Error = D::Error;
+
+    forward_deserialize!(deserialize_any);
+    forward_deserialize!(deserialize_bool);
+    forward_deserialize!(deserialize_u8);
+    forward_deserialize!(deserialize_u16);
+    forward_deserialize!(deserialize_u32);
+    forward_deserialize!(deserialize_u64);
+    forward_deserialize!(deserialize_i8);
+    forward_deserialize!(deserialize_i16);
+    forward_deserialize!(deserialize_i32);
+    forward_deserialize!(deserialize_i64);
+    forward_deserialize!(deserialize_f32);
+    forward_deserialize!(deserialize_f64);
+    forward_deserialize!(deserialize_char);
+    forward_deserialize!(deserialize_str);
+    forward_deserialize!(deserialize_string);
+    forward_deserialize!(deserialize_unit);
+    forward_deserialize!(deserialize_option);
+    forward_deserialize!(deserialize_seq);
+    forward_deserialize!(deserialize_bytes);
+    forward_deserialize!(deserialize_byte_buf);
+    forward_deserialize!(deserialize_map);
+    forward_deserialize!(deserialize_unit_struct, name => &'static str);
+    forward_deserialize!(deserialize_newtype_struct, name => &'static str);
+    forward_deserialize!(deserialize_tuple_struct, name => &'static str, len => usize);
+    forward_deserialize!(deserialize_struct,
+                         name => &'static str,
+                         fields => &'static [&'static str]);
+    forward_deserialize!(deserialize_identifier);
+    forward_deserialize!(deserialize_tuple, len => usize);
+    forward_deserialize!(deserialize_enum,
+                         name => &'static str,
+                         variants => &'static [&'static str]);
+    forward_deserialize!(deserialize_ignored_any);
+
+    fn is_human_readable(&self) -> bool {
+        self.deserializer.is_human_readable()
+    }
+}
+
+/// Wrap a visitor to skip the entries `keys` of the map at `path` in the visited map
+struct SkipEntriesVisitor<'p, V> {
+    visitor: V,
+    path: &'p [String],
+    keys: &'p [String],
+}
+
+macro_rules! forward_visit {
+    ($name:ident, $ty:ty) => {
+        #[inline]
+        fn $name<E>(self, v: $ty) -> Result<V::Value, E>
+        where
+            E: serde::de::Error,
+        {
+            self.visitor.$name(v)
+        }
+    };
+}
+
+impl<'de, 'p, V: Visitor<'de>> Visitor<'de> for SkipEntriesVisitor<'p, V> {
+    type Value = V::Value;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.visitor.expecting(formatter)
+    }
+
+    forward_visit!(visit_bool, bool);
+    forward_visit!(visit_i8, i8);
+    forward_visit!(visit_i16, i16);
+    forward_visit!(visit_i32, i32);
+    forward_visit!(visit_i64, i64);
+    forward_visit!(visit_u8, u8);
+    forward_visit!(visit_u16, u16);
+    forward_visit!(visit_u32, u32);
+    forward_visit!(visit_u64, u64);
+    forward_visit!(visit_f32, f32);
+    forward_visit!(visit_f64, f64);
+    forward_visit!(visit_char, char);
+    forward_visit!(visit_bytes, &[u8]);
+    forward_visit!(visit_borrowed_bytes, &'de [u8]);
+    forward_visit!(visit_byte_buf, Vec<u8>);
+    forward_visit!(visit_str, &str);
+    forward_visit!(visit_borrowed_str, &'de str);
+    forward_visit!(visit_string, String);
+
+    #[inline]
+    fn visit_none<E>(self) -> Result<V::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        self.visitor.visit_none()
+    }
+
+    #[inline]
+    fn visit_some<D>(self, deserializer: D) -> Result<V::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        self.visitor
+            .visit_some(SkipEntries::new(deserializer, self.path, self.keys))
+    }
+
+    #[inline]
+    fn visit_unit<E>(self) -> Result<V::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        self.visitor.visit_unit()
+    }
+
+    #[inline]
+    fn visit_newtype_struct<D>(self, deserializer: D) -> Result<V::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        self.visitor
+            .visit_newtype_struct(SkipEntries::new(deserializer, self.path, self.keys))
+    }
+
+    #[inline]
+    fn visit_seq<A>(self, seq: A) -> Result<V::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        self.visitor.visit_seq(seq)
+    }
+
+    #[inline]
+    fn visit_map<A>(self, map: A) -> Result<V::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        self.visitor.visit_map(SkipEntriesMap {
+            map,
+            path: self.path,
+            keys: self.keys,
+            descend: false,
+        })
+    }
+
+    #[inline]
+    fn visit_enum<A>(self, data: A) -> Result<V::Value, A::Error>
+    where
+        A: EnumAccess<'de>,
+    {
+        self.visitor.visit_enum(data)
+    }
+}
+
+/// Entries of a map without the entries `keys` of the map at `path`
+struct SkipEntriesMap<'p, A> {
+    map: A,
+    path: &'p [String],
+    keys: &'p [String],
+    /// Whether the next value is the map at `path`
+    descend: bool,
+}
+
+impl<'de, 'p, A: MapAccess<'de>> MapAccess<'de> for SkipEntriesMap<'p, A> {
+    type Error = A::Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, A::Error>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        loop {
+            let key = match self.map.next_key_seed(KeySeed)? {
+                Some(key) => key,
+                None => return Ok(None),
+            };
+            let name = match &key {
+                Key::Borrowed(key) => key,
+                Key::Owned(key) => key.as_str(),
+            };
+            match self.path.first() {
+                None if self.keys.iter().any(|skipped| skipped == name) => {
+                    self.map.next_value::<IgnoredAny>()?;
+                    continue;
+                }
+                Some(parent) => self.descend = parent == name,
+                None => {}
+            }
+            return match key {
+                Key::Borrowed(key) => seed.deserialize(BorrowedStrDeserializer::new(key)),
+                Key::Owned(key) => seed.deserialize(StringDeserializer::new(key)),
+            }
+            .map(Some);
+        }
+    }
+
+    fn next_value_seed<S>(&mut self, seed: S) -> Result<S::Value, A::Error>
+    where
+        S: DeserializeSeed<'de>,
+    {
+        if std::mem::replace(&mut self.descend, false) {
+            self.map.next_value_seed(SkipEntriesSeed {
+                seed,
+                path: &self.path[1..],
+                keys: self.keys,
+            })
+        } else {
+            self.map.next_value_seed(seed)
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        None
+    }
+}
+
+/// Key of a map, borrowed from the input when possible
+enum Key<'de> {
+    Borrowed(&'de str),
+    Owned(String),
+}
+
+struct KeySeed;
+
+impl<'de> DeserializeSeed<'de> for KeySeed {
+    type Value = Key<'de>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Key<'de>, D::Error> {
+        deserializer.deserialize_str(self)
+    }
+}
+
+impl<'de> Visitor<'de> for KeySeed {
+    type Value = Key<'de>;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(formatter, "a key")
+    }
+
+    fn visit_borrowed_str<E: serde::de::Error>(self, v: &'de str) -> Result<Key<'de>, E> {
+        Ok(Key::Borrowed(v))
+    }
+
+    fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Key<'de>, E> {
+        Ok(Key::Owned(v.to_owned()))
+    }
+
+    fn visit_string<E: serde::de::Error>(self, v: String) -> Result<Key<'de>, E> {
+        Ok(Key::Owned(v))
+    }
+}
+
+/// Deserialize a map on the path of the skipped entries
+struct SkipEntriesSeed<'p, S> {
+    seed: S,
+    path: &'p [String],
+    keys: &'p [String],
+}
+
+impl<'de, 'p, S: DeserializeSeed<'de>> DeserializeSeed<'de> for SkipEntriesSeed<'p, S> {
+    type Value = S::Value;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<S::Value, D::Error> {
+        self.seed
+            .deserialize(SkipEntries::new(deserializer, self.path, self.keys))
+    }
+}
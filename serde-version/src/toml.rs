@@ -1,12 +1,27 @@
 //! Serialization utilities for the Toml format
+//!
+//! The version header is the `v` key of the root table by default, [`Options`] chooses
+//! another key, a table for it or a comment header.
 
+use crate::skip_entries::SkipEntries;
+use crate::upgrade::UpgradeFormat;
 use crate::version_map::AggregateVersionMap;
 use crate::{
-    AggregateVersionMapError, DeserializeVersioned, Error, VersionGroupResolver, VersionGroupURIs,
-    VersionMap, VersionedDeserializer,
+    AggregateVersionMapError, DeserializeVersioned, Error, InvalidVersionGroupURIError,
+    VersionGroupResolver, VersionGroupURI, VersionGroupURIs, VersionMap, VersionedDeserializer,
 };
 use failure::Fail;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[cfg(feature = "toml-edit-support")]
+pub mod edit;
+
+/// Key of the default version header, in the root table
+pub const DEFAULT_VERSION_HEADER_KEY: &str = "v";
+
+/// Marker of the default comment version header: `# serde-version: a:1, b:2`
+pub const DEFAULT_COMMENT_HEADER_MARKER: &str = "serde-version";
 
 /// Deserialization error
 #[derive(Debug, Fail)]
@@ -17,6 +32,12 @@ pub enum DeserializeError {
     De(Error<Error<::toml::de::Error>>),
     #[fail(display = "{}", 0)]
     AggregateError(AggregateVersionMapError),
+    #[fail(display = "Missing version header '{}'", 0)]
+    MissingVersionHeader(String),
+    #[fail(display = "Invalid version header: {}", 0)]
+    InvalidVersionHeader(::toml::de::Error),
+    #[fail(display = "Invalid version header comment: {}", 0)]
+    InvalidCommentHeader(InvalidVersionGroupURIError),
 }
 impl_from_enum! {
     DeserializeError::Toml => ::toml::de::Error,
@@ -24,6 +45,326 @@ impl_from_enum! {
     DeserializeError::AggregateError => AggregateVersionMapError,
 }
 
+/// Error raised while upgrading a Toml file
+pub type UpgradeError = crate::upgrade::UpgradeError<DeserializeError, ::toml::ser::Error>;
+
+/// Report of the upgrade of the Toml files of a directory
+pub type BatchReport = crate::upgrade::BatchReport<DeserializeError, ::toml::ser::Error>;
+
+/// Error raised while computing the diff of a migration
+#[cfg(feature = "diff-support")]
+pub type DiffError = crate::diff::DiffError<DeserializeError, ::toml::ser::Error>;
+
+/// Where the version header is written in a Toml document
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VersionHeaderLocation {
+    /// An array of uris under `key`, in the table at the path `table` (the root table if empty)
+    Key { table: Vec<String>, key: String },
+    /// A comment in the leading comments of the document: `# <marker>: a:1, b:2`
+    ///
+    /// The document does not contain extra keys.
+    Comment { marker: String },
+}
+
+impl VersionHeaderLocation {
+    fn missing(&self) -> DeserializeError {
+        DeserializeError::MissingVersionHeader(match self {
+            VersionHeaderLocation::Key { table, key } => table
+                .iter()
+                .chain(std::iter::once(key))
+                .map(String::as_str)
+                .collect::<Vec<_>>()
+                .join("."),
+            VersionHeaderLocation::Comment { marker } => format!("# {}:", marker),
+        })
+    }
+}
+
+/// Options of the Toml format
+///
+/// By default, the version header is the `v` key of the root table.
+///
+/// ```
+/// let options = serde_version::toml::Options::new()
+///     .header_table("meta")
+///     .header_key("versions");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Options {
+    header: VersionHeaderLocation,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            header: VersionHeaderLocation::Key {
+                table: Vec::new(),
+                key: DEFAULT_VERSION_HEADER_KEY.to_owned(),
+            },
+        }
+    }
+}
+
+impl Options {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn header(&self) -> &VersionHeaderLocation {
+        &self.header
+    }
+
+    /// Use the key `key` for the version header
+    ///
+    /// The table of the header is kept, a comment header is replaced by a key in the root table.
+    pub fn header_key<K: Into<String>>(mut self, key: K) -> Self {
+        let key = key.into();
+        self.header = match self.header {
+            VersionHeaderLocation::Key { table, .. } => VersionHeaderLocation::Key { table, key },
+            VersionHeaderLocation::Comment { .. } => VersionHeaderLocation::Key {
+                table: Vec::new(),
+                key,
+            },
+        };
+        self
+    }
+
+    /// Put the version header key in the table at the dotted path `path`, like `meta.versions`
+    ///
+    /// An empty path is the root table.
+    pub fn header_table(mut self, path: &str) -> Self {
+        let table = if path.is_empty() {
+            Vec::new()
+        } else {
+            path.split('.').map(str::to_owned).collect()
+        };
+        self.header = match self.header {
+            VersionHeaderLocation::Key { key, .. } => VersionHeaderLocation::Key { table, key },
+            VersionHeaderLocation::Comment { .. } => VersionHeaderLocation::Key {
+                table,
+                key: DEFAULT_VERSION_HEADER_KEY.to_owned(),
+            },
+        };
+        self
+    }
+
+    /// Write the version header in a comment: `# <marker>: a:1, b:2`
+    ///
+    /// See `DEFAULT_COMMENT_HEADER_MARKER`.
+    pub fn comment_header<M: Into<String>>(mut self, marker: M) -> Self {
+        self.header = VersionHeaderLocation::Comment {
+            marker: marker.into(),
+        };
+        self
+    }
+
+    /// Deserialize a Toml string with versioning support
+    ///
+    /// See [`deserialize`](fn.deserialize.html).
+    pub fn deserialize<'de, T, VMR, AGG>(
+        &self,
+        input: &'de str,
+        resolver: &VMR,
+        aggregate: &AGG,
+    ) -> Result<T, DeserializeError>
+    where
+        T: DeserializeVersioned<'de>,
+        VMR: VersionGroupResolver,
+        AGG: AggregateVersionMap,
+    {
+        let (uris, header_entry) = match &self.header {
+            VersionHeaderLocation::Key { table: path, key } => {
+                let mut table: ::toml::value::Table = ::toml::from_str(input)?;
+                let uris = take_version_header(&mut table, path, key)
+                    .ok_or_else(|| self.header.missing())?
+                    .map_err(DeserializeError::InvalidVersionHeader)?;
+                (uris, header_entry(&table, path, key))
+            }
+            VersionHeaderLocation::Comment { marker } => {
+                let uris = crate::comment_header::read(input, marker)
+                    .ok_or_else(|| self.header.missing())?
+                    .map_err(DeserializeError::InvalidCommentHeader)?;
+                (uris, Vec::new())
+            }
+        };
+
+        let version_map = aggregate.aggregate_version_maps(&uris, resolver)?;
+
+        // The body is read from the input, so it can borrow from it
+        let mut de = ::toml::Deserializer::new(input);
+        match header_entry.split_last() {
+            Some((key, path)) => deserialize_body(
+                SkipEntries::new(&mut de, path, std::slice::from_ref(key)),
+                &version_map,
+            ),
+            None => deserialize_body(&mut de, &version_map),
+        }
+    }
+
+    /// Serialize in a string a value formatted in Toml with its version header
+    ///
+    /// See [`serialize_inplace`](fn.serialize_inplace.html).
+    pub fn serialize_inplace<T: Serialize>(
+        &self,
+        str: &mut String,
+        value: &T,
+        uris: &VersionGroupURIs,
+    ) -> Result<(), ::toml::ser::Error> {
+        match &self.header {
+            VersionHeaderLocation::Key { table, key } if table.is_empty() => {
+                let mut ser = ::toml::ser::Serializer::new(str);
+                let mut header = BTreeMap::new();
+                header.insert(key.as_str(), &**uris);
+                Serialize::serialize(&header, &mut ser)?;
+                Serialize::serialize(value, &mut ser)?;
+            }
+            VersionHeaderLocation::Key { table, key } => {
+                let mut document = ::toml::Value::try_from(value)?;
+                let mut header_table = &mut document;
+                for name in table {
+                    header_table = match header_table {
+                        ::toml::Value::Table(parent) => parent
+                            .entry(name.clone())
+                            .or_insert_with(|| ::toml::Value::Table(Default::default())),
+                        _ => return Err(header_table_error(table)),
+                    };
+                }
+                match header_table {
+                    ::toml::Value::Table(header_table) => {
+                        header_table.insert(key.clone(), ::toml::Value::try_from(&**uris)?)
+                    }
+                    _ => return Err(header_table_error(table)),
+                };
+                let mut ser = ::toml::ser::Serializer::new(str);
+                Serialize::serialize(&document, &mut ser)?;
+            }
+            VersionHeaderLocation::Comment { marker } => {
+                let uris: Vec<String> = uris.iter().map(ToString::to_string).collect();
+                str.push_str(&format!("# {}: {}\n", marker, uris.join(", ")));
+                let mut ser = ::toml::ser::Serializer::new(str);
+                Serialize::serialize(value, &mut ser)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Serialize a value formatted in Toml with its version header
+    ///
+    /// See [`serialize`](fn.serialize.html).
+    pub fn serialize<T: Serialize>(
+        &self,
+        value: &T,
+        uris: &VersionGroupURIs,
+    ) -> Result<String, ::toml::ser::Error> {
+        let mut str = String::new();
+        self.serialize_inplace(&mut str, value, uris)?;
+        Ok(str)
+    }
+}
+
+impl UpgradeFormat for Options {
+    type DeserializeError = DeserializeError;
+    type SerializeError = ::toml::ser::Error;
+
+    fn read_document<T, VMR, AGG>(
+        &self,
+        input: &[u8],
+        resolver: &VMR,
+        aggregate: &AGG,
+    ) -> Result<T, UpgradeError>
+    where
+        T: for<'de> DeserializeVersioned<'de>,
+        VMR: VersionGroupResolver,
+        AGG: AggregateVersionMap,
+    {
+        let input = crate::upgrade::as_str(input)?;
+        self.deserialize(input, resolver, aggregate)
+            .map_err(UpgradeError::Deserialize)
+    }
+
+    fn write_document<T: Serialize>(
+        &self,
+        value: &T,
+        uris: &VersionGroupURIs,
+    ) -> Result<Vec<u8>, UpgradeError> {
+        Ok(self
+            .serialize(value, uris)
+            .map_err(UpgradeError::Serialize)?
+            .into_bytes())
+    }
+}
+
+/// Remove the version header from its table
+///
+/// A table left empty is removed as well.
+fn take_version_header(
+    table: &mut ::toml::value::Table,
+    path: &[String],
+    key: &str,
+) -> Option<Result<VersionGroupURIs, ::toml::de::Error>> {
+    let header = match path.split_first() {
+        None => table.remove(key)?,
+        Some((name, path)) => {
+            let child = match table.get_mut(name)? {
+                ::toml::Value::Table(child) => child,
+                _ => return None,
+            };
+            let header = take_version_header(child, path, key)?;
+            if child.is_empty() {
+                table.remove(name);
+            }
+            return Some(header);
+        }
+    };
+    Some(Vec::<VersionGroupURI>::deserialize(header).map(Into::into))
+}
+
+/// Path of the entry removed with the version header
+///
+/// It is the path of the header key, or of its first table left empty, which is removed
+/// as well.
+fn header_entry(table: &::toml::value::Table, path: &[String], key: &str) -> Vec<String> {
+    let mut entry = Vec::with_capacity(path.len() + 1);
+    let mut table = table;
+    for name in path {
+        entry.push(name.clone());
+        match table.get(name) {
+            Some(::toml::Value::Table(child)) => table = child,
+            _ => return entry,
+        }
+    }
+    entry.push(key.to_owned());
+    entry
+}
+
+fn deserialize_body<'de, T, D, VM>(deserializer: D, version_map: VM) -> Result<T, DeserializeError>
+where
+    T: DeserializeVersioned<'de>,
+    D: serde::Deserializer<'de, Error = ::toml::de::Error>,
+    VM: VersionMap,
+{
+    let de = VersionedDeserializer::new(deserializer, version_map.clone());
+    Ok(DeserializeVersioned::deserialize_versioned(
+        de,
+        version_map,
+    )?)
+}
+
+fn header_table_error(table: &[String]) -> ::toml::ser::Error {
+    serde::ser::Error::custom(format!(
+        "the version header table '{}' is not a table",
+        table.join(".")
+    ))
+}
+
+#[cfg(feature = "diff-support")]
+impl crate::diff::DiffFormat for Options {
+    fn read_value(&self, input: &str) -> Result<crate::diff::Value, DeserializeError> {
+        Ok(::toml::from_str(input)?)
+    }
+}
+
 /// Deserialize a Toml string with versioning support
 ///
 /// # Generic Parameters
@@ -32,7 +373,7 @@ impl_from_enum! {
 /// - `AGG`: aggregator to combine found version groups
 ///
 /// # Parameters
-/// - `input`: Toml formatted string, first entry is the version header
+/// - `input`: Toml formatted string, the version header is the `v` key of the root table
 /// - `resolver`: resolver to find the version groups to use
 /// - `aggregate`: aggregator to combine found version groups
 ///
@@ -44,27 +385,11 @@ pub fn deserialize<'de, T, VMR, AGG>(
     aggregate: &AGG,
 ) -> Result<T, DeserializeError>
 where
-    VMR::VM: VersionMap,
-    T: DeserializeVersioned<'de, VMR::VM>,
+    T: DeserializeVersioned<'de>,
     VMR: VersionGroupResolver,
     AGG: AggregateVersionMap,
 {
-    let mut de = ::toml::de::Deserializer::new(input);
-    let uris: VersionGroupURIs = serde::Deserialize::deserialize(&mut de)?;
-
-    let version_map = aggregate.aggregate_version_maps(&uris, resolver)?;
-
-    // unwrap: the VersionGroupURIs ends with an array, so the token ']'
-    // must exists as we successfully deserialized it.
-    let end_of_version_header = input.find(']').unwrap();
-
-    let input_left = input.split_at(end_of_version_header + 1).1;
-    let mut de2 = ::toml::de::Deserializer::new(input_left);
-    let de3 = VersionedDeserializer::new(&mut de2, &version_map);
-    Ok(DeserializeVersioned::deserialize_versioned(
-        de3,
-        &version_map,
-    )?)
+    Options::default().deserialize(input, resolver, aggregate)
 }
 
 /// Serialize in a string a value formatted in Toml with its version header.
@@ -81,16 +406,12 @@ where
 ///
 /// # Returns
 /// The error that occurred, if any.
-pub fn serialize_inplace<'s, T: Serialize>(
-    str: &'s mut String,
+pub fn serialize_inplace<T: Serialize>(
+    str: &mut String,
     value: &T,
     uris: &VersionGroupURIs,
 ) -> Result<(), ::toml::ser::Error> {
-    let mut ser = ::toml::ser::Serializer::new(str);
-    Serialize::serialize(&*uris, &mut ser)?;
-    Serialize::serialize(value, &mut ser)?;
-
-    Ok(())
+    Options::default().serialize_inplace(str, value, uris)
 }
 
 /// Serialize a value formatted in Toml with its version header.
@@ -106,11 +427,11 @@ pub fn serialize_inplace<'s, T: Serialize>(
 ///
 /// # Returns
 /// The formatted string or the error that occurred, if any.
-pub fn serialize<'s, T: Serialize>(
+pub fn serialize<T: Serialize>(
     value: &T,
     uris: &VersionGroupURIs,
 ) -> Result<String, ::toml::ser::Error> {
-    let mut str = String::new();
-    serialize_inplace(&mut str, value, uris)?;
-    Ok(str)
+    Options::default().serialize(value, uris)
 }
+
+upgrade_functions!("Toml", "toml");
@@ -0,0 +1,467 @@
+//! Format-preserving upgrades of Toml documents
+//!
+//! [`upgrade`] migrates a document like [`upgrade_file`](../fn.upgrade_file.html), but applies
+//! the migrated value to the original document instead of writing a new one: comments,
+//! ordering and formatting of the keys are kept. A key whose value didn't change is left
+//! byte-identical, a key with a new value keeps its comments, a removed key is removed and a
+//! new key is added at the end of its table. A renamed key keeps its place and comments when it
+//! is declared in [`Renames`], it is a removed key and a new key otherwise.
+//!
+//! ```toml
+//! # My config, edited by hand
+//! v = ["my.company:1"]
+//! name = "my config name" # the displayed name
+//! ```
+//! is upgraded to
+//! ```toml
+//! # My config, edited by hand
+//! v = ["my.company:2"]
+//! name = "my config name" # the displayed name
+//! enabled = true
+//! ```
+
+use super::{DeserializeError, Options, UpgradeError, VersionHeaderLocation};
+use crate::upgrade::{is_up_to_date, UpgradeOptions, UpgradeReport};
+use crate::version_map::{AggregateVersionMap, RecordingAggregate};
+use crate::{DeserializeVersioned, VersionGroupResolver, VersionGroupURIs};
+use ::toml_edit::{ArrayOfTables, DocumentMut, InlineTable, Item, Key, Table, Value};
+use serde::Serialize;
+use std::path::Path;
+
+impl Options {
+    /// Upgrade a Toml document to the current version, preserving its formatting
+    ///
+    /// See [`upgrade`](edit/fn.upgrade.html).
+    pub fn upgrade_preserving<T, VMR, AGG>(
+        &self,
+        input: &str,
+        resolver: &VMR,
+        aggregate: &AGG,
+        current_uris: &VersionGroupURIs,
+        renames: &Renames,
+    ) -> Result<String, UpgradeError>
+    where
+        T: Serialize + for<'de> DeserializeVersioned<'de>,
+        VMR: VersionGroupResolver,
+        AGG: AggregateVersionMap,
+    {
+        let value: T = self
+            .deserialize(input, resolver, aggregate)
+            .map_err(UpgradeError::Deserialize)?;
+        // The comment header is rewritten in the text of the document
+        let upgraded = match &self.header {
+            VersionHeaderLocation::Key { .. } => self.serialize(&value, current_uris),
+            VersionHeaderLocation::Comment { .. } => ::toml::to_string(&value),
+        }
+        .map_err(UpgradeError::Serialize)?;
+
+        let mut document = input.parse::<DocumentMut>().map_err(|err| {
+            UpgradeError::Deserialize(DeserializeError::Toml(serde::de::Error::custom(err)))
+        })?;
+        let upgraded = upgraded
+            .parse::<DocumentMut>()
+            .map_err(|err| UpgradeError::Serialize(serde::ser::Error::custom(err)))?;
+
+        let mut merge = Merge {
+            positions: Positions::after(document.as_table()),
+            renames,
+            path: Vec::new(),
+        };
+        let root = merge.table(document.as_table(), upgraded.as_table());
+        *document.as_table_mut() = root;
+
+        let output = document.to_string();
+        Ok(match &self.header {
+            VersionHeaderLocation::Key { .. } => output,
+            VersionHeaderLocation::Comment { marker } => {
+                replace_comment_header(&output, marker, current_uris)
+            }
+        })
+    }
+
+    /// Upgrade a Toml file in place to the current version, preserving its formatting
+    ///
+    /// See [`upgrade_file`](edit/fn.upgrade_file.html).
+    pub fn upgrade_file_preserving<T, P, VMR, AGG>(
+        &self,
+        path: P,
+        resolver: &VMR,
+        aggregate: &AGG,
+        current_uris: &VersionGroupURIs,
+        renames: &Renames,
+        upgrade: &UpgradeOptions,
+    ) -> Result<UpgradeReport, UpgradeError>
+    where
+        T: Serialize + for<'de> DeserializeVersioned<'de>,
+        P: AsRef<Path>,
+        VMR: VersionGroupResolver,
+        AGG: AggregateVersionMap,
+    {
+        upgrade.upgrade_file_with(path, |input| {
+            let input = crate::upgrade::as_str(input)?;
+            let recording = RecordingAggregate::new(aggregate);
+            let output = self.upgrade_preserving::<T, _, _>(
+                input,
+                resolver,
+                &recording,
+                current_uris,
+                renames,
+            )?;
+            if is_up_to_date(&recording, current_uris) {
+                return Ok(None);
+            }
+            Ok(Some(output.into_bytes()))
+        })
+    }
+}
+
+/// Positions of the new tables in the document, after the existing ones
+struct Positions(usize);
+
+impl Positions {
+    fn after(root: &Table) -> Self {
+        fn max_position(table: &Table) -> usize {
+            table
+                .iter()
+                .map(|(_, item)| match item {
+                    Item::Table(table) => max_position(table),
+                    Item::ArrayOfTables(array) => array.iter().map(max_position).max().unwrap_or(0),
+                    _ => 0,
+                })
+                .chain(table.position())
+                .max()
+                .unwrap_or(0)
+        }
+        Positions(max_position(root) + 1)
+    }
+
+    /// Move the tables of a new item to the end of the document
+    fn assign(&mut self, item: &mut Item) {
+        match item {
+            Item::Table(table) => self.assign_table(table),
+            Item::ArrayOfTables(array) => {
+                array.iter_mut().for_each(|table| self.assign_table(table))
+            }
+            _ => {}
+        }
+    }
+
+    fn assign_table(&mut self, table: &mut Table) {
+        if table.position().is_some() {
+            table.set_position(self.0);
+            self.0 += 1;
+        }
+        table.iter_mut().for_each(|(_, item)| self.assign(item));
+    }
+}
+
+/// Keys renamed by a migration, by their path in the original document
+///
+/// A declared renamed key keeps its place and comments, the other renamed keys are removed
+/// and added again at the end of their table. The path is dotted like the path of the header
+/// table, the new name is a key of the same table. It applies to each table of an array of
+/// tables.
+///
+/// ```
+/// let renames = serde_version::toml::edit::Renames::new()
+///     .key("size", "capacity")
+///     .key("server.address", "host");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Renames {
+    keys: Vec<(Vec<String>, String)>,
+}
+
+impl Renames {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Rename the key at the dotted path `path` to `name`
+    pub fn key<P: AsRef<str>, N: Into<String>>(mut self, path: P, name: N) -> Self {
+        let path = path.as_ref().split('.').map(str::to_owned).collect();
+        self.keys.push((path, name.into()));
+        self
+    }
+
+    /// New name of the key `key` of the table at `path`, if it is renamed
+    fn renamed(&self, path: &[String], key: &str) -> Option<&str> {
+        self.keys
+            .iter()
+            .find(|(old, _)| {
+                old.split_last()
+                    .is_some_and(|(old_key, old_path)| old_key == key && old_path == path)
+            })
+            .map(|(_, name)| name.as_str())
+    }
+}
+
+/// Apply the migrated document to the original one
+struct Merge<'r> {
+    positions: Positions,
+    renames: &'r Renames,
+    /// Path of the merged table in the original document
+    path: Vec<String>,
+}
+
+impl<'r> Merge<'r> {
+    /// Apply the `upgraded` table to the `original` one
+    fn table(&mut self, original: &Table, upgraded: &Table) -> Table {
+        let mut merged = original.clone();
+        merged.clear();
+        for (name, item) in original.iter() {
+            let key = original.key(name).expect("the key of an entry");
+            if let Some(upgraded_item) = upgraded.get(name) {
+                let item = self.child(name, |merge| merge.item(item, upgraded_item));
+                merged.insert_formatted(key, item);
+            } else if let Some((new_name, upgraded_item)) = self
+                .renamed_key(name)
+                .filter(|new_name| !original.contains_key(new_name))
+                .and_then(|new_name| Some((new_name, upgraded.get(new_name)?)))
+            {
+                let item = self.child(name, |merge| merge.item(item, upgraded_item));
+                merged.insert_formatted(&renamed(key, new_name), item);
+            }
+        }
+        for (name, item) in upgraded.iter() {
+            if !merged.contains_key(name) {
+                let mut item = item.clone();
+                self.positions.assign(&mut item);
+                merged.insert_formatted(upgraded.key(name).expect("the key of an entry"), item);
+            }
+        }
+        merged
+    }
+
+    /// Apply the `upgraded` inline table to the `original` one
+    fn inline_table(&mut self, original: &InlineTable, upgraded: &InlineTable) -> InlineTable {
+        let mut merged = original.clone();
+        merged.clear();
+        for (name, value) in original.iter() {
+            let key = original.key(name).expect("the key of an entry");
+            if let Some(upgraded_value) = upgraded.get(name) {
+                let value = self.child(name, |merge| merge.value(value, upgraded_value));
+                merged.insert_formatted(key, value);
+            } else if let Some((new_name, upgraded_value)) = self
+                .renamed_key(name)
+                .filter(|new_name| !original.contains_key(new_name))
+                .and_then(|new_name| Some((new_name, upgraded.get(new_name)?)))
+            {
+                let value = self.child(name, |merge| merge.value(value, upgraded_value));
+                merged.insert_formatted(&renamed(key, new_name), value);
+            }
+        }
+        let last = merged.iter().last().map(|(name, _)| name.to_owned());
+        let mut added = None;
+        for (name, value) in upgraded.iter() {
+            if !merged.contains_key(name) {
+                let mut value = value.clone();
+                value.decor_mut().clear();
+                merged.insert(name, value);
+                added = Some(name);
+            }
+        }
+        // The spacing before the closing brace follows the last entry
+        if let (Some(last), Some(added)) = (last, added) {
+            let suffix = merged
+                .get_mut(&last)
+                .and_then(|value| value.decor_mut().suffix().cloned());
+            if let Some(suffix) = suffix {
+                let value = merged.get_mut(&last).expect("the last entry");
+                value.decor_mut().set_suffix("");
+                let value = merged.get_mut(added).expect("the added entry");
+                value.decor_mut().set_prefix(" ");
+                value.decor_mut().set_suffix(suffix);
+            }
+        }
+        merged
+    }
+
+    fn item(&mut self, original: &Item, upgraded: &Item) -> Item {
+        match (original, upgraded) {
+            (Item::Table(original), Item::Table(upgraded)) => {
+                Item::Table(self.table(original, upgraded))
+            }
+            (Item::ArrayOfTables(original), Item::ArrayOfTables(upgraded)) => {
+                let mut merged = ArrayOfTables::new();
+                for (index, table) in upgraded.iter().enumerate() {
+                    match original.get(index) {
+                        Some(original) => merged.push(self.table(original, table)),
+                        None => {
+                            let mut table = table.clone();
+                            self.positions.assign_table(&mut table);
+                            merged.push(table);
+                        }
+                    }
+                }
+                Item::ArrayOfTables(merged)
+            }
+            (Item::Value(original), Item::Value(upgraded)) => {
+                Item::Value(self.value(original, upgraded))
+            }
+            // The tables keep their original style
+            (Item::Value(Value::InlineTable(original)), Item::Table(upgraded)) => {
+                let upgraded = upgraded.clone().into_inline_table();
+                Item::Value(Value::InlineTable(self.inline_table(original, &upgraded)))
+            }
+            (Item::Table(original), Item::Value(Value::InlineTable(upgraded))) => {
+                let mut upgraded = upgraded.clone().into_table();
+                self.positions.assign_table(&mut upgraded);
+                Item::Table(self.table(original, &upgraded))
+            }
+            (_, upgraded) => {
+                let mut upgraded = upgraded.clone();
+                self.positions.assign(&mut upgraded);
+                upgraded
+            }
+        }
+    }
+
+    fn value(&mut self, original: &Value, upgraded: &Value) -> Value {
+        if same_value(original, upgraded) {
+            return original.clone();
+        }
+        if let (Value::InlineTable(original), Value::InlineTable(upgraded)) = (original, upgraded) {
+            return Value::InlineTable(self.inline_table(original, upgraded));
+        }
+        // The comments around the value are kept
+        let mut merged = upgraded.clone();
+        *merged.decor_mut() = original.decor().clone();
+        merged
+    }
+
+    /// Merge the entry `name` of the current table
+    fn child<R>(&mut self, name: &str, merge: impl FnOnce(&mut Self) -> R) -> R {
+        self.path.push(name.to_owned());
+        let merged = merge(self);
+        self.path.pop();
+        merged
+    }
+
+    /// New name of the key `name` of the current table, if it is renamed
+    fn renamed_key(&self, name: &str) -> Option<&'r str> {
+        self.renames.renamed(&self.path, name)
+    }
+}
+
+fn renamed(key: &Key, name: &str) -> Key {
+    Key::new(name)
+        .with_leaf_decor(key.leaf_decor().clone())
+        .with_dotted_decor(key.dotted_decor().clone())
+}
+
+fn same_value(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::String(a), Value::String(b)) => a.value() == b.value(),
+        (Value::Integer(a), Value::Integer(b)) => a.value() == b.value(),
+        (Value::Float(a), Value::Float(b)) => {
+            a.value() == b.value() || (a.value().is_nan() && b.value().is_nan())
+        }
+        (Value::Boolean(a), Value::Boolean(b)) => a.value() == b.value(),
+        (Value::Datetime(a), Value::Datetime(b)) => a.value() == b.value(),
+        (Value::Array(a), Value::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| same_value(a, b))
+        }
+        (Value::InlineTable(a), Value::InlineTable(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .all(|(name, a)| b.get(name).is_some_and(|b| same_value(a, b)))
+        }
+        _ => false,
+    }
+}
+
+/// Replace the version header in the leading comments of a document
+///
+/// Only the uris are replaced, the marker and the spacing of the comment are kept.
+fn replace_comment_header(input: &str, marker: &str, uris: &VersionGroupURIs) -> String {
+    let uris: Vec<String> = uris.iter().map(ToString::to_string).collect();
+    let uris = uris.join(", ");
+
+    match crate::comment_header::find(input, marker) {
+        Some(range) => format!("{} {}{}", &input[..range.start], uris, &input[range.end..]),
+        None => format!("# {}: {}\n{}", marker, uris, input),
+    }
+}
+
+/// Upgrade a Toml document to the current version, preserving its formatting
+///
+/// The document is deserialized with versioning support and the migrated value is applied to
+/// the original document: the keys that didn't change are left byte-identical with their
+/// comments, and the version header is rewritten with `current_uris`.
+///
+/// # Generic Parameters
+/// - `T`: type of the document
+/// - `VMR`: resolver to find the version groups to use
+/// - `AGG`: aggregator to combine found version groups
+///
+/// # Parameters
+/// - `input`: Toml formatted string, the version header is the `v` key of the root table
+/// - `resolver`: resolver to find the version groups to use
+/// - `aggregate`: aggregator to combine found version groups
+/// - `current_uris`: The uris to serialize in the version header of the upgraded document
+///
+/// # Returns
+/// The upgraded document or the error that occurred
+pub fn upgrade<T, VMR, AGG>(
+    input: &str,
+    resolver: &VMR,
+    aggregate: &AGG,
+    current_uris: &VersionGroupURIs,
+) -> Result<String, UpgradeError>
+where
+    T: Serialize + for<'de> DeserializeVersioned<'de>,
+    VMR: VersionGroupResolver,
+    AGG: AggregateVersionMap,
+{
+    Options::default().upgrade_preserving::<T, _, _>(
+        input,
+        resolver,
+        aggregate,
+        current_uris,
+        &Renames::default(),
+    )
+}
+
+/// Upgrade a Toml file in place to the current version, preserving its formatting
+///
+/// See [`upgrade`](fn.upgrade.html) for the upgrade of the document. The file is replaced
+/// atomically, unless its version header is already `current_uris`. Use
+/// [`Options::upgrade_file_preserving`] to keep a backup of the original file or to declare
+/// renamed keys.
+///
+/// # Generic Parameters
+/// - `T`: type of the contents of the file
+/// - `P`: path of the file
+/// - `VMR`: resolver to find the version groups to use
+/// - `AGG`: aggregator to combine found version groups
+///
+/// # Parameters
+/// - `path`: path of the Toml file to upgrade
+/// - `resolver`: resolver to find the version groups to use
+/// - `aggregate`: aggregator to combine found version groups
+/// - `current_uris`: The uris to serialize in the version header of the upgraded file
+///
+/// # Returns
+/// The report of the upgrade or the error that occurred
+pub fn upgrade_file<T, P, VMR, AGG>(
+    path: P,
+    resolver: &VMR,
+    aggregate: &AGG,
+    current_uris: &VersionGroupURIs,
+) -> Result<UpgradeReport, UpgradeError>
+where
+    T: Serialize + for<'de> DeserializeVersioned<'de>,
+    P: AsRef<Path>,
+    VMR: VersionGroupResolver,
+    AGG: AggregateVersionMap,
+{
+    Options::default().upgrade_file_preserving::<T, _, _, _>(
+        path,
+        resolver,
+        aggregate,
+        current_uris,
+        &Renames::default(),
+        &UpgradeOptions::default(),
+    )
+}
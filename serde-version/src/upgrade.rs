@@ -0,0 +1,542 @@
+//! Upgrade files in place: read any version, write the current one with a fresh header
+//!
+//! Each format module has an `upgrade_file` function built on [`UpgradeOptions`]:
+//! ```rust,ignore
+//! let report = serde_version::toml::upgrade_file::<Config, _, _, _>(
+//!     "config.toml",
+//!     &resolver,
+//!     &(),
+//!     &current_uris,
+//! )?;
+//! if report.changed {
+//!     println!("config.toml upgraded");
+//! }
+//! ```
+//!
+//! A file whose version header is already `current_uris` is left untouched, its formatting and
+//! comments are kept. Otherwise, the upgraded file is written in a temporary file next to it,
+//! then renamed over it: the file is either the original or the upgraded one, even if the
+//! process is interrupted.
+//!
+//! The `upgrade_dir` functions upgrade all the files of a directory and report the outcome of
+//! each file, an error doesn't stop the others:
+//! ```rust,ignore
+//! let report = serde_version::toml::upgrade_dir::<Config, _, _, _>(
+//!     "assets",
+//!     &resolver,
+//!     &(),
+//!     &current_uris,
+//! );
+//! for file in report.failed() {
+//!     eprintln!("{}: {}", file.path.display(), file.result.as_ref().unwrap_err());
+//! }
+//! ```
+//!
+//! These functions use the default `Options` of the format and [`UpgradeOptions`]. The
+//! `Options` of the format modules implement [`UpgradeFormat`]: call
+//! [`UpgradeOptions::upgrade_file_as`] and [`UpgradeOptions::upgrade_dir_as`] with them to keep
+//! a backup, to choose the files with another [`FileFilter`] or to use other options. Other
+//! formats implement [`UpgradeFormat`] to be upgraded the same way.
+
+use crate::version_map::{AggregateVersionMap, AggregatedVersionMap, RecordingAggregate};
+use crate::{DeserializeVersioned, VersionGroupResolver, VersionGroupURIs};
+use failure::Fail;
+use serde::Serialize;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Default extension of the backup files, appended to the name of the upgraded file
+pub const DEFAULT_BACKUP_EXTENSION: &str = "bak";
+
+/// Error raised while upgrading a file
+#[derive(Debug)]
+pub enum UpgradeError<D, S> {
+    /// The file can't be read or written
+    Io(io::Error),
+    /// The document can't be deserialized
+    Deserialize(D),
+    /// The upgraded value can't be serialized
+    Serialize(S),
+}
+
+impl<D, S> From<io::Error> for UpgradeError<D, S> {
+    fn from(error: io::Error) -> Self {
+        UpgradeError::Io(error)
+    }
+}
+
+impl<D, S> std::fmt::Display for UpgradeError<D, S>
+where
+    D: std::fmt::Display,
+    S: std::fmt::Display,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            UpgradeError::Io(err) => write!(f, "{}", err),
+            UpgradeError::Deserialize(err) => write!(f, "Can't read the document: {}", err),
+            UpgradeError::Serialize(err) => write!(f, "Can't write the upgraded document: {}", err),
+        }
+    }
+}
+
+impl<D: Fail, S: Fail> Fail for UpgradeError<D, S> {
+    fn cause(&self) -> Option<&dyn Fail> {
+        match self {
+            UpgradeError::Io(err) => Some(err),
+            UpgradeError::Deserialize(err) => Some(err),
+            UpgradeError::Serialize(err) => Some(err),
+        }
+    }
+}
+
+/// Outcome of the upgrade of a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpgradeReport {
+    /// Whether the file was rewritten: it is left untouched when it is already up to date
+    pub changed: bool,
+    /// Path of the backup of the original file, if any
+    pub backup: Option<PathBuf>,
+}
+
+/// Options of the upgrade of a file
+///
+/// By default, no backup of the original file is kept.
+///
+/// ```
+/// let options = serde_version::upgrade::UpgradeOptions::new().backup("orig");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UpgradeOptions {
+    backup_extension: Option<String>,
+}
+
+impl UpgradeOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep a copy of the original file, named after it with the extension `extension`
+    /// appended: `config.toml` is copied to `config.toml.bak`
+    ///
+    /// See `DEFAULT_BACKUP_EXTENSION`.
+    pub fn backup<E: Into<String>>(mut self, extension: E) -> Self {
+        self.backup_extension = Some(extension.into());
+        self
+    }
+
+    /// Path of the backup of the file `path`, if a backup is kept
+    pub fn backup_path(&self, path: &Path) -> Option<PathBuf> {
+        self.backup_extension
+            .as_ref()
+            .map(|extension| append_extension(path, extension))
+    }
+
+    /// Upgrade the file `path` with `upgrade`, the function computing the upgraded contents
+    /// from the original ones
+    ///
+    /// The format modules use it for their `upgrade_file` functions, it can be used for other
+    /// formats too. The file is rewritten atomically, unless `upgrade` finds it up to date.
+    ///
+    /// # Parameters
+    /// - `path`: path of the file to upgrade
+    /// - `upgrade`: computes the upgraded contents from the original contents, or `None` when
+    ///   the file is up to date
+    ///
+    /// # Returns
+    /// The report of the upgrade or the error that occurred
+    pub fn upgrade_file_with<P, D, S, F>(
+        &self,
+        path: P,
+        upgrade: F,
+    ) -> Result<UpgradeReport, UpgradeError<D, S>>
+    where
+        P: AsRef<Path>,
+        F: FnOnce(&[u8]) -> Result<Option<Vec<u8>>, UpgradeError<D, S>>,
+    {
+        let path = path.as_ref();
+        let upgraded = match upgrade(&fs::read(path)?)? {
+            Some(upgraded) => upgraded,
+            None => {
+                return Ok(UpgradeReport {
+                    changed: false,
+                    backup: None,
+                })
+            }
+        };
+
+        let backup = self.backup_path(path);
+        if let Some(backup) = &backup {
+            fs::copy(path, backup)?;
+        }
+        write_atomic(path, &upgraded)?;
+        Ok(UpgradeReport {
+            changed: true,
+            backup,
+        })
+    }
+
+    /// Upgrade the file `path` of the format `format` to the current version
+    ///
+    /// The format modules use it for their `upgrade_file` functions. The file is read with any
+    /// known version. It is left untouched when its version header is already `current_uris`,
+    /// otherwise it is written again with `current_uris` as version header.
+    ///
+    /// # Parameters
+    /// - `format`: reads and writes the documents, like `json::Options`
+    /// - `path`: path of the file to upgrade
+    /// - `resolver`: resolver to find the version groups to use
+    /// - `aggregate`: aggregator to combine found version groups
+    /// - `current_uris`: The uris to serialize in the version header of the upgraded file
+    ///
+    /// # Returns
+    /// The report of the upgrade or the error that occurred
+    pub fn upgrade_file_as<T, F, P, VMR, AGG>(
+        &self,
+        format: &F,
+        path: P,
+        resolver: &VMR,
+        aggregate: &AGG,
+        current_uris: &VersionGroupURIs,
+    ) -> Result<UpgradeReport, UpgradeError<F::DeserializeError, F::SerializeError>>
+    where
+        T: Serialize + for<'de> DeserializeVersioned<'de>,
+        F: UpgradeFormat,
+        P: AsRef<Path>,
+        VMR: VersionGroupResolver,
+        AGG: AggregateVersionMap,
+    {
+        self.upgrade_file_with(path, |input| {
+            let recording = RecordingAggregate::new(aggregate);
+            let value: T = format.read_document(input, resolver, &recording)?;
+            if is_up_to_date(&recording, current_uris) {
+                return Ok(None);
+            }
+            format.write_document(&value, current_uris).map(Some)
+        })
+    }
+}
+
+/// Format of the files upgraded by [`UpgradeOptions::upgrade_file_as`]
+///
+/// Implemented by the `Options` of the format modules.
+pub trait UpgradeFormat {
+    type DeserializeError;
+    type SerializeError;
+
+    /// Deserialize a document with versioning support
+    fn read_document<T, VMR, AGG>(
+        &self,
+        input: &[u8],
+        resolver: &VMR,
+        aggregate: &AGG,
+    ) -> Result<T, UpgradeError<Self::DeserializeError, Self::SerializeError>>
+    where
+        T: for<'de> DeserializeVersioned<'de>,
+        VMR: VersionGroupResolver,
+        AGG: AggregateVersionMap;
+
+    /// Serialize a value with its version header
+    fn write_document<T: Serialize>(
+        &self,
+        value: &T,
+        uris: &VersionGroupURIs,
+    ) -> Result<Vec<u8>, UpgradeError<Self::DeserializeError, Self::SerializeError>>;
+}
+
+/// Whether the document read with `aggregate` already has the version header `current_uris`
+pub(crate) fn is_up_to_date<AGG>(
+    aggregate: &RecordingAggregate<AGG>,
+    current_uris: &VersionGroupURIs,
+) -> bool {
+    aggregate.uris().as_ref() == Some(current_uris)
+}
+
+/// Files of a directory to upgrade
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileFilter {
+    /// The files with the extension, like `toml`
+    Extension(String),
+    /// The files whose path relative to the directory matches the pattern, like `**/*.toml`
+    #[cfg(feature = "glob-support")]
+    Glob(::glob::Pattern),
+}
+
+impl FileFilter {
+    pub fn extension<E: Into<String>>(extension: E) -> Self {
+        FileFilter::Extension(extension.into())
+    }
+
+    #[cfg(feature = "glob-support")]
+    pub fn glob(pattern: &str) -> Result<Self, ::glob::PatternError> {
+        ::glob::Pattern::new(pattern).map(FileFilter::Glob)
+    }
+
+    /// Whether the file `relative_path`, relative to the upgraded directory, is upgraded
+    pub fn matches(&self, relative_path: &Path) -> bool {
+        match self {
+            FileFilter::Extension(extension) => relative_path
+                .extension()
+                .is_some_and(|ext| ext == extension.as_str()),
+            #[cfg(feature = "glob-support")]
+            FileFilter::Glob(pattern) => pattern.matches_path(relative_path),
+        }
+    }
+}
+
+/// Outcome of the upgrade of a file of a directory
+#[derive(Debug)]
+pub struct FileReport<D, S> {
+    /// Path of the file
+    pub path: PathBuf,
+    /// Version header of the original file, if it was read
+    pub uris: Option<VersionGroupURIs>,
+    /// Version map of the original file, if its version header was aggregated
+    pub version_map: Option<AggregatedVersionMap>,
+    /// Report or error of the upgrade
+    pub result: Result<UpgradeReport, UpgradeError<D, S>>,
+}
+
+impl<D, S> FileReport<D, S> {
+    /// Whether the file was rewritten
+    pub fn migrated(&self) -> bool {
+        matches!(&self.result, Ok(report) if report.changed)
+    }
+}
+
+/// Outcome of the upgrade of the files of a directory
+///
+/// The files are sorted by path.
+#[derive(Debug)]
+pub struct BatchReport<D, S> {
+    pub files: Vec<FileReport<D, S>>,
+}
+
+impl<D, S> BatchReport<D, S> {
+    /// Files that were rewritten
+    pub fn migrated(&self) -> impl Iterator<Item = &FileReport<D, S>> {
+        self.files.iter().filter(|file| file.migrated())
+    }
+
+    /// Files that were already up to date
+    pub fn unchanged(&self) -> impl Iterator<Item = &FileReport<D, S>> {
+        self.files
+            .iter()
+            .filter(|file| matches!(&file.result, Ok(report) if !report.changed))
+    }
+
+    /// Files that couldn't be upgraded
+    pub fn failed(&self) -> impl Iterator<Item = &FileReport<D, S>> {
+        self.files.iter().filter(|file| file.result.is_err())
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.failed().next().is_some()
+    }
+}
+
+impl UpgradeOptions {
+    /// Upgrade the files of the directory `dir` and its subdirectories with `upgrade`
+    ///
+    /// `upgrade` upgrades a file, reading it with the given aggregate to record its original
+    /// version header. The errors, including the directories that can't be read, are reported
+    /// and the other files are still upgraded.
+    ///
+    /// The symbolic links in the directory are skipped, whether they point to files or to
+    /// directories: only the files actually in the directory are upgraded, once.
+    ///
+    /// # Parameters
+    /// - `dir`: directory of the files to upgrade
+    /// - `filter`: the files to upgrade
+    /// - `aggregate`: aggregator to combine found version groups
+    /// - `upgrade`: upgrades a file, like [`UpgradeOptions::upgrade_file_with`]
+    ///
+    /// # Returns
+    /// The report of each file
+    pub fn upgrade_dir_with<P, AGG, D, S, F>(
+        &self,
+        dir: P,
+        filter: &FileFilter,
+        aggregate: &AGG,
+        mut upgrade: F,
+    ) -> BatchReport<D, S>
+    where
+        P: AsRef<Path>,
+        F: FnMut(&Path, &RecordingAggregate<AGG>) -> Result<UpgradeReport, UpgradeError<D, S>>,
+    {
+        let dir = dir.as_ref();
+        let mut files = Vec::new();
+        let mut paths = Vec::new();
+        let mut dirs = vec![dir.to_owned()];
+        while let Some(current) = dirs.pop() {
+            match read_dir(&current) {
+                Ok(entries) => {
+                    for (path, is_dir) in entries {
+                        if is_dir {
+                            dirs.push(path);
+                        } else if filter.matches(path.strip_prefix(dir).unwrap_or(&path)) {
+                            paths.push(path);
+                        }
+                    }
+                }
+                Err(err) => files.push(FileReport {
+                    path: current,
+                    uris: None,
+                    version_map: None,
+                    result: Err(err.into()),
+                }),
+            }
+        }
+
+        for path in paths {
+            let recording = RecordingAggregate::new(aggregate);
+            let result = upgrade(&path, &recording);
+            files.push(FileReport {
+                path,
+                uris: recording.uris(),
+                version_map: recording.version_map(),
+                result,
+            });
+        }
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        BatchReport { files }
+    }
+
+    /// Upgrade the files of the directory `dir` of the format `format` to the current version
+    ///
+    /// The format modules use it for their `upgrade_dir` functions. Each file is upgraded like
+    /// with [`upgrade_file_as`](#method.upgrade_file_as), see
+    /// [`upgrade_dir_with`](#method.upgrade_dir_with) for the files upgraded and the report.
+    ///
+    /// # Parameters
+    /// - `format`: reads and writes the documents, like `json::Options`
+    /// - `dir`: directory of the files to upgrade
+    /// - `filter`: the files to upgrade
+    /// - `resolver`: resolver to find the version groups to use
+    /// - `aggregate`: aggregator to combine found version groups
+    /// - `current_uris`: The uris to serialize in the version header of the upgraded files
+    ///
+    /// # Returns
+    /// The report of each file
+    pub fn upgrade_dir_as<T, F, P, VMR, AGG>(
+        &self,
+        format: &F,
+        dir: P,
+        filter: &FileFilter,
+        resolver: &VMR,
+        aggregate: &AGG,
+        current_uris: &VersionGroupURIs,
+    ) -> BatchReport<F::DeserializeError, F::SerializeError>
+    where
+        T: Serialize + for<'de> DeserializeVersioned<'de>,
+        F: UpgradeFormat,
+        P: AsRef<Path>,
+        VMR: VersionGroupResolver,
+        AGG: AggregateVersionMap,
+    {
+        self.upgrade_dir_with(dir, filter, aggregate, |path, aggregate| {
+            self.upgrade_file_as::<T, _, _, _, _>(format, path, resolver, aggregate, current_uris)
+        })
+    }
+}
+
+/// Entries of a directory and whether they are directories, without the symbolic links
+fn read_dir(dir: &Path) -> io::Result<Vec<(PathBuf, bool)>> {
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if !file_type.is_symlink() {
+            entries.push((entry.path(), file_type.is_dir()));
+        }
+    }
+    Ok(entries)
+}
+
+/// Number of temporary file names tried by `write_atomic`
+const TEMP_FILE_ATTEMPTS: usize = 16;
+
+/// Write `contents` to the file `path` atomically
+///
+/// The contents are written to a temporary file in the same directory, then renamed over
+/// `path`. The permissions of an existing file are kept. When `path` is a symbolic link, the
+/// file it points to is replaced and the link is kept.
+pub fn write_atomic<P: AsRef<Path>>(path: P, contents: &[u8]) -> io::Result<()> {
+    let path = match fs::canonicalize(path.as_ref()) {
+        Ok(path) => path,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => path.as_ref().to_owned(),
+        Err(err) => return Err(err),
+    };
+    let (mut file, temp_path) = create_temp_file(&path)?;
+
+    let result = (|| {
+        file.write_all(contents)?;
+        if let Ok(metadata) = fs::metadata(&path) {
+            file.set_permissions(metadata.permissions())?;
+        }
+        file.sync_all()?;
+        fs::rename(&temp_path, &path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
+}
+
+/// Create a new temporary file next to `path`, named `.<name>.<pid>.<count>.tmp`
+///
+/// The count changes with each call, so a file left by an interrupted process is skipped.
+fn create_temp_file(path: &Path) -> io::Result<(fs::File, PathBuf)> {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    let file_name = path.file_name().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not a file path", path.display()),
+        )
+    })?;
+    let mut last_error = None;
+    for _ in 0..TEMP_FILE_ATTEMPTS {
+        let mut temp_name = std::ffi::OsString::from(".");
+        temp_name.push(file_name);
+        temp_name.push(format!(
+            ".{}.{}.tmp",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let temp_path = path.with_file_name(temp_name);
+        match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&temp_path)
+        {
+            Ok(file) => return Ok((file, temp_path)),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => last_error = Some(err),
+            Err(err) => return Err(err),
+        }
+    }
+    Err(last_error.expect("at least one temporary file name is tried"))
+}
+
+/// Read the contents of a text file
+#[cfg_attr(
+    not(any(
+        feature = "json-support",
+        feature = "ron-support",
+        feature = "toml-support",
+        feature = "xml-support",
+        feature = "yaml-support"
+    )),
+    allow(dead_code)
+)]
+pub(crate) fn as_str(input: &[u8]) -> io::Result<&str> {
+    std::str::from_utf8(input).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+fn append_extension(path: &Path, extension: &str) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_owned();
+    file_name.push(".");
+    file_name.push(extension);
+    path.with_file_name(file_name)
+}
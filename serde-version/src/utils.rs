@@ -10,3 +10,116 @@ macro_rules! impl_from_enum {
         )*
     }
 }
+
+/// Convert the errors kept by `header::DocumentVisitor` into the `DeserializeError` of a format
+///
+/// The error has a `De(Error<_>)` and an `AggregateError` variant. A missing version header is
+/// converted with `$missing`, into the `MissingVersionHeader` variant by default.
+#[cfg_attr(
+    not(any(
+        feature = "json-support",
+        feature = "msgpack-support",
+        feature = "ron-support",
+        feature = "cbor-support"
+    )),
+    allow(unused_macros)
+)]
+macro_rules! impl_from_header_error {
+    ($error:ident) => {
+        impl_from_header_error!($error, $error::MissingVersionHeader);
+    };
+    ($error:ident, $missing:expr) => {
+        impl ::std::convert::From<crate::header::HeaderError> for $error {
+            fn from(error: crate::header::HeaderError) -> Self {
+                use crate::header::HeaderError;
+                let missing: fn(String) -> $error = $missing;
+                match error {
+                    HeaderError::MissingVersionHeader(key) => missing(key),
+                    HeaderError::AggregateError(err) => $error::AggregateError(err),
+                    HeaderError::InvalidVersionError(err) => {
+                        $error::De(crate::Error::InvalidVersionError(err))
+                    }
+                    HeaderError::Message(message) => $error::De(crate::Error::Message(message)),
+                }
+            }
+        }
+    };
+}
+
+/// Define the `upgrade_file` and `upgrade_dir` functions of a format module
+///
+/// They upgrade with the default `Options` of the module and `UpgradeOptions`, the files of a
+/// directory are chosen by `$extension`.
+#[cfg_attr(
+    not(any(
+        feature = "toml-support",
+        feature = "json-support",
+        feature = "yaml-support",
+        feature = "ron-support",
+        feature = "xml-support",
+        feature = "msgpack-support",
+        feature = "cbor-support"
+    )),
+    allow(unused_macros)
+)]
+macro_rules! upgrade_functions {
+    ($format:literal, $extension:literal) => {
+        #[doc = concat!("Upgrade a ", $format, " file in place to the current version")]
+        ///
+        /// The file is upgraded with the default [`Options`], see the
+        /// [`upgrade`](../upgrade/index.html) module. Call `UpgradeOptions::upgrade_file_as`
+        /// with other options or to keep a backup of the original file.
+        pub fn upgrade_file<T, P, VMR, AGG>(
+            path: P,
+            resolver: &VMR,
+            aggregate: &AGG,
+            current_uris: &crate::VersionGroupURIs,
+        ) -> Result<crate::upgrade::UpgradeReport, UpgradeError>
+        where
+            T: ::serde::Serialize + for<'de> crate::DeserializeVersioned<'de>,
+            P: AsRef<::std::path::Path>,
+            VMR: crate::VersionGroupResolver,
+            AGG: crate::AggregateVersionMap,
+        {
+            crate::upgrade::UpgradeOptions::default().upgrade_file_as::<T, _, _, _, _>(
+                &Options::default(),
+                path,
+                resolver,
+                aggregate,
+                current_uris,
+            )
+        }
+
+        #[doc = concat!(
+            "Upgrade the ", $format, " files of a directory and its subdirectories in place to ",
+            "the current version"
+        )]
+        ///
+        #[doc = concat!(
+            "The files with the extension `", $extension, "` are upgraded like with ",
+            "[`upgrade_file`], see the [`upgrade`](../upgrade/index.html) module. Call ",
+            "`UpgradeOptions::upgrade_dir_as` to choose the files with another filter."
+        )]
+        pub fn upgrade_dir<T, P, VMR, AGG>(
+            dir: P,
+            resolver: &VMR,
+            aggregate: &AGG,
+            current_uris: &crate::VersionGroupURIs,
+        ) -> BatchReport
+        where
+            T: ::serde::Serialize + for<'de> crate::DeserializeVersioned<'de>,
+            P: AsRef<::std::path::Path>,
+            VMR: crate::VersionGroupResolver,
+            AGG: crate::AggregateVersionMap,
+        {
+            crate::upgrade::UpgradeOptions::default().upgrade_dir_as::<T, _, _, _, _>(
+                &Options::default(),
+                dir,
+                &crate::upgrade::FileFilter::extension($extension),
+                resolver,
+                aggregate,
+                current_uris,
+            )
+        }
+    };
+}
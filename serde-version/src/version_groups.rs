@@ -0,0 +1,512 @@
+//! Version groups: a version number shared by a set of types
+//!
+//! A version group is identified by a [`VersionGroupURI`] (`"api_group:version"`)
+//! and maps to a [`VersionMap`] through a [`VersionGroupResolver`].
+//!
+//! A group can extend a parent group and declare only the versions that changed,
+//! [`flatten_version_groups`] resolves the inherited versions.
+
+use crate::VersionMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::hash::{BuildHasher, Hash};
+use std::str::FromStr;
+
+/// Separator between the api group and the version of a uri
+pub const VERSION_GROUP_URI_SEPARATOR: char = ':';
+
+/// Error used when a string is not a valid version group uri
+#[derive(Debug, Clone, Hash, PartialEq, Eq, Fail)]
+#[fail(
+    display = "Invalid version group uri '{}', expected 'api_group:version'",
+    uri
+)]
+pub struct InvalidVersionGroupURIError {
+    pub uri: String,
+}
+
+/// Identifier of a version group: `"api_group:version"`
+///
+/// The version is the part after the last `:`, so the api group may contain `:`.
+#[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct VersionGroupURI {
+    api_group: String,
+    version: String,
+}
+
+impl VersionGroupURI {
+    pub fn new<A: Into<String>, V: Into<String>>(
+        api_group: A,
+        version: V,
+    ) -> Result<Self, InvalidVersionGroupURIError> {
+        let api_group = api_group.into();
+        let version = version.into();
+        if api_group.is_empty()
+            || version.is_empty()
+            || version.contains(VERSION_GROUP_URI_SEPARATOR)
+        {
+            return Err(InvalidVersionGroupURIError {
+                uri: format!("{}{}{}", api_group, VERSION_GROUP_URI_SEPARATOR, version),
+            });
+        }
+
+        Ok(Self { api_group, version })
+    }
+
+    pub fn api_group(&self) -> &str {
+        &self.api_group
+    }
+
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+}
+
+impl std::fmt::Display for VersionGroupURI {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        write!(
+            f,
+            "{}{}{}",
+            self.api_group, VERSION_GROUP_URI_SEPARATOR, self.version
+        )
+    }
+}
+
+impl FromStr for VersionGroupURI {
+    type Err = InvalidVersionGroupURIError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.rfind(VERSION_GROUP_URI_SEPARATOR) {
+            Some(index) => Self::new(&s[..index], &s[index + 1..]),
+            None => Err(InvalidVersionGroupURIError { uri: s.to_owned() }),
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a str> for VersionGroupURI {
+    type Error = InvalidVersionGroupURIError;
+
+    fn try_from(value: &'a str) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl TryFrom<String> for VersionGroupURI {
+    type Error = InvalidVersionGroupURIError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<VersionGroupURI> for String {
+    fn from(uri: VersionGroupURI) -> Self {
+        uri.to_string()
+    }
+}
+
+/// The version header of a document: the list of version group uris used
+///
+/// It is (de)serialized as a struct with a single `v` field.
+#[derive(Debug, Clone, Default, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VersionGroupURIs {
+    v: Vec<VersionGroupURI>,
+}
+
+impl VersionGroupURIs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, uri: VersionGroupURI) {
+        self.v.push(uri);
+    }
+}
+
+impl From<Vec<VersionGroupURI>> for VersionGroupURIs {
+    fn from(v: Vec<VersionGroupURI>) -> Self {
+        Self { v }
+    }
+}
+
+impl From<VersionGroupURIs> for Vec<VersionGroupURI> {
+    fn from(uris: VersionGroupURIs) -> Self {
+        uris.v
+    }
+}
+
+impl std::iter::FromIterator<VersionGroupURI> for VersionGroupURIs {
+    fn from_iter<I: IntoIterator<Item = VersionGroupURI>>(iter: I) -> Self {
+        Self {
+            v: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl std::ops::Deref for VersionGroupURIs {
+    type Target = [VersionGroupURI];
+
+    fn deref(&self) -> &Self::Target {
+        &self.v
+    }
+}
+
+impl<'a> IntoIterator for &'a VersionGroupURIs {
+    type Item = &'a VersionGroupURI;
+    type IntoIter = std::slice::Iter<'a, VersionGroupURI>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.v.iter()
+    }
+}
+
+/// Find the version map of a version group
+pub trait VersionGroupResolver {
+    type VM: VersionMap;
+
+    /// Get the version map of the group identified by `uri`, if it is known
+    fn resolve(&self, uri: &VersionGroupURI) -> Option<&Self::VM>;
+}
+
+impl<VM: VersionMap, S: BuildHasher> VersionGroupResolver for HashMap<VersionGroupURI, VM, S> {
+    type VM = VM;
+
+    fn resolve(&self, uri: &VersionGroupURI) -> Option<&VM> {
+        self.get(uri)
+    }
+}
+
+impl<T: VersionGroupResolver> VersionGroupResolver for &T {
+    type VM = T::VM;
+
+    fn resolve(&self, uri: &VersionGroupURI) -> Option<&Self::VM> {
+        (**self).resolve(uri)
+    }
+}
+
+/// Error raised while flattening groups that extend other groups
+#[derive(Debug, Clone, PartialEq, Eq, Fail)]
+pub enum VersionGroupInheritanceError {
+    /// A group extends a group that is not declared
+    #[fail(
+        display = "Version group {} extends the unknown version group {}",
+        uri, parent
+    )]
+    UnknownParent {
+        uri: VersionGroupURI,
+        parent: VersionGroupURI,
+    },
+    /// The groups extend each other
+    #[fail(display = "Cycle in the parents of version groups: {}", _0)]
+    Cycle(VersionGroupCycle),
+}
+
+/// Groups extending each other, the first uri of the cycle is repeated at its end
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionGroupCycle(pub Vec<VersionGroupURI>);
+
+impl std::fmt::Display for VersionGroupCycle {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        for (i, uri) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, " -> ")?;
+            }
+            write!(f, "{}", uri)?;
+        }
+        Ok(())
+    }
+}
+
+/// Flatten the inheritance of version groups
+///
+/// Each group is declared with its optional parent and the versions it overrides.
+/// The version map of a group is the version map of its parent, recursively, updated with
+/// its own versions.
+///
+/// ```
+/// use serde_version::{flatten_version_groups, VersionMap};
+/// let groups = flatten_version_groups(vec![
+///     ("company:1.3".parse().unwrap(), None, vec![("A", 1), ("B", 1)].into_iter().collect()),
+///     (
+///         "company:1.4".parse().unwrap(),
+///         Some("company:1.3".parse().unwrap()),
+///         vec![("A", 2)].into_iter().collect(),
+///     ),
+/// ])
+/// .unwrap();
+/// let version_map = &groups[&"company:1.4".parse().unwrap()];
+/// assert_eq!(Some(2), VersionMap::get(version_map, "A"));
+/// assert_eq!(Some(1), VersionMap::get(version_map, "B"));
+/// ```
+pub fn flatten_version_groups<K, I>(
+    groups: I,
+) -> Result<HashMap<VersionGroupURI, HashMap<K, usize>>, VersionGroupInheritanceError>
+where
+    K: Hash + Eq + Clone,
+    I: IntoIterator<Item = (VersionGroupURI, Option<VersionGroupURI>, HashMap<K, usize>)>,
+{
+    // Sorted, so the reported errors do not depend on the order of a hash map
+    let declared: BTreeMap<_, _> = groups
+        .into_iter()
+        .map(|(uri, parent, version_map)| (uri, (parent, version_map)))
+        .collect();
+    let mut flattened = HashMap::with_capacity(declared.len());
+    for uri in declared.keys() {
+        flatten_version_group(uri, &declared, &mut flattened, &mut Vec::new())?;
+    }
+    Ok(flattened)
+}
+
+type DeclaredVersionGroups<K> =
+    BTreeMap<VersionGroupURI, (Option<VersionGroupURI>, HashMap<K, usize>)>;
+
+fn flatten_version_group<K: Hash + Eq + Clone>(
+    uri: &VersionGroupURI,
+    declared: &DeclaredVersionGroups<K>,
+    flattened: &mut HashMap<VersionGroupURI, HashMap<K, usize>>,
+    children: &mut Vec<VersionGroupURI>,
+) -> Result<(), VersionGroupInheritanceError> {
+    if flattened.contains_key(uri) {
+        return Ok(());
+    }
+    if let Some(start) = children.iter().position(|child| child == uri) {
+        let mut cycle = children[start..].to_vec();
+        cycle.push(uri.clone());
+        return Err(VersionGroupInheritanceError::Cycle(VersionGroupCycle(
+            cycle,
+        )));
+    }
+
+    let (parent, versions) = &declared[uri];
+    let mut version_map = match parent {
+        Some(parent) => {
+            if !declared.contains_key(parent) {
+                return Err(VersionGroupInheritanceError::UnknownParent {
+                    uri: uri.clone(),
+                    parent: parent.clone(),
+                });
+            }
+            children.push(uri.clone());
+            flatten_version_group(parent, declared, flattened, children)?;
+            children.pop();
+            flattened[parent].clone()
+        }
+        None => HashMap::with_capacity(versions.len()),
+    };
+    version_map.extend(versions.iter().map(|(key, index)| (key.clone(), *index)));
+    flattened.insert(uri.clone(), version_map);
+    Ok(())
+}
+
+/// Version group resolver built by `version_group_resolver_static!`
+pub type StaticVersionGroupResolver = HashMap<VersionGroupURI, HashMap<&'static str, usize>>;
+
+/// Build a `VersionGroupURI` from its api group and version.
+///
+/// Panics if the uri is invalid, use `VersionGroupURI::new` to handle the error.
+#[doc(hidden)]
+pub fn static_version_group_uri<A: Into<String>, V: Into<String>>(
+    api_group: A,
+    version: V,
+) -> VersionGroupURI {
+    match VersionGroupURI::new(api_group, version) {
+        Ok(uri) => uri,
+        Err(err) => panic!("{}", err),
+    }
+}
+
+#[doc(hidden)]
+pub fn collect_static_version_map<I>(entries: I) -> HashMap<&'static str, usize>
+where
+    I: IntoIterator<Item = (&'static str, usize)>,
+{
+    entries.into_iter().collect()
+}
+
+/// Parse the optional parent of a static group.
+///
+/// Panics if the uri is invalid.
+#[doc(hidden)]
+pub fn static_version_group_parent(parent: &[&str]) -> Option<VersionGroupURI> {
+    parent.first().map(|uri| match uri.parse() {
+        Ok(uri) => uri,
+        Err(err) => panic!("{}", err),
+    })
+}
+
+/// Flatten the static groups.
+///
+/// Panics if a parent is unknown or if the groups extend each other.
+#[doc(hidden)]
+pub fn collect_static_resolver<I>(entries: I) -> StaticVersionGroupResolver
+where
+    I: IntoIterator<
+        Item = (
+            VersionGroupURI,
+            Option<VersionGroupURI>,
+            HashMap<&'static str, usize>,
+        ),
+    >,
+{
+    match flatten_version_groups(entries) {
+        Ok(groups) => groups,
+        Err(err) => panic!("{}", err),
+    }
+}
+
+/// Declare a static `VersionGroupResolver`
+///
+/// The version of a type is stored under its `std::any::type_name`, like the key used by
+/// `#[derive(DeserializeVersioned)]`.
+///
+/// A group can `extends` a parent group and list only the types whose version changed.
+/// The groups are flattened when the resolver is first used, which panics if a parent
+/// is unknown or if the groups extend each other.
+///
+/// ```
+/// # #[macro_use] extern crate serde_version;
+/// struct A;
+/// struct B;
+///
+/// version_group_resolver_static! {
+///     pub VERSIONS = {
+///         ("my.company", "1") => { A => 1, B => 1, },
+///         ("my.company", "2") extends "my.company:1" => { A => 2, },
+///     }
+/// }
+///
+/// # fn main() {
+/// use serde_version::{VersionGroupResolver, VersionMap};
+/// let uri = "my.company:2".parse().unwrap();
+/// let version_map = VERSIONS.resolve(&uri).unwrap();
+/// assert_eq!(Some(2), VersionMap::get(version_map, std::any::type_name::<A>()));
+/// assert_eq!(Some(1), VersionMap::get(version_map, std::any::type_name::<B>()));
+/// # }
+/// ```
+#[macro_export]
+macro_rules! version_group_resolver_static {
+    (
+        $(#[$attr:meta])*
+        pub $name:ident = { $($groups:tt)* }
+    ) => {
+        $crate::lazy_static! {
+            $(#[$attr])*
+            pub static ref $name: $crate::StaticVersionGroupResolver =
+                $crate::version_group_resolver_static!(@groups $($groups)*);
+        }
+    };
+    (
+        $(#[$attr:meta])*
+        $name:ident = { $($groups:tt)* }
+    ) => {
+        $crate::lazy_static! {
+            $(#[$attr])*
+            static ref $name: $crate::StaticVersionGroupResolver =
+                $crate::version_group_resolver_static!(@groups $($groups)*);
+        }
+    };
+    (@groups $(
+        ($api_group:expr, $version:expr) $(extends $parent:literal)? => {
+            $($ty:ty => $index:expr),* $(,)?
+        }
+    ),* $(,)?) => {
+        $crate::collect_static_resolver(::std::vec![$(
+            (
+                $crate::static_version_group_uri($api_group, $version),
+                $crate::static_version_group_parent(&[$($parent)?]),
+                $crate::collect_static_version_map(::std::vec![$(
+                    (::std::any::type_name::<$ty>(), $index),
+                )*]),
+            ),
+        )*])
+    };
+}
+
+/// Declare an enum whose variants stand for version group uris
+///
+/// Each variant is renamed with serde to its alias and converts to its uri.
+/// The enum must derive serde's traits for the `rename` attributes to be valid.
+///
+/// ```
+/// # #[macro_use] extern crate serde_version;
+/// # #[macro_use] extern crate serde;
+/// version_group_enum! {
+///     #[derive(Deserialize)]
+///     pub enum Versions {
+///         V1 as "v1" => "my.company:1",
+///         V2 as "v2" => "my.company:2",
+///     }
+/// }
+///
+/// # fn main() {
+/// assert_eq!("my.company:2", Versions::V2.uri().to_string());
+/// # }
+/// ```
+#[macro_export]
+macro_rules! version_group_enum {
+    (
+        $(#[$attr:meta])*
+        $vis:vis enum $name:ident {
+            $($variant:ident as $alias:literal => $uri:literal),* $(,)?
+        }
+    ) => {
+        $(#[$attr])*
+        $vis enum $name {
+            $(
+                #[serde(rename = $alias)]
+                $variant,
+            )*
+        }
+
+        impl $name {
+            /// Uri of the version group
+            #[allow(dead_code)]
+            pub fn uri(&self) -> $crate::VersionGroupURI {
+                match *self {
+                    $($name::$variant => match $uri.parse() {
+                        ::std::result::Result::Ok(uri) => uri,
+                        ::std::result::Result::Err(err) => ::std::panic!("{}", err),
+                    },)*
+                }
+            }
+        }
+
+        impl ::std::convert::From<$name> for $crate::VersionGroupURI {
+            fn from(v: $name) -> Self {
+                v.uri()
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::VersionGroupURI;
+    use quickcheck::TestResult;
+
+    #[quickcheck]
+    fn uri_display_roundtrip(api_group: String, version: String) -> TestResult {
+        let uri = match VersionGroupURI::new(api_group, version) {
+            Ok(uri) => uri,
+            Err(_) => return TestResult::discard(),
+        };
+        TestResult::from_bool(Ok(uri.clone()) == uri.to_string().parse())
+    }
+
+    #[test]
+    fn parse_uri() {
+        let uri: VersionGroupURI = "org.my.company:1.0.2".parse().unwrap();
+        assert_eq!("org.my.company", uri.api_group());
+        assert_eq!("1.0.2", uri.version());
+
+        let uri: VersionGroupURI = "org:plugin:3".parse().unwrap();
+        assert_eq!("org:plugin", uri.api_group());
+        assert_eq!("3", uri.version());
+
+        assert!("org.my.company".parse::<VersionGroupURI>().is_err());
+        assert!(":1".parse::<VersionGroupURI>().is_err());
+        assert!("org:".parse::<VersionGroupURI>().is_err());
+    }
+}
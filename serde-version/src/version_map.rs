@@ -1,4 +1,7 @@
+use crate::{VersionGroupResolver, VersionGroupURI, VersionGroupURIs};
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::rc::Rc;
 
 /// Maps the version number for each deserialization type name
 pub trait VersionMap: Clone + Sync + for<'a> VersionMapIter<'a> {
@@ -10,6 +13,292 @@ pub trait VersionMapIter<'a> {
     fn iter(&'a self) -> Self::Iter;
 }
 pub type DefaultVersionMap<'a> = HashMap<&'a str, usize>;
+/// Version map built by an `AggregateVersionMap`
+pub type AggregatedVersionMap = HashMap<String, usize>;
+
+/// Error used when two version groups define different versions for the same type
+#[derive(Debug, Clone, Hash, PartialEq, Eq, Fail)]
+#[fail(
+    display = "Conflicting versions for type {}: version {} from {} and version {} from {}",
+    type_id, first_version, first_uri, second_version, second_uri
+)]
+pub struct VersionConflictError {
+    pub type_id: String,
+    pub first_version: usize,
+    pub first_uri: VersionGroupURI,
+    pub second_version: usize,
+    pub second_uri: VersionGroupURI,
+}
+
+/// Error raised while aggregating the version maps of version groups
+#[derive(Debug, Clone, Hash, PartialEq, Eq, Fail)]
+pub enum AggregateVersionMapError {
+    #[fail(display = "Unknown version group uri {}", 0)]
+    UnknownVersionGroupURI(VersionGroupURI),
+    #[fail(display = "{}", 0)]
+    VersionConflict(Box<VersionConflictError>),
+}
+
+/// Combine the version maps of several version groups into a single version map
+pub trait AggregateVersionMap {
+    fn aggregate_version_maps<VMR>(
+        &self,
+        uris: &VersionGroupURIs,
+        resolver: &VMR,
+    ) -> Result<AggregatedVersionMap, AggregateVersionMapError>
+    where
+        VMR: VersionGroupResolver;
+
+    /// Call `f` with the version map aggregated for `uris`
+    ///
+    /// The aggregators that keep their version maps lend them to `f` instead of cloning them.
+    fn with_version_map<VMR, F, R>(
+        &self,
+        uris: &VersionGroupURIs,
+        resolver: &VMR,
+        f: F,
+    ) -> Result<R, AggregateVersionMapError>
+    where
+        VMR: VersionGroupResolver,
+        F: FnOnce(&AggregatedVersionMap) -> R,
+    {
+        Ok(f(&self.aggregate_version_maps(uris, resolver)?))
+    }
+}
+
+/// How to aggregate version groups that define different versions for the same type
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum AggregatePolicy {
+    /// Fail with `AggregateVersionMapError::VersionConflict`
+    ErrorOnConflict,
+    /// Keep the version of the first uri defining the type
+    FirstWins,
+    /// Keep the version of the last uri defining the type
+    LastWins,
+    /// Keep the highest version
+    HighestVersionWins,
+}
+
+impl AggregateVersionMap for AggregatePolicy {
+    fn aggregate_version_maps<VMR>(
+        &self,
+        uris: &VersionGroupURIs,
+        resolver: &VMR,
+    ) -> Result<AggregatedVersionMap, AggregateVersionMapError>
+    where
+        VMR: VersionGroupResolver,
+    {
+        // Version and index of the uri that defined it, for each type
+        let mut versions: HashMap<String, (usize, usize)> = HashMap::new();
+        for (uri_index, uri) in uris.iter().enumerate() {
+            let version_map = resolver
+                .resolve(uri)
+                .ok_or_else(|| AggregateVersionMapError::UnknownVersionGroupURI(uri.clone()))?;
+            for (type_id, version) in version_map.iter() {
+                let entry = match versions.get_mut(type_id) {
+                    Some(entry) => entry,
+                    None => {
+                        versions.insert(type_id.to_owned(), (version, uri_index));
+                        continue;
+                    }
+                };
+                if entry.0 == version {
+                    continue;
+                }
+                match self {
+                    AggregatePolicy::ErrorOnConflict => {
+                        return Err(AggregateVersionMapError::VersionConflict(Box::new(
+                            VersionConflictError {
+                                type_id: type_id.to_owned(),
+                                first_version: entry.0,
+                                first_uri: uris[entry.1].clone(),
+                                second_version: version,
+                                second_uri: uri.clone(),
+                            },
+                        )));
+                    }
+                    AggregatePolicy::FirstWins => {}
+                    AggregatePolicy::LastWins => *entry = (version, uri_index),
+                    AggregatePolicy::HighestVersionWins => {
+                        if version > entry.0 {
+                            *entry = (version, uri_index);
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(versions
+            .into_iter()
+            .map(|(type_id, (version, _))| (type_id, version))
+            .collect())
+    }
+}
+
+/// Default aggregation: the version maps are merged in the order of the uris,
+/// so a later uri overrides the versions of the previous ones.
+///
+/// Same as `AggregatePolicy::LastWins`.
+impl AggregateVersionMap for () {
+    fn aggregate_version_maps<VMR>(
+        &self,
+        uris: &VersionGroupURIs,
+        resolver: &VMR,
+    ) -> Result<AggregatedVersionMap, AggregateVersionMapError>
+    where
+        VMR: VersionGroupResolver,
+    {
+        AggregatePolicy::LastWins.aggregate_version_maps(uris, resolver)
+    }
+}
+
+/// Cache the version maps aggregated by another `AggregateVersionMap`, by version header
+///
+/// Useful when many documents are read with the same few headers, like the records of a
+/// log: each distinct header is aggregated once. The cache assumes that the same resolver
+/// is used for every call, create a new one when the resolver changes. The errors are not
+/// cached.
+///
+/// The cached version maps are shared: `with_version_map` lends them without copy, while
+/// `aggregate_version_maps` returns a copy.
+///
+/// ```
+/// use serde_version::{AggregatePolicy, CachedAggregate};
+///
+/// let aggregate = CachedAggregate::new(&AggregatePolicy::ErrorOnConflict);
+/// assert_eq!(0, aggregate.len());
+/// ```
+#[derive(Debug)]
+pub struct CachedAggregate<'a, AGG> {
+    aggregate: &'a AGG,
+    cache: RefCell<HashMap<VersionGroupURIs, Rc<AggregatedVersionMap>>>,
+}
+
+impl<'a, AGG> CachedAggregate<'a, AGG> {
+    pub fn new(aggregate: &'a AGG) -> Self {
+        Self {
+            aggregate,
+            cache: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Number of cached version headers
+    pub fn len(&self) -> usize {
+        self.cache.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cache.borrow().is_empty()
+    }
+
+    /// Forget the cached version maps
+    pub fn clear(&self) {
+        self.cache.borrow_mut().clear();
+    }
+
+    /// Cached version map of `uris`, aggregated on the first call
+    pub fn version_map<VMR>(
+        &self,
+        uris: &VersionGroupURIs,
+        resolver: &VMR,
+    ) -> Result<Rc<AggregatedVersionMap>, AggregateVersionMapError>
+    where
+        AGG: AggregateVersionMap,
+        VMR: VersionGroupResolver,
+    {
+        if let Some(version_map) = self.cache.borrow().get(uris) {
+            return Ok(Rc::clone(version_map));
+        }
+        let version_map = Rc::new(self.aggregate.aggregate_version_maps(uris, resolver)?);
+        self.cache
+            .borrow_mut()
+            .insert(uris.clone(), Rc::clone(&version_map));
+        Ok(version_map)
+    }
+}
+
+impl<'a, AGG: AggregateVersionMap> AggregateVersionMap for CachedAggregate<'a, AGG> {
+    fn aggregate_version_maps<VMR>(
+        &self,
+        uris: &VersionGroupURIs,
+        resolver: &VMR,
+    ) -> Result<AggregatedVersionMap, AggregateVersionMapError>
+    where
+        VMR: VersionGroupResolver,
+    {
+        Ok((*self.version_map(uris, resolver)?).clone())
+    }
+
+    fn with_version_map<VMR, F, R>(
+        &self,
+        uris: &VersionGroupURIs,
+        resolver: &VMR,
+        f: F,
+    ) -> Result<R, AggregateVersionMapError>
+    where
+        VMR: VersionGroupResolver,
+        F: FnOnce(&AggregatedVersionMap) -> R,
+    {
+        // The cache is not borrowed while `f` runs
+        Ok(f(&*self.version_map(uris, resolver)?))
+    }
+}
+
+/// Record the version header and the version map of the last document read with another
+/// `AggregateVersionMap`
+///
+/// Used to report the original version of the files upgraded by
+/// `UpgradeOptions::upgrade_dir_with`. The version header is recorded even when it can't be
+/// aggregated.
+///
+/// ```
+/// use serde_version::RecordingAggregate;
+///
+/// let aggregate = RecordingAggregate::new(&());
+/// assert_eq!(None, aggregate.uris());
+/// ```
+#[derive(Debug)]
+pub struct RecordingAggregate<'a, AGG> {
+    aggregate: &'a AGG,
+    uris: RefCell<Option<VersionGroupURIs>>,
+    version_map: RefCell<Option<AggregatedVersionMap>>,
+}
+
+impl<'a, AGG> RecordingAggregate<'a, AGG> {
+    pub fn new(aggregate: &'a AGG) -> Self {
+        Self {
+            aggregate,
+            uris: RefCell::new(None),
+            version_map: RefCell::new(None),
+        }
+    }
+
+    /// Version header of the last document
+    pub fn uris(&self) -> Option<VersionGroupURIs> {
+        self.uris.borrow().clone()
+    }
+
+    /// Version map of the last document, if its version header could be aggregated
+    pub fn version_map(&self) -> Option<AggregatedVersionMap> {
+        self.version_map.borrow().clone()
+    }
+}
+
+impl<'a, AGG: AggregateVersionMap> AggregateVersionMap for RecordingAggregate<'a, AGG> {
+    fn aggregate_version_maps<VMR>(
+        &self,
+        uris: &VersionGroupURIs,
+        resolver: &VMR,
+    ) -> Result<AggregatedVersionMap, AggregateVersionMapError>
+    where
+        VMR: VersionGroupResolver,
+    {
+        *self.uris.borrow_mut() = Some(uris.clone());
+        let version_map = self.aggregate.aggregate_version_maps(uris, resolver);
+        *self.version_map.borrow_mut() = version_map.as_ref().ok().cloned();
+        version_map
+    }
+}
 
 mod version_map_impls {
     use crate::version_map::VersionMapIter;
@@ -48,7 +337,7 @@ mod version_map_impls {
             <T as VersionMap>::get(self, type_id)
         }
     }
-    impl<'a, 'i, T: VersionMapIter<'i>> VersionMapIter<'i> for &'a T {
+    impl<'i, T: VersionMapIter<'i>> VersionMapIter<'i> for &T {
         type Iter = <T as VersionMapIter<'i>>::Iter;
 
         fn iter(&'i self) -> Self::Iter {
@@ -64,7 +353,7 @@ mod version_map_impls {
             <T as VersionMap>::get(self, type_id)
         }
     }
-    impl<'a, 'i, T: VersionMapIter<'i>> VersionMapIter<'i> for &'a mut T {
+    impl<'i, T: VersionMapIter<'i>> VersionMapIter<'i> for &mut T {
         type Iter = <T as VersionMapIter<'i>>::Iter;
 
         fn iter(&'i self) -> Self::Iter {
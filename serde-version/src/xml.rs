@@ -0,0 +1,345 @@
+//! Serialization utilities for the XML format
+//!
+//! The version header is the `sv:v` attribute of the root element by default, the uris
+//! separated by whitespace, with the `sv` prefix bound to `DEFAULT_VERSION_HEADER_NAMESPACE`:
+//! ```xml
+//! <Config xmlns:sv="https://github.com/fredericvauchelles/serde-version"
+//!     sv:v="my.company:1 other.company:2"><name>my config name</name></Config>
+//! ```
+//!
+//! [`Options::header_attribute`] chooses another attribute and [`Options::namespace`] binds
+//! its prefix to a namespace: the attribute is then found by its namespace, whatever the
+//! prefix used by the document.
+//!
+//! The body is deserialized with `quick_xml` through the versioned machinery. The version
+//! header attribute and its namespace declaration are not given to the root struct, so it
+//! can deny unknown fields. As `quick_xml` names the attribute fields by their local name,
+//! a root attribute with the local name of the version header is skipped as well.
+
+use crate::skip_entries::SkipEntries;
+use crate::upgrade::UpgradeFormat;
+use crate::version_map::AggregateVersionMap;
+use crate::{
+    AggregateVersionMapError, DeserializeVersioned, Error, InvalidVersionGroupURIError,
+    VersionGroupResolver, VersionGroupURIs, VersionedDeserializer,
+};
+use ::quick_xml::events::{BytesStart, Event};
+use ::quick_xml::DeError;
+use failure::Fail;
+use serde::Serialize;
+
+/// Qualified name of the default version header attribute, on the root element
+pub const DEFAULT_VERSION_HEADER_ATTRIBUTE: &str = "sv:v";
+
+/// Namespace of the default version header attribute
+pub const DEFAULT_VERSION_HEADER_NAMESPACE: &str =
+    "https://github.com/fredericvauchelles/serde-version";
+
+/// Deserialization error
+#[derive(Debug, Fail)]
+pub enum DeserializeError {
+    #[fail(display = "{}", 0)]
+    Xml(::quick_xml::Error),
+    #[fail(display = "{}", 0)]
+    De(Error<DeError>),
+    #[fail(display = "{}", 0)]
+    AggregateError(AggregateVersionMapError),
+    #[fail(
+        display = "Missing version header attribute '{}' on the root element",
+        0
+    )]
+    MissingVersionHeader(String),
+    #[fail(display = "Invalid version header: {}", 0)]
+    InvalidVersionHeader(InvalidVersionGroupURIError),
+}
+impl_from_enum! {
+    DeserializeError::Xml => ::quick_xml::Error,
+    DeserializeError::De => Error<DeError>,
+    DeserializeError::AggregateError => AggregateVersionMapError,
+    DeserializeError::InvalidVersionHeader => InvalidVersionGroupURIError,
+}
+
+/// Error raised while upgrading a XML file
+pub type UpgradeError = crate::upgrade::UpgradeError<DeserializeError, ::quick_xml::SeError>;
+
+/// Report of the upgrade of the XML files of a directory
+pub type BatchReport = crate::upgrade::BatchReport<DeserializeError, ::quick_xml::SeError>;
+
+/// Options of the XML format
+///
+/// By default, the version header is the `sv:v` attribute of the root element, in the
+/// namespace `DEFAULT_VERSION_HEADER_NAMESPACE`.
+///
+/// ```
+/// let options = serde_version::xml::Options::new()
+///     .header_attribute("meta:versions")
+///     .namespace("urn:my-company:meta");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Options {
+    attribute: String,
+    namespace: Option<String>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            attribute: DEFAULT_VERSION_HEADER_ATTRIBUTE.to_owned(),
+            namespace: Some(DEFAULT_VERSION_HEADER_NAMESPACE.to_owned()),
+        }
+    }
+}
+
+impl Options {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Qualified name of the version header attribute
+    pub fn attribute(&self) -> &str {
+        &self.attribute
+    }
+
+    /// Use the attribute `name` of the root element for the version header
+    ///
+    /// `name` is a qualified name like `sv:v`, see `DEFAULT_VERSION_HEADER_ATTRIBUTE`. The
+    /// attribute has no namespace until `namespace` binds its prefix.
+    pub fn header_attribute<N: Into<String>>(mut self, name: N) -> Self {
+        self.attribute = name.into();
+        self.namespace = None;
+        self
+    }
+
+    /// Bind the prefix of the version header attribute to the namespace `uri`
+    ///
+    /// The namespace is declared on the root element when writing. When reading, the version
+    /// header is the attribute with the same local name in this namespace, whatever its prefix.
+    pub fn namespace<U: Into<String>>(mut self, uri: U) -> Self {
+        self.namespace = Some(uri.into());
+        self
+    }
+
+    /// Deserialize a XML string with versioning support
+    ///
+    /// See [`deserialize`](fn.deserialize.html).
+    pub fn deserialize<'de, T, VMR, AGG>(
+        &self,
+        input: &'de str,
+        resolver: &VMR,
+        aggregate: &AGG,
+    ) -> Result<T, DeserializeError>
+    where
+        T: DeserializeVersioned<'de>,
+        VMR: VersionGroupResolver,
+        AGG: AggregateVersionMap,
+    {
+        let header = self
+            .read_version_header(input)?
+            .ok_or_else(|| DeserializeError::MissingVersionHeader(self.attribute.clone()))?;
+        let uris = header
+            .value
+            .split_whitespace()
+            .map(str::parse)
+            .collect::<Result<VersionGroupURIs, _>>()?;
+        let version_map = aggregate.aggregate_version_maps(&uris, resolver)?;
+
+        // The attributes of the root element are the `@` fields of the root struct
+        let skipped: Vec<String> = header
+            .fields
+            .iter()
+            .map(|name| format!("@{}", name))
+            .collect();
+        let mut de = ::quick_xml::de::Deserializer::from_str(input);
+        let de = SkipEntries::new(&mut de, &[], &skipped);
+        Ok(DeserializeVersioned::deserialize_versioned(
+            VersionedDeserializer::new(de, &version_map),
+            &version_map,
+        )
+        .map_err(Error::reduce)?)
+    }
+
+    /// Serialize a value formatted in XML with its version header
+    ///
+    /// See [`serialize`](fn.serialize.html).
+    pub fn serialize<T: Serialize>(
+        &self,
+        value: &T,
+        uris: &VersionGroupURIs,
+    ) -> Result<String, ::quick_xml::SeError> {
+        let mut document = ::quick_xml::se::to_string(value)?;
+
+        let mut attributes = String::new();
+        if let (Some(namespace), (Some(prefix), _)) = (&self.namespace, self.split_attribute()) {
+            attributes.push_str(&format!(
+                " xmlns:{}=\"{}\"",
+                prefix,
+                ::quick_xml::escape::escape(namespace.as_str())
+            ));
+        }
+        let header = uris
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(" ");
+        attributes.push_str(&format!(
+            " {}=\"{}\"",
+            self.attribute,
+            ::quick_xml::escape::escape(header)
+        ));
+
+        // The root element is the first tag written by quick_xml
+        let name_end = document
+            .find(|c: char| c.is_whitespace() || c == '>' || c == '/')
+            .ok_or_else(|| ::quick_xml::SeError::Custom("no root element".to_owned()))?;
+        document.insert_str(name_end, &attributes);
+        Ok(document)
+    }
+
+    /// Prefix and local name of the version header attribute
+    fn split_attribute(&self) -> (Option<&str>, &str) {
+        match self.attribute.split_once(':') {
+            Some((prefix, local_name)) => (Some(prefix), local_name),
+            None => (None, &self.attribute),
+        }
+    }
+
+    /// Read the version header in the attributes of the root element
+    fn read_version_header(&self, input: &str) -> Result<Option<RootHeader>, ::quick_xml::Error> {
+        let mut reader = ::quick_xml::Reader::from_str(input);
+        let root = loop {
+            match reader.read_event()? {
+                Event::Start(root) | Event::Empty(root) => break root,
+                Event::Eof => return Ok(None),
+                _ => {}
+            }
+        };
+
+        let (name, declaration) = match (&self.namespace, self.split_attribute()) {
+            (Some(namespace), (Some(_), local_name)) => match namespace_prefix(&root, namespace)? {
+                Some(prefix) => (
+                    format!("{}:{}", prefix, local_name),
+                    Some(format!("xmlns:{}", prefix)),
+                ),
+                None => return Ok(None),
+            },
+            _ => (self.attribute.clone(), None),
+        };
+        for attribute in root.attributes() {
+            let attribute = attribute?;
+            if attribute.key.as_ref() == name.as_bytes() {
+                // `quick_xml` names the fields of attributes by their local name, except the
+                // namespace declarations
+                let local_name = attribute.key.local_name();
+                let local_name = String::from_utf8_lossy(local_name.as_ref()).into_owned();
+                return Ok(Some(RootHeader {
+                    value: attribute.unescape_value()?.into_owned(),
+                    fields: std::iter::once(local_name).chain(declaration).collect(),
+                }));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl UpgradeFormat for Options {
+    type DeserializeError = DeserializeError;
+    type SerializeError = ::quick_xml::SeError;
+
+    fn read_document<T, VMR, AGG>(
+        &self,
+        input: &[u8],
+        resolver: &VMR,
+        aggregate: &AGG,
+    ) -> Result<T, UpgradeError>
+    where
+        T: for<'de> DeserializeVersioned<'de>,
+        VMR: VersionGroupResolver,
+        AGG: AggregateVersionMap,
+    {
+        let input = crate::upgrade::as_str(input)?;
+        self.deserialize(input, resolver, aggregate)
+            .map_err(UpgradeError::Deserialize)
+    }
+
+    fn write_document<T: Serialize>(
+        &self,
+        value: &T,
+        uris: &VersionGroupURIs,
+    ) -> Result<Vec<u8>, UpgradeError> {
+        Ok(self
+            .serialize(value, uris)
+            .map_err(UpgradeError::Serialize)?
+            .into_bytes())
+    }
+}
+
+/// Version header in the attributes of the root element
+struct RootHeader {
+    value: String,
+    /// Field names of the version header attribute and of its namespace declaration, without
+    /// the leading `@`
+    fields: Vec<String>,
+}
+
+/// Prefix declared for the namespace `uri` on `element`
+fn namespace_prefix(element: &BytesStart, uri: &str) -> Result<Option<String>, ::quick_xml::Error> {
+    for attribute in element.attributes() {
+        let attribute = attribute?;
+        if let Some(prefix) = attribute.key.as_ref().strip_prefix(b"xmlns:") {
+            if attribute.unescape_value()? == uri {
+                return Ok(Some(String::from_utf8_lossy(prefix).into_owned()));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Deserialize a XML string with versioning support
+///
+/// # Generic Parameters
+/// - `T`: type to deserialize
+/// - `VMR`: resolver to find the version groups to use
+/// - `AGG`: aggregator to combine found version groups
+///
+/// # Parameters
+/// - `input`: XML string, the version header is the `sv:v` attribute of the root element
+/// - `resolver`: resolver to find the version groups to use
+/// - `aggregate`: aggregator to combine found version groups
+///
+/// # Returns
+/// The deserialized value or the error that occurred
+pub fn deserialize<'de, T, VMR, AGG>(
+    input: &'de str,
+    resolver: &VMR,
+    aggregate: &AGG,
+) -> Result<T, DeserializeError>
+where
+    T: DeserializeVersioned<'de>,
+    VMR: VersionGroupResolver,
+    AGG: AggregateVersionMap,
+{
+    Options::default().deserialize(input, resolver, aggregate)
+}
+
+/// Serialize a value formatted in XML with its version header.
+///
+/// The root element is named after the type of the value, the version header is its
+/// `sv:v` attribute.
+///
+/// # Generic Parameters
+/// - `T`: type to serialize
+///
+/// # Parameters
+/// - `value`: The value to serialize
+/// - `uris`: The uris to serialize in the version header
+///
+/// # Returns
+/// The string or the error that occurred, if any.
+pub fn serialize<T: Serialize>(
+    value: &T,
+    uris: &VersionGroupURIs,
+) -> Result<String, ::quick_xml::SeError> {
+    Options::default().serialize(value, uris)
+}
+
+upgrade_functions!("XML", "xml");
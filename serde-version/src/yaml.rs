@@ -0,0 +1,333 @@
+//! Serialization utilities for the Yaml format
+//!
+//! The version header is the `v` key of the root mapping by default:
+//! ```yaml
+//! v: ["my.company:1"]
+//! name: my config name
+//! ```
+//!
+//! [`Options::header_document`] reads and writes it in a first document instead, so the
+//! payload document is left untouched:
+//! ```yaml
+//! v: ["my.company:1"]
+//! ---
+//! name: my config name
+//! ```
+
+use crate::skip_entries::SkipEntries;
+use crate::upgrade::UpgradeFormat;
+use crate::version_map::AggregateVersionMap;
+use crate::{
+    AggregateVersionMapError, DeserializeVersioned, Error, VersionGroupResolver, VersionGroupURI,
+    VersionGroupURIs, VersionedDeserializer,
+};
+use failure::Fail;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Key of the default version header
+pub const DEFAULT_VERSION_HEADER_KEY: &str = "v";
+
+/// Deserialization error
+#[derive(Debug, Fail)]
+pub enum DeserializeError {
+    #[fail(display = "{}", 0)]
+    Yaml(::serde_yaml::Error),
+    #[fail(display = "{}", 0)]
+    De(Error<Error<::serde_yaml::Error>>),
+    #[fail(display = "{}", 0)]
+    AggregateError(AggregateVersionMapError),
+    #[fail(display = "Missing version header '{}'", 0)]
+    MissingVersionHeader(String),
+    #[fail(display = "Invalid version header: {}", 0)]
+    InvalidVersionHeader(::serde_yaml::Error),
+    #[fail(display = "Missing the payload document after the version header document")]
+    MissingPayload,
+    #[fail(display = "Unexpected document after the payload document")]
+    UnexpectedDocument,
+}
+impl_from_enum! {
+    DeserializeError::Yaml => ::serde_yaml::Error,
+    DeserializeError::De => Error<Error<::serde_yaml::Error>>,
+    DeserializeError::AggregateError => AggregateVersionMapError,
+}
+
+/// Error raised while upgrading a Yaml file
+pub type UpgradeError = crate::upgrade::UpgradeError<DeserializeError, ::serde_yaml::Error>;
+
+/// Report of the upgrade of the Yaml files of a directory
+pub type BatchReport = crate::upgrade::BatchReport<DeserializeError, ::serde_yaml::Error>;
+
+/// Error raised while computing the diff of a migration
+#[cfg(feature = "diff-support")]
+pub type DiffError = crate::diff::DiffError<DeserializeError, ::serde_yaml::Error>;
+
+/// Where the version header is written in a Yaml stream
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VersionHeaderLocation {
+    /// A key of the root mapping
+    Key { key: String },
+    /// The only key of a first document, the payload is the second document
+    Document { key: String },
+}
+
+/// Options of the Yaml format
+///
+/// By default, the version header is the `v` key of the root mapping.
+///
+/// ```
+/// let options = serde_version::yaml::Options::new()
+///     .header_key("versions")
+///     .header_document();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Options {
+    header: VersionHeaderLocation,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            header: VersionHeaderLocation::Key {
+                key: DEFAULT_VERSION_HEADER_KEY.to_owned(),
+            },
+        }
+    }
+}
+
+impl Options {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn header(&self) -> &VersionHeaderLocation {
+        &self.header
+    }
+
+    /// Use the key `key` for the version header
+    pub fn header_key<K: Into<String>>(mut self, key: K) -> Self {
+        let key = key.into();
+        self.header = match self.header {
+            VersionHeaderLocation::Key { .. } => VersionHeaderLocation::Key { key },
+            VersionHeaderLocation::Document { .. } => VersionHeaderLocation::Document { key },
+        };
+        self
+    }
+
+    /// Write the version header in a first document, before the payload document
+    pub fn header_document(mut self) -> Self {
+        self.header = match self.header {
+            VersionHeaderLocation::Key { key } | VersionHeaderLocation::Document { key } => {
+                VersionHeaderLocation::Document { key }
+            }
+        };
+        self
+    }
+
+    /// Deserialize a Yaml string with versioning support
+    ///
+    /// See [`deserialize`](fn.deserialize.html).
+    pub fn deserialize<'de, T, VMR, AGG>(
+        &self,
+        input: &'de str,
+        resolver: &VMR,
+        aggregate: &AGG,
+    ) -> Result<T, DeserializeError>
+    where
+        T: DeserializeVersioned<'de>,
+        VMR: VersionGroupResolver,
+        AGG: AggregateVersionMap,
+    {
+        match &self.header {
+            VersionHeaderLocation::Key { key } => {
+                let document =
+                    ::serde_yaml::Value::deserialize(::serde_yaml::Deserializer::from_str(input))?;
+                let header = document
+                    .as_mapping()
+                    .and_then(|mapping| mapping.get(key.as_str()))
+                    .ok_or_else(|| DeserializeError::MissingVersionHeader(key.clone()))?;
+                let uris = Vec::<VersionGroupURI>::deserialize(header)
+                    .map_err(DeserializeError::InvalidVersionHeader)?
+                    .into();
+
+                let version_map = aggregate.aggregate_version_maps(&uris, resolver)?;
+
+                // The body is read from the input, so it can borrow from it
+                let de = SkipEntries::new(
+                    ::serde_yaml::Deserializer::from_str(input),
+                    &[],
+                    std::slice::from_ref(key),
+                );
+                let de = VersionedDeserializer::new(de, &version_map);
+                Ok(DeserializeVersioned::deserialize_versioned(
+                    de,
+                    &version_map,
+                )?)
+            }
+            VersionHeaderLocation::Document { key } => {
+                let mut documents = ::serde_yaml::Deserializer::from_str(input);
+                let header_document = documents
+                    .next()
+                    .ok_or_else(|| DeserializeError::MissingVersionHeader(key.clone()))?;
+                let mut header =
+                    BTreeMap::<String, ::serde_yaml::Value>::deserialize(header_document)
+                        .map_err(DeserializeError::InvalidVersionHeader)?;
+                let uris = header
+                    .remove(key)
+                    .ok_or_else(|| DeserializeError::MissingVersionHeader(key.clone()))?;
+                if let Some(other) = header.keys().next() {
+                    return Err(DeserializeError::InvalidVersionHeader(
+                        serde::de::Error::custom(format!(
+                            "unexpected key '{}' in the version header document",
+                            other
+                        )),
+                    ));
+                }
+                let uris = Vec::<VersionGroupURI>::deserialize(uris)
+                    .map_err(DeserializeError::InvalidVersionHeader)?
+                    .into();
+
+                let version_map = aggregate.aggregate_version_maps(&uris, resolver)?;
+
+                let payload = documents.next().ok_or(DeserializeError::MissingPayload)?;
+                let de = VersionedDeserializer::new(payload, &version_map);
+                let value = DeserializeVersioned::deserialize_versioned(de, &version_map)?;
+                if documents.next().is_some() {
+                    return Err(DeserializeError::UnexpectedDocument);
+                }
+                Ok(value)
+            }
+        }
+    }
+
+    /// Serialize a value formatted in Yaml with its version header
+    ///
+    /// See [`serialize`](fn.serialize.html).
+    pub fn serialize<T: Serialize>(
+        &self,
+        value: &T,
+        uris: &VersionGroupURIs,
+    ) -> Result<String, ::serde_yaml::Error> {
+        match &self.header {
+            VersionHeaderLocation::Key { key } => {
+                let value = match ::serde_yaml::to_value(value)? {
+                    ::serde_yaml::Value::Mapping(mapping) => mapping,
+                    _ => {
+                        return Err(serde::ser::Error::custom(
+                            "the version header can only be added to a mapping",
+                        ))
+                    }
+                };
+                let mut document = ::serde_yaml::Mapping::with_capacity(value.len() + 1);
+                document.insert(key.as_str().into(), ::serde_yaml::to_value(&**uris)?);
+                document.extend(value);
+                ::serde_yaml::to_string(&document)
+            }
+            VersionHeaderLocation::Document { key } => {
+                let mut header = BTreeMap::new();
+                header.insert(key.as_str(), &**uris);
+                Ok(format!(
+                    "{}---\n{}",
+                    ::serde_yaml::to_string(&header)?,
+                    ::serde_yaml::to_string(value)?
+                ))
+            }
+        }
+    }
+}
+
+impl UpgradeFormat for Options {
+    type DeserializeError = DeserializeError;
+    type SerializeError = ::serde_yaml::Error;
+
+    fn read_document<T, VMR, AGG>(
+        &self,
+        input: &[u8],
+        resolver: &VMR,
+        aggregate: &AGG,
+    ) -> Result<T, UpgradeError>
+    where
+        T: for<'de> DeserializeVersioned<'de>,
+        VMR: VersionGroupResolver,
+        AGG: AggregateVersionMap,
+    {
+        let input = crate::upgrade::as_str(input)?;
+        self.deserialize(input, resolver, aggregate)
+            .map_err(UpgradeError::Deserialize)
+    }
+
+    fn write_document<T: Serialize>(
+        &self,
+        value: &T,
+        uris: &VersionGroupURIs,
+    ) -> Result<Vec<u8>, UpgradeError> {
+        Ok(self
+            .serialize(value, uris)
+            .map_err(UpgradeError::Serialize)?
+            .into_bytes())
+    }
+}
+
+/// A Yaml stream is read as a sequence of its documents if there are several
+#[cfg(feature = "diff-support")]
+impl crate::diff::DiffFormat for Options {
+    fn read_value(&self, input: &str) -> Result<crate::diff::Value, DeserializeError> {
+        let mut documents = ::serde_yaml::Deserializer::from_str(input)
+            .map(crate::diff::Value::deserialize)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(if documents.len() == 1 {
+            documents.remove(0)
+        } else {
+            crate::diff::Value::Seq(documents)
+        })
+    }
+}
+
+/// Deserialize a Yaml string with versioning support
+///
+/// # Generic Parameters
+/// - `T`: type to deserialize
+/// - `VMR`: resolver to find the version groups to use
+/// - `AGG`: aggregator to combine found version groups
+///
+/// # Parameters
+/// - `input`: Yaml formatted string, the version header is the `v` key of the root mapping
+/// - `resolver`: resolver to find the version groups to use
+/// - `aggregate`: aggregator to combine found version groups
+///
+/// # Returns
+/// The deserialized value or the error that occurred
+pub fn deserialize<'de, T, VMR, AGG>(
+    input: &'de str,
+    resolver: &VMR,
+    aggregate: &AGG,
+) -> Result<T, DeserializeError>
+where
+    T: DeserializeVersioned<'de>,
+    VMR: VersionGroupResolver,
+    AGG: AggregateVersionMap,
+{
+    Options::default().deserialize(input, resolver, aggregate)
+}
+
+/// Serialize a value formatted in Yaml with its version header.
+///
+/// The version header will be serialized as the first key of the value.
+///
+/// # Generic Parameters
+/// - `T`: type to serialize
+///
+/// # Parameters
+/// - `value`: The value to serialize
+/// - `uris`: The uris to serialize in the version header
+///
+/// # Returns
+/// The formatted string or the error that occurred, if any.
+pub fn serialize<T: Serialize>(
+    value: &T,
+    uris: &VersionGroupURIs,
+) -> Result<String, ::serde_yaml::Error> {
+    Options::default().serialize(value, uris)
+}
+
+upgrade_functions!("Yaml", "yaml");
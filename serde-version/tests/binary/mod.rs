@@ -0,0 +1,307 @@
+use serde_version::binary::{BinaryHeader, DecodeHeaderError, ReadHeaderError};
+use serde_version::{VersionGroupResolver, VersionGroupURIs, VersionMap};
+use std::collections::BTreeMap;
+use std::net::Ipv4Addr;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "A")]
+struct Av1 {
+    a: u32,
+}
+
+#[derive(Serialize, Deserialize, DeserializeVersioned, PartialEq, Debug)]
+#[versions(v(index = 1, type = "Av1"), v(index = 2, self))]
+struct A {
+    b: u32,
+    // Serialized as a string only by the human readable formats
+    address: Ipv4Addr,
+}
+
+impl From<Av1> for A {
+    fn from(v: Av1) -> Self {
+        Self {
+            b: v.a,
+            address: Ipv4Addr::LOCALHOST,
+        }
+    }
+}
+
+version_group_resolver_static! {
+    pub VERSIONS = {
+        ("a" , "1") => { A => 1, },
+        ("a" , "2") => { A => 2, },
+        ("a" , "3") => { A => 3, },
+    }
+}
+
+fn uris(uris: &[&str]) -> VersionGroupURIs {
+    uris.iter().map(|uri| uri.parse().unwrap()).collect()
+}
+
+fn a2_versions() -> &'static impl VersionMap {
+    VERSIONS.resolve(&"a:2".parse().unwrap()).unwrap()
+}
+
+#[cfg(any(feature = "bincode-support", feature = "postcard-support"))]
+fn a() -> A {
+    A {
+        b: 5,
+        address: Ipv4Addr::new(10, 0, 0, 1),
+    }
+}
+
+#[test]
+fn header_layout_is_stable() {
+    let mut out = Vec::new();
+    BinaryHeader::from(uris(&["a:1", "b:2"])).encode(&mut out);
+    assert_eq!(
+        vec![0x01, 0x02, 0x03, b'a', b':', b'1', 0x03, b'b', b':', b'2'],
+        out
+    );
+
+    let mut versions = BTreeMap::new();
+    versions.insert("y".to_owned(), 300);
+    versions.insert("x".to_owned(), 2);
+    let mut out = Vec::new();
+    BinaryHeader::Versions(versions).encode(&mut out);
+    assert_eq!(
+        vec![0x02, 0x02, 0x01, b'x', 0x02, 0x01, b'y', 0xac, 0x02],
+        out
+    );
+
+    let mut out = Vec::new();
+    BinaryHeader::from(VersionGroupURIs::new()).encode(&mut out);
+    assert_eq!(vec![0x01, 0x00], out);
+}
+
+#[test]
+fn header_decode_works() {
+    let header = BinaryHeader::from(uris(&["a:1", "b:2"]));
+    let mut input = Vec::new();
+    header.encode(&mut input);
+    input.extend_from_slice(&[0xaa, 0xbb]);
+    let (decoded, payload) = BinaryHeader::decode(&input).unwrap();
+    assert_eq!(header, decoded);
+    assert_eq!(&[0xaa, 0xbb], payload);
+
+    let header = BinaryHeader::versions(a2_versions());
+    let mut input = Vec::new();
+    header.encode(&mut input);
+    assert_eq!((header, &[][..]), BinaryHeader::decode(&input).unwrap());
+}
+
+#[test]
+fn header_decode_errors() {
+    for (input, error) in [
+        (&[][..], DecodeHeaderError::UnexpectedEnd),
+        (&[0x01][..], DecodeHeaderError::UnexpectedEnd),
+        (
+            &[0x01, 0x01, 0x03, b'a'][..],
+            DecodeHeaderError::UnexpectedEnd,
+        ),
+        (&[0x03, 0x00][..], DecodeHeaderError::UnknownKind(0x03)),
+        (
+            &[0x01, 0x01, 0x01, 0xff][..],
+            DecodeHeaderError::InvalidUtf8,
+        ),
+        (
+            &[
+                0x01, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+            ][..],
+            DecodeHeaderError::VarintOverflow,
+        ),
+    ] {
+        assert_eq!(Err(error), BinaryHeader::decode(input), "{:?}", input);
+    }
+    match BinaryHeader::decode(&[0x01, 0x01, 0x01, b'a']) {
+        Err(DecodeHeaderError::InvalidURI(err)) => assert_eq!("a", err.uri),
+        r => panic!("invalid uri must fail, got {:?}", r),
+    }
+}
+
+#[test]
+fn header_read_from_works() {
+    for header in [
+        BinaryHeader::from(uris(&["a:1", "b:2"])),
+        BinaryHeader::versions(a2_versions()),
+        BinaryHeader::from(VersionGroupURIs::new()),
+    ] {
+        let mut input = Vec::new();
+        header.encode(&mut input);
+        input.extend_from_slice(&[0xaa, 0xbb]);
+        let mut reader = &input[..];
+        assert_eq!(header, BinaryHeader::read_from(&mut reader).unwrap());
+        // The reader is at the start of the payload
+        assert_eq!(&[0xaa, 0xbb], reader);
+    }
+}
+
+#[test]
+fn header_read_from_errors() {
+    for (input, error) in [
+        (&[][..], DecodeHeaderError::UnexpectedEnd),
+        (
+            &[0x01, 0x01, 0x03, b'a'][..],
+            DecodeHeaderError::UnexpectedEnd,
+        ),
+        // The length of the string is larger than the input
+        (
+            &[0x01, 0x01, 0xff, 0xff, 0xff, 0xff, 0x0f, b'a'][..],
+            DecodeHeaderError::UnexpectedEnd,
+        ),
+        (&[0x03, 0x00][..], DecodeHeaderError::UnknownKind(0x03)),
+        (
+            &[0x01, 0x01, 0x01, 0xff][..],
+            DecodeHeaderError::InvalidUtf8,
+        ),
+        (
+            &[
+                0x01, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+            ][..],
+            DecodeHeaderError::VarintOverflow,
+        ),
+    ] {
+        match BinaryHeader::read_from(&mut &input[..]) {
+            Err(ReadHeaderError::Decode(err)) => assert_eq!(error, err, "{:?}", input),
+            r => panic!("{:?} must fail, got {:?}", input, r),
+        }
+    }
+
+    struct FailingReader;
+    impl std::io::Read for FailingReader {
+        fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+            Err(std::io::ErrorKind::PermissionDenied.into())
+        }
+    }
+    match BinaryHeader::read_from(&mut FailingReader) {
+        Err(ReadHeaderError::Io(err)) => {
+            assert_eq!(std::io::ErrorKind::PermissionDenied, err.kind())
+        }
+        r => panic!("io errors must be kept, got {:?}", r),
+    }
+}
+
+#[cfg(feature = "bincode-support")]
+mod bincode {
+    use super::*;
+    use serde_version::binary::bincode::{deserialize, from_reader, serialize, DeserializeError};
+    use serde_version::AggregateVersionMapError;
+
+    #[test]
+    fn deserialize_works() {
+        let input = serialize(&Av1 { a: 5 }, &uris(&["a:1"]).into()).unwrap();
+        assert_eq!(vec![0x01, 0x01, 0x03, b'a', b':', b'1', 5, 0, 0, 0], input);
+        let de: A = deserialize(&input, &*VERSIONS, &()).unwrap();
+        assert_eq!(A::from(Av1 { a: 5 }), de);
+    }
+
+    #[test]
+    fn from_reader_works() {
+        for header in [
+            BinaryHeader::from(uris(&["a:2"])),
+            BinaryHeader::versions(a2_versions()),
+        ] {
+            // Only the header and the value are read
+            let mut input = serialize(&a(), &header).unwrap();
+            input.extend_from_slice(&[0xaa, 0xbb]);
+            let mut reader = &input[..];
+            let de: A = from_reader(&mut reader, &*VERSIONS, &()).unwrap();
+            assert_eq!(a(), de);
+            assert_eq!(&[0xaa, 0xbb], reader);
+        }
+
+        let input = serialize(&Av1 { a: 5 }, &uris(&["a:1"]).into()).unwrap();
+        match from_reader::<_, A, _, _>(&input[..3], &*VERSIONS, &()) {
+            Err(DeserializeError::Header(DecodeHeaderError::UnexpectedEnd)) => {}
+            r => panic!("truncated header must fail, got {:?}", r),
+        }
+        match from_reader::<_, A, _, _>(&input[..8], &*VERSIONS, &()) {
+            Err(DeserializeError::De(serde_version::Error::DeserializeError(_))) => {}
+            r => panic!("truncated payload must fail, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn roundtrip_is_not_human_readable() {
+        for header in [
+            BinaryHeader::from(uris(&["a:2"])),
+            BinaryHeader::versions(a2_versions()),
+        ] {
+            let input = serialize(&a(), &header).unwrap();
+            // The address is serialized as 4 bytes, not as a string
+            assert!(input.ends_with(&[5, 0, 0, 0, 10, 0, 0, 1]));
+            let de: A = deserialize(&input, &*VERSIONS, &()).unwrap();
+            assert_eq!(a(), de);
+        }
+    }
+
+    #[test]
+    fn deserialize_errors() {
+        match deserialize::<A, _, _>(&[0x01, 0x01], &*VERSIONS, &()) {
+            Err(DeserializeError::Header(DecodeHeaderError::UnexpectedEnd)) => {}
+            r => panic!("truncated header must fail, got {:?}", r),
+        }
+        let input = serialize(&a(), &uris(&["a:4"]).into()).unwrap();
+        match deserialize::<A, _, _>(&input, &*VERSIONS, &()) {
+            Err(DeserializeError::AggregateError(
+                AggregateVersionMapError::UnknownVersionGroupURI(uri),
+            )) => assert_eq!("a:4", uri.to_string()),
+            r => panic!("unknown uri must fail, got {:?}", r),
+        }
+        let input = serialize(&a(), &uris(&["a:3"]).into()).unwrap();
+        match deserialize::<A, _, _>(&input, &*VERSIONS, &()) {
+            Err(DeserializeError::De(serde_version::Error::InvalidVersionError(err))) => {
+                assert_eq!(3, err.version)
+            }
+            r => panic!("unknown version must fail, got {:?}", r),
+        }
+        let input = serialize(&Av1 { a: 5 }, &uris(&["a:2"]).into()).unwrap();
+        match deserialize::<A, _, _>(&input, &*VERSIONS, &()) {
+            Err(DeserializeError::De(serde_version::Error::DeserializeError(_))) => {}
+            r => panic!("truncated payload must fail, got {:?}", r),
+        }
+    }
+}
+
+#[cfg(feature = "postcard-support")]
+mod postcard {
+    use super::*;
+    use serde_version::binary::postcard::{deserialize, serialize, DeserializeError};
+
+    #[test]
+    fn deserialize_works() {
+        let input = serialize(&Av1 { a: 300 }, &uris(&["a:1"]).into()).unwrap();
+        assert_eq!(vec![0x01, 0x01, 0x03, b'a', b':', b'1', 0xac, 0x02], input);
+        let de: A = deserialize(&input, &*VERSIONS, &()).unwrap();
+        assert_eq!(A::from(Av1 { a: 300 }), de);
+    }
+
+    #[test]
+    fn roundtrip_is_not_human_readable() {
+        for header in [
+            BinaryHeader::from(uris(&["a:2"])),
+            BinaryHeader::versions(a2_versions()),
+        ] {
+            let input = serialize(&a(), &header).unwrap();
+            assert!(input.ends_with(&[5, 10, 0, 0, 1]));
+            let de: A = deserialize(&input, &*VERSIONS, &()).unwrap();
+            assert_eq!(a(), de);
+        }
+    }
+
+    #[test]
+    fn deserialize_errors() {
+        let input = serialize(&a(), &uris(&["a:3"]).into()).unwrap();
+        match deserialize::<A, _, _>(&input, &*VERSIONS, &()) {
+            Err(DeserializeError::De(serde_version::Error::InvalidVersionError(err))) => {
+                assert_eq!(3, err.version)
+            }
+            r => panic!("unknown version must fail, got {:?}", r),
+        }
+        let input = serialize(&Av1 { a: 5 }, &uris(&["a:2"]).into()).unwrap();
+        match deserialize::<A, _, _>(&input, &*VERSIONS, &()) {
+            Err(DeserializeError::De(serde_version::Error::DeserializeError(_))) => {}
+            r => panic!("truncated payload must fail, got {:?}", r),
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use serde_version::cbor::{deserialize, serialize, DeserializeError, Options};
+use serde_version::{AggregateVersionMapError, VersionGroupURIs};
+use std::collections::HashMap;
+use std::net::Ipv4Addr;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "A")]
+struct Av1 {
+    a: u32,
+}
+
+#[derive(Serialize, Deserialize, DeserializeVersioned, PartialEq, Debug)]
+#[versions(v(index = 1, type = "Av1"), v(index = 2, self))]
+struct A {
+    b: u32,
+    address: Ipv4Addr,
+}
+
+impl From<Av1> for A {
+    fn from(v: Av1) -> Self {
+        Self {
+            b: v.a,
+            address: Ipv4Addr::LOCALHOST,
+        }
+    }
+}
+
+version_group_resolver_static! {
+    pub VERSIONS = {
+        ("a" , "1") => { A => 1, },
+        ("a" , "2") => { A => 2, },
+        ("a" , "3") => { A => 3, },
+    }
+}
+
+fn uris(uris: &[&str]) -> VersionGroupURIs {
+    uris.iter().map(|uri| uri.parse().unwrap()).collect()
+}
+
+fn a() -> A {
+    A {
+        b: 5,
+        address: Ipv4Addr::new(10, 0, 0, 1),
+    }
+}
+
+fn legacy_options() -> Options {
+    let mut version_map = HashMap::new();
+    version_map.insert(std::any::type_name::<A>().to_owned(), 1);
+    Options::new().legacy_version_map(version_map)
+}
+
+#[test]
+fn serialize_works() {
+    let output = serialize(&a(), &uris(&["a:2"])).unwrap();
+    // tag(0x53564552)([["a:2"], ...])
+    assert!(output.starts_with(&[0xda, 0x53, 0x56, 0x45, 0x52, 0x82, 0x81, 0x63, b'a', b':', b'2']));
+    // The address is serialized as 4 bytes, not as a string
+    assert!(output.ends_with(&[0x84, 10, 0, 0, 1]));
+
+    for (tag, head) in [
+        (5, &[0xc5][..]),
+        (24, &[0xd8, 24][..]),
+        (40_000, &[0xd9, 0x9c, 0x40][..]),
+        (1 << 40, &[0xdb, 0, 0, 1, 0, 0, 0, 0, 0][..]),
+    ] {
+        let output = Options::new()
+            .tag(tag)
+            .serialize(&a(), &uris(&["a:2"]))
+            .unwrap();
+        assert!(output.starts_with(head), "{}", tag);
+        assert_eq!(0x82, output[head.len()], "{}", tag);
+    }
+}
+
+#[test]
+fn roundtrip_works() {
+    for options in &[Options::new(), Options::new().tag(5), legacy_options()] {
+        let output = options.serialize(&a(), &uris(&["a:2"])).unwrap();
+        let de: A = options.deserialize(&output, &*VERSIONS, &()).unwrap();
+        assert_eq!(a(), de);
+
+        let output = options.serialize(&Av1 { a: 5 }, &uris(&["a:1"])).unwrap();
+        let de: A = options.deserialize(&output, &*VERSIONS, &()).unwrap();
+        assert_eq!(A::from(Av1 { a: 5 }), de);
+    }
+}
+
+#[test]
+fn deserialize_legacy() {
+    let options = legacy_options();
+
+    let input = serde_cbor::to_vec(&Av1 { a: 5 }).unwrap();
+    let de: A = options.deserialize(&input, &*VERSIONS, &()).unwrap();
+    assert_eq!(A::from(Av1 { a: 5 }), de);
+
+    // Data with another tag is not legacy data
+    let mut input = vec![0xc5];
+    input.extend(serde_cbor::to_vec(&Av1 { a: 5 }).unwrap());
+    match options.deserialize::<A, _, _>(&input, &*VERSIONS, &()) {
+        Err(DeserializeError::UnexpectedTag { found, .. }) => assert_eq!(5, found),
+        r => panic!("another tag must fail, got {:?}", r),
+    }
+}
+
+#[test]
+fn deserialize_errors() {
+    let input = serde_cbor::to_vec(&a()).unwrap();
+    match deserialize::<A, _, _>(&input, &*VERSIONS, &()) {
+        Err(DeserializeError::MissingVersionTag(tag)) => {
+            assert_eq!(serde_version::cbor::DEFAULT_VERSION_TAG, tag)
+        }
+        r => panic!("untagged data must fail, got {:?}", r),
+    }
+    let input = Options::new()
+        .tag(5)
+        .serialize(&a(), &uris(&["a:2"]))
+        .unwrap();
+    match deserialize::<A, _, _>(&input, &*VERSIONS, &()) {
+        Err(DeserializeError::UnexpectedTag { found, .. }) => assert_eq!(5, found),
+        r => panic!("another tag must fail, got {:?}", r),
+    }
+    let input = serialize(&a(), &uris(&["a:4"])).unwrap();
+    match deserialize::<A, _, _>(&input, &*VERSIONS, &()) {
+        Err(DeserializeError::AggregateError(
+            AggregateVersionMapError::UnknownVersionGroupURI(uri),
+        )) => assert_eq!("a:4", uri.to_string()),
+        r => panic!("unknown uri must fail, got {:?}", r),
+    }
+    let input = serialize(&a(), &uris(&["a:3"])).unwrap();
+    match deserialize::<A, _, _>(&input, &*VERSIONS, &()) {
+        Err(DeserializeError::De(serde_version::Error::InvalidVersionError(err))) => {
+            assert_eq!(3, err.version)
+        }
+        r => panic!("unknown version must fail, got {:?}", r),
+    }
+
+    let valid = serialize(&a(), &uris(&["a:2"])).unwrap();
+    for input in [
+        // Trailing data
+        [&valid[..], &[0x01]].concat(),
+        // Truncated tag
+        vec![0xda, 0x53, 0x56],
+        // Reserved additional information
+        vec![0xdc],
+        // tag(["a:2"])
+        [&valid[..5], &[0x81, 0x63, b'a', b':', b'2']].concat(),
+    ] {
+        match deserialize::<A, _, _>(&input, &*VERSIONS, &()) {
+            Err(DeserializeError::Cbor(_)) => {}
+            r => panic!("{:?} must fail, got {:?}", input, r),
+        }
+    }
+}
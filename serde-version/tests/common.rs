@@ -148,7 +148,7 @@ pub mod de {
         }
     }
 
-    impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
+    impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
         type Error = Error;
 
         forward_to_deserialize_any! {
@@ -732,7 +732,7 @@ macro_rules! declare_tests_versions {
                 let version_map = get_version_map!(version_map, $($vm)*);
 
                 $(
-                    let mut de = crate::common::de::Deserializer::new($tokens);
+                    let mut de = $crate::common::de::Deserializer::new($tokens);
                     let de_versioned = VersionedDeserializer::new(&mut de, version_map);
                     match <::core::marker::PhantomData<$ty> as ::serde_version::DeserializeVersionedSeed<'_>>::deserialize_versioned(::core::marker::PhantomData, de_versioned, version_map) {
                         Ok(_) => {
@@ -757,7 +757,7 @@ macro_rules! declare_tests_versions {
 
                 $(
                     // Test ser/de roundtripping
-                    let mut de = crate::common::de::Deserializer::new($tokens);
+                    let mut de = $crate::common::de::Deserializer::new($tokens);
                     let de_versioned = ::serde_version::VersionedDeserializer::new(&mut de, version_map);
                     match <::core::marker::PhantomData<$ty> as ::serde_version::DeserializeVersionedSeed<'_>>::deserialize_versioned(::core::marker::PhantomData, de_versioned, version_map) {
                         Ok(v) => {
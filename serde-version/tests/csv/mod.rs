@@ -0,0 +1,280 @@
+use serde_version::csv::{deserialize, serialize, DeserializeError, Options};
+use serde_version::{AggregateVersionMapError, VersionGroupURIs};
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "A")]
+struct Av1 {
+    a: u32,
+    name: String,
+}
+
+#[derive(Serialize, Deserialize, DeserializeVersioned, PartialEq, Debug)]
+#[versions(v(index = 1, type = "Av1"), v(index = 2, self))]
+struct A {
+    b: u32,
+    name: String,
+    comment: Option<String>,
+}
+
+impl From<Av1> for A {
+    fn from(v: Av1) -> Self {
+        Self {
+            b: v.a,
+            name: v.name,
+            comment: None,
+        }
+    }
+}
+
+version_group_resolver_static! {
+    pub VERSIONS = {
+        ("a" , "1") => { A => 1, },
+        ("a" , "2") => { A => 2, },
+        ("a" , "3") => { A => 3, },
+    }
+}
+
+fn uris(uris: &[&str]) -> VersionGroupURIs {
+    uris.iter().map(|uri| uri.parse().unwrap()).collect()
+}
+
+fn records() -> Vec<A> {
+    vec![
+        A {
+            b: 5,
+            name: "first, with a comma".to_owned(),
+            comment: Some("ok".to_owned()),
+        },
+        A {
+            b: 6,
+            name: "second".to_owned(),
+            comment: None,
+        },
+    ]
+}
+
+#[test]
+fn serialize_works() {
+    let output = serialize(records(), &uris(&["a:2"])).unwrap();
+    assert_eq!(
+        "# serde-version: a:2\nb,name,comment\n5,\"first, with a comma\",ok\n6,second,\n",
+        output
+    );
+
+    let output = Options::new()
+        .metadata_row("v")
+        .serialize(records(), &uris(&["a:2"]))
+        .unwrap();
+    assert_eq!(
+        "v,a:2\nb,name,comment\n5,\"first, with a comma\",ok\n6,second,\n",
+        output
+    );
+
+    let output = Options::new()
+        .version_column("v")
+        .serialize(records(), &uris(&["a:2"]))
+        .unwrap();
+    assert_eq!(
+        "v,b,name,comment\na:2,5,\"first, with a comma\",ok\na:2,6,second,\n",
+        output
+    );
+
+    let output = Options::new()
+        .version_column("v")
+        .delimiter(b';')
+        .has_headers(false)
+        .serialize(records(), &uris(&["a:2"]))
+        .unwrap();
+    assert_eq!("a:2;5;first, with a comma;ok\na:2;6;second;\n", output);
+}
+
+#[test]
+fn roundtrip_works() {
+    for options in &[
+        Options::new(),
+        Options::new().comment_header("versions").delimiter(b'\t'),
+        Options::new().metadata_row("v"),
+        Options::new().metadata_row("v").has_headers(false),
+        Options::new().version_column("v"),
+        Options::new().version_column("v").has_headers(false),
+    ] {
+        let output = options.serialize(records(), &uris(&["a:2"])).unwrap();
+        let de: Vec<A> = options.deserialize(&output, &*VERSIONS, &()).unwrap();
+        assert_eq!(records(), de, "{:?}", options);
+
+        let v1 = [Av1 {
+            a: 5,
+            name: "first".to_owned(),
+        }];
+        let output = options.serialize(&v1, &uris(&["a:1"])).unwrap();
+        let de: Vec<A> = options.deserialize(&output, &*VERSIONS, &()).unwrap();
+        assert_eq!(
+            vec![A::from(Av1 {
+                a: 5,
+                name: "first".to_owned(),
+            })],
+            de,
+            "{:?}",
+            options
+        );
+    }
+}
+
+#[test]
+fn deserialize_comment_header() {
+    let input = "\
+# exported records
+#   serde-version: a:1
+
+name,a
+first,5
+#second,6
+";
+    let de: Vec<A> = deserialize(input, &*VERSIONS, &()).unwrap();
+    assert_eq!(
+        vec![
+            A {
+                b: 5,
+                name: "first".to_owned(),
+                comment: None,
+            },
+            A {
+                b: 6,
+                name: "#second".to_owned(),
+                comment: None,
+            },
+        ],
+        de
+    );
+
+    let de: Vec<A> = deserialize("# serde-version: a:2\n", &*VERSIONS, &()).unwrap();
+    assert!(de.is_empty());
+}
+
+#[derive(Serialize, Deserialize, DeserializeVersioned, PartialEq, Debug)]
+#[versions(v(index = 1, self))]
+struct Tagged {
+    tag: String,
+    b: u32,
+}
+
+#[test]
+fn roundtrip_leading_hash() {
+    // The first cell of the first row is quoted, it would read as a comment otherwise
+    let records = vec![Tagged {
+        tag: "# not a comment".to_owned(),
+        b: 5,
+    }];
+    let options = Options::new().has_headers(false);
+    let output = options.serialize(&records, &uris(&["a:2"])).unwrap();
+    assert_eq!("# serde-version: a:2\n\"# not a comment\",5\n", output);
+    let de: Vec<Tagged> = options.deserialize(&output, &*VERSIONS, &()).unwrap();
+    assert_eq!(records, de);
+}
+
+#[test]
+fn deserialize_version_column() {
+    // Each row migrates from its own version
+    let input = "\
+name,v,a,b,comment
+first,a:1,5,,
+second,a:2,,6,ok
+third,a:1,7,,
+";
+    let de: Vec<A> = Options::new()
+        .version_column("v")
+        .deserialize(input, &*VERSIONS, &())
+        .unwrap();
+    assert_eq!(
+        vec![
+            A {
+                b: 5,
+                name: "first".to_owned(),
+                comment: None,
+            },
+            A {
+                b: 6,
+                name: "second".to_owned(),
+                comment: Some("ok".to_owned()),
+            },
+            A {
+                b: 7,
+                name: "third".to_owned(),
+                comment: None,
+            },
+        ],
+        de
+    );
+}
+
+#[test]
+fn deserialize_errors() {
+    match deserialize::<A, _, _>("b,name,comment\n5,first,\n", &*VERSIONS, &()) {
+        Err(DeserializeError::MissingVersionHeader(header)) => {
+            assert_eq!("# serde-version:", header)
+        }
+        r => panic!("missing comment header must fail, got {:?}", r),
+    }
+    match Options::new().metadata_row("v").deserialize::<A, _, _>(
+        "b,name,comment\n5,first,\n",
+        &*VERSIONS,
+        &(),
+    ) {
+        Err(DeserializeError::MissingVersionHeader(header)) => assert_eq!("v", header),
+        r => panic!("missing metadata row must fail, got {:?}", r),
+    }
+    match Options::new()
+        .version_column("version")
+        .deserialize::<A, _, _>("v,b,name,comment\na:2,5,first,\n", &*VERSIONS, &())
+    {
+        Err(DeserializeError::MissingVersionHeader(header)) => assert_eq!("version", header),
+        r => panic!("missing version column must fail, got {:?}", r),
+    }
+    match deserialize::<A, _, _>("# serde-version: a\n", &*VERSIONS, &()) {
+        Err(DeserializeError::InvalidVersionHeader(_)) => {}
+        r => panic!("invalid header must fail, got {:?}", r),
+    }
+    match deserialize::<A, _, _>("# serde-version: a:4\nb,name\n", &*VERSIONS, &()) {
+        Err(DeserializeError::AggregateError(
+            AggregateVersionMapError::UnknownVersionGroupURI(uri),
+        )) => assert_eq!("a:4", uri.to_string()),
+        r => panic!("unknown uri must fail, got {:?}", r),
+    }
+    match Options::new().version_column("v").deserialize::<A, _, _>(
+        "v,b,name,comment\na:2,5,first,\na:3,6,second,\n",
+        &*VERSIONS,
+        &(),
+    ) {
+        Err(DeserializeError::De {
+            line,
+            error: serde_version::Error::InvalidVersionError(err),
+        }) => {
+            assert_eq!(3, line);
+            assert_eq!(3, err.version)
+        }
+        r => panic!("unknown version must fail, got {:?}", r),
+    }
+    match deserialize::<A, _, _>(
+        "# serde-version: a:2\nb,name,comment\n5,first,\nfive,second,\n",
+        &*VERSIONS,
+        &(),
+    ) {
+        Err(DeserializeError::De {
+            line,
+            error: serde_version::Error::DeserializeError(_),
+        }) => assert_eq!(4, line),
+        r => panic!("invalid cell must fail, got {:?}", r),
+    }
+    match deserialize::<A, _, _>(
+        "# serde-version: a:2\nb,name,comment\n5,first\n",
+        &*VERSIONS,
+        &(),
+    ) {
+        Err(DeserializeError::UnequalLengths {
+            line,
+            expected,
+            found,
+        }) => assert_eq!((3, 3, 2), (line, expected, found)),
+        r => panic!("missing field must fail, got {:?}", r),
+    }
+}
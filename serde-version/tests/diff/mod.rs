@@ -0,0 +1,141 @@
+use serde_version::diff::{Change, MigrationDiff, Value};
+
+fn map(entries: &[(&str, Value)]) -> Value {
+    Value::Map(
+        entries
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.clone()))
+            .collect(),
+    )
+}
+
+fn string(value: &str) -> Value {
+    Value::String(value.to_owned())
+}
+
+#[test]
+fn diff_works() {
+    let old = map(&[
+        ("v", Value::Seq(vec![string("a:1")])),
+        ("name", string("config")),
+        ("size", Value::Integer(5)),
+        (
+            "server",
+            map(&[("host", string("localhost")), ("port", Value::Integer(80))]),
+        ),
+        ("tags", Value::Seq(vec![string("a"), string("b")])),
+        ("my key", Value::Bool(true)),
+    ]);
+    let new = map(&[
+        ("v", Value::Seq(vec![string("a:2")])),
+        ("name", string("config")),
+        ("capacity", Value::Integer(5)),
+        (
+            "server",
+            map(&[
+                ("host", string("localhost")),
+                ("port", Value::Integer(8080)),
+                ("timeout", Value::Float(1.5)),
+            ]),
+        ),
+        ("tags", Value::Seq(vec![string("a")])),
+        ("my key", Value::Null),
+    ]);
+
+    let diff = MigrationDiff::new("old\n", "new\n", &old, &new);
+    assert_eq!(
+        vec![
+            Change::Changed {
+                path: "v[0]".to_owned(),
+                old: string("a:1"),
+                new: string("a:2"),
+            },
+            Change::Removed {
+                path: "size".to_owned(),
+                value: Value::Integer(5),
+            },
+            Change::Changed {
+                path: "server.port".to_owned(),
+                old: Value::Integer(80),
+                new: Value::Integer(8080),
+            },
+            Change::Added {
+                path: "server.timeout".to_owned(),
+                value: Value::Float(1.5),
+            },
+            Change::Removed {
+                path: "tags[1]".to_owned(),
+                value: string("b"),
+            },
+            Change::Changed {
+                path: "\"my key\"".to_owned(),
+                old: Value::Bool(true),
+                new: Value::Null,
+            },
+            Change::Added {
+                path: "capacity".to_owned(),
+                value: Value::Integer(5),
+            },
+        ],
+        diff.changes
+    );
+    assert!(diff.has_changes());
+    assert_eq!(
+        vec!["server.timeout", "capacity"],
+        diff.added().collect::<Vec<_>>()
+    );
+    assert_eq!(vec!["size", "tags[1]"], diff.removed().collect::<Vec<_>>());
+    assert_eq!(
+        vec!["v[0]", "server.port", "\"my key\""],
+        diff.changed().collect::<Vec<_>>()
+    );
+    assert_eq!(
+        "--- original\n+++ migrated\n@@ -1 +1 @@\n-old\n+new\n",
+        diff.unified_diff
+    );
+
+    let diff = MigrationDiff::new("same\n", "same\n", &old, &old);
+    assert!(!diff.has_changes());
+    assert_eq!("", diff.unified_diff);
+}
+
+#[test]
+fn diff_nan_is_unchanged() {
+    let old = map(&[("ratio", Value::Float(f64::NAN))]);
+    let diff = MigrationDiff::new("ratio = nan\n", "ratio = nan\n", &old, &old.clone());
+    assert!(!diff.has_changes());
+
+    let new = map(&[("ratio", Value::Float(1.0))]);
+    let diff = MigrationDiff::new("ratio = nan\n", "ratio = 1.0\n", &old, &new);
+    assert_eq!(vec!["ratio"], diff.changed().collect::<Vec<_>>());
+}
+
+#[test]
+fn display_works() {
+    let value = map(&[
+        ("a", Value::Seq(vec![Value::Integer(1), Value::Float(2.0)])),
+        ("b", map(&[])),
+        ("c", Value::Bytes(b"\x00a".to_vec())),
+    ]);
+    assert_eq!(
+        r#"{ a = [1, 2.0], b = {}, c = b"\x00a" }"#,
+        value.to_string()
+    );
+    assert_eq!(
+        "~ server.port: 80 -> \"80\"",
+        Change::Changed {
+            path: "server.port".to_owned(),
+            old: Value::Integer(80),
+            new: string("80"),
+        }
+        .to_string()
+    );
+    assert_eq!(
+        "+ a = null",
+        Change::Added {
+            path: "a".to_owned(),
+            value: Value::Null,
+        }
+        .to_string()
+    );
+}
@@ -0,0 +1,206 @@
+use serde_version::file::{
+    from_slice, load, save, to_vec, LoadError, PayloadFormat, SaveError, ENVELOPE_VERSION,
+};
+use serde_version::VersionGroupURIs;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "A")]
+struct Av1 {
+    a: u32,
+    name: String,
+}
+
+#[derive(Serialize, Deserialize, DeserializeVersioned, PartialEq, Debug)]
+#[versions(v(index = 1, type = "Av1"), v(index = 2, self))]
+struct A {
+    b: u32,
+    name: String,
+}
+
+impl From<Av1> for A {
+    fn from(v: Av1) -> Self {
+        Self {
+            b: v.a,
+            name: v.name,
+        }
+    }
+}
+
+version_group_resolver_static! {
+    pub VERSIONS = {
+        ("a" , "1") => { A => 1, },
+        ("a" , "2") => { A => 2, },
+        ("a" , "3") => { A => 3, },
+    }
+}
+
+fn uris(uris: &[&str]) -> VersionGroupURIs {
+    uris.iter().map(|uri| uri.parse().unwrap()).collect()
+}
+
+const FORMATS: [PayloadFormat; 8] = [
+    PayloadFormat::Toml,
+    PayloadFormat::Json,
+    PayloadFormat::Yaml,
+    PayloadFormat::Ron,
+    PayloadFormat::Bincode,
+    PayloadFormat::Postcard,
+    PayloadFormat::MsgPack,
+    PayloadFormat::Cbor,
+];
+
+#[test]
+fn format_tags() {
+    for format in FORMATS {
+        assert_eq!(Some(format), PayloadFormat::from_tag(format.tag()));
+    }
+    assert_eq!(None, PayloadFormat::from_tag(0));
+}
+
+#[test]
+fn to_vec_works() {
+    for format in FORMATS
+        .iter()
+        .copied()
+        .filter(|format| format.is_supported())
+    {
+        let output = to_vec(
+            &Av1 {
+                a: 5,
+                name: "n".to_owned(),
+            },
+            format,
+            &uris(&["a:1"]),
+        )
+        .unwrap();
+        assert_eq!(b"SVER", &output[..4]);
+        assert_eq!([ENVELOPE_VERSION, format.tag()], output[4..6]);
+        assert!(output.len() > 6, "{}", format);
+    }
+}
+
+#[test]
+fn roundtrip_works() {
+    let dir = tempfile::tempdir().unwrap();
+    for format in FORMATS
+        .iter()
+        .copied()
+        .filter(|format| format.is_supported())
+    {
+        let path = dir.path().join(format.name());
+        let value = A {
+            b: 5,
+            name: "n".to_owned(),
+        };
+        save(&path, &value, format, &uris(&["a:2"])).unwrap();
+        let de: A = load(&path, &*VERSIONS).unwrap();
+        assert_eq!(value, de, "{}", format);
+
+        let v1 = Av1 {
+            a: 5,
+            name: "n".to_owned(),
+        };
+        save(&path, &v1, format, &uris(&["a:1"])).unwrap();
+        let de: A = load(&path, &*VERSIONS).unwrap();
+        assert_eq!(A::from(v1), de, "{}", format);
+
+        let output = to_vec(&value, format, &uris(&["a:2"])).unwrap();
+        let de: A = from_slice(&output, &*VERSIONS, &()).unwrap();
+        assert_eq!(value, de, "{}", format);
+    }
+}
+
+#[test]
+fn unsupported_formats() {
+    for format in FORMATS
+        .iter()
+        .copied()
+        .filter(|format| !format.is_supported())
+    {
+        match to_vec(
+            &A {
+                b: 5,
+                name: "n".to_owned(),
+            },
+            format,
+            &uris(&["a:2"]),
+        ) {
+            Err(SaveError::UnsupportedFormat(unsupported)) => assert_eq!(format, unsupported),
+            r => panic!("{} must be unsupported, got {:?}", format, r),
+        }
+        let input = [&b"SVER"[..], &[ENVELOPE_VERSION, format.tag(), 0x01, 0x00]].concat();
+        match from_slice::<A, _, _>(&input, &*VERSIONS, &()) {
+            Err(LoadError::UnsupportedFormat(unsupported)) => assert_eq!(format, unsupported),
+            r => panic!("{} must be unsupported, got {:?}", format, r),
+        }
+    }
+}
+
+#[test]
+fn load_errors() {
+    let from_bytes = |input: &[u8]| from_slice::<A, _, _>(input, &*VERSIONS, &());
+
+    for input in [&b""[..], b"SVE", b"{\"b\": 5}", b"SVEX\x01\x02"] {
+        match from_bytes(input) {
+            Err(LoadError::NotAnEnvelope) => {}
+            r => panic!("{:?} is not an envelope, got {:?}", input, r),
+        }
+    }
+    match from_bytes(b"SVER\x01") {
+        Err(LoadError::UnexpectedEnd) => {}
+        r => panic!("truncated envelope must fail, got {:?}", r),
+    }
+    match from_bytes(b"SVER\x02\x02\x01\x00") {
+        Err(LoadError::UnsupportedEnvelopeVersion(2)) => {}
+        r => panic!("newer envelope must fail, got {:?}", r),
+    }
+    match from_bytes(b"SVER\x01\x2a\x01\x00") {
+        Err(LoadError::UnknownFormat(42)) => {}
+        r => panic!("unknown format must fail, got {:?}", r),
+    }
+
+    let dir = tempfile::tempdir().unwrap();
+    match load::<A, _, _>(dir.path().join("missing"), &*VERSIONS) {
+        Err(LoadError::Io(_)) => {}
+        r => panic!("missing file must fail, got {:?}", r),
+    }
+
+    let value = A {
+        b: 5,
+        name: "n".to_owned(),
+    };
+    for format in FORMATS
+        .iter()
+        .copied()
+        .filter(|format| format.is_supported())
+    {
+        // The errors of the module of the format are kept
+        match from_bytes(&to_vec(&value, format, &uris(&["a:4"])).unwrap()) {
+            Err(LoadError::Payload {
+                format: payload_format,
+                error,
+            }) => {
+                assert_eq!(format, payload_format);
+                assert!(error.to_string().contains("a:4"), "{}: {}", format, error);
+            }
+            r => panic!("unknown uri must fail for {}, got {:?}", format, r),
+        }
+        match from_bytes(&to_vec(&value, format, &uris(&["a:3"])).unwrap()) {
+            Err(LoadError::Payload {
+                format: payload_format,
+                ..
+            }) => assert_eq!(format, payload_format),
+            r => panic!("unknown version must fail for {}, got {:?}", format, r),
+        }
+        let mut output = to_vec(&value, format, &uris(&["a:2"])).unwrap();
+        // Cut in the last key: an empty Yaml value would be read as an empty string
+        output.truncate(output.len() - 6);
+        match from_bytes(&output) {
+            Err(LoadError::Payload {
+                format: payload_format,
+                ..
+            }) => assert_eq!(format, payload_format),
+            r => panic!("truncated payload must fail for {}, got {:?}", format, r),
+        }
+    }
+}
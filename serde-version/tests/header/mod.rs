@@ -0,0 +1,102 @@
+use serde_version::header::{DeserializeError, VersionHeader};
+use serde_version::{deserialize_with_header, AggregateVersionMapError};
+
+#[derive(Deserialize)]
+#[serde(rename = "A")]
+struct Av1 {
+    a: usize,
+}
+
+#[derive(Deserialize, DeserializeVersioned, PartialEq, Debug)]
+#[versions(v(index = 1, type = "Av1"), v(index = 2, self))]
+struct A {
+    b: usize,
+}
+
+impl From<Av1> for A {
+    fn from(v: Av1) -> Self {
+        Self { b: v.a }
+    }
+}
+
+version_group_resolver_static! {
+    pub VERSIONS = {
+        ("a" , "1") => { A => 1, },
+        ("a" , "2") => { A => 2, },
+        ("a" , "3") => { A => 3, },
+    }
+}
+
+fn deserialize(header: &VersionHeader, input: &str) -> Result<A, DeserializeError<ron::de::Error>> {
+    let mut de = ron::de::Deserializer::from_str(input).unwrap();
+    header.deserialize(&mut de, &*VERSIONS, &())
+}
+
+#[test]
+fn deserialize_with_header_works() {
+    for input in [
+        r#"(v: ["a:1"], a: 5)"#,
+        r#"(v: ["a:2"], b: 5)"#,
+        r#"[["a:1"], (a: 5)]"#,
+        r#"(["a:2"], (b: 5))"#,
+    ] {
+        let mut de = ron::de::Deserializer::from_str(input).unwrap();
+        let value: A = deserialize_with_header(&mut de, &*VERSIONS, &()).unwrap();
+        assert_eq!(A { b: 5 }, value, "{}", input);
+    }
+}
+
+#[test]
+fn deserialize_layouts() {
+    let header = VersionHeader::LeadingField {
+        key: "versions".to_owned(),
+    };
+    assert_eq!(
+        A { b: 5 },
+        deserialize(&header, r#"(versions: ["a:1"], a: 5)"#).unwrap()
+    );
+
+    let header = VersionHeader::Envelope {
+        header_key: "v".to_owned(),
+        data_key: "data".to_owned(),
+    };
+    assert_eq!(
+        A { b: 5 },
+        deserialize(&header, r#"(v: ["a:1"], data: (a: 5))"#).unwrap()
+    );
+
+    let header = VersionHeader::Sequence;
+    assert_eq!(
+        A { b: 5 },
+        deserialize(&header, r#"(["a:1"], (a: 5))"#).unwrap()
+    );
+}
+
+#[test]
+fn deserialize_errors() {
+    let header = VersionHeader::default();
+    match deserialize(&header, r#"(b: 5, v: ["a:2"])"#) {
+        Err(DeserializeError::MissingVersionHeader(key)) => assert_eq!("v", key),
+        r => panic!("header must be the first field, got {:?}", r),
+    }
+    match deserialize(&header, r#"(v: ["a:4"], b: 5)"#) {
+        Err(DeserializeError::AggregateError(
+            AggregateVersionMapError::UnknownVersionGroupURI(uri),
+        )) => assert_eq!("a:4", uri.to_string()),
+        r => panic!("unknown uri must fail, got {:?}", r),
+    }
+    match deserialize(&header, r#"(["a:3"], (b: 5))"#) {
+        Err(DeserializeError::De(serde_version::Error::InvalidVersionError(err))) => {
+            assert_eq!(3, err.version)
+        }
+        r => panic!("unknown version must fail, got {:?}", r),
+    }
+    match deserialize(&header, r#"(["a:2"], (b: 5), 6)"#) {
+        Err(DeserializeError::De(serde_version::Error::DeserializeError(_))) => {}
+        r => panic!("a third element must fail, got {:?}", r),
+    }
+    match deserialize(&VersionHeader::Sequence, r#"(v: ["a:2"], b: 5)"#) {
+        Err(DeserializeError::De(serde_version::Error::DeserializeError(_))) => {}
+        r => panic!("a map must fail with the sequence layout, got {:?}", r),
+    }
+}
@@ -0,0 +1,275 @@
+use serde_version::json::{records, serialize, DeserializeError, Options};
+use serde_version::{AggregateVersionMapError, VersionGroupURIs};
+
+#[derive(Deserialize)]
+#[serde(rename = "A")]
+struct Av1 {
+    a: usize,
+}
+
+#[derive(Serialize, Deserialize, DeserializeVersioned, PartialEq, Debug)]
+#[versions(v(index = 1, type = "Av1"), v(index = 2, self))]
+struct A {
+    b: usize,
+}
+
+impl From<Av1> for A {
+    fn from(v: Av1) -> Self {
+        Self { b: v.a }
+    }
+}
+
+version_group_resolver_static! {
+    pub VERSIONS = {
+        ("a" , "1") => { A => 1, },
+        ("a" , "2") => { A => 2, },
+        ("a" , "3") => { A => 3, },
+    }
+}
+
+fn uris(uris: &[&str]) -> VersionGroupURIs {
+    uris.iter().map(|uri| uri.parse().unwrap()).collect()
+}
+
+macro_rules! declare_de_tests {
+    ($name:ident $options:expr => { $($value:expr => $json:expr)* }) => {
+        #[test]
+        fn $name() {
+            $({
+                let input = $json;
+                let de: A = $options.deserialize(input, &*VERSIONS, &()).unwrap();
+                assert_eq!($value, de);
+                let de: A = $options.from_reader(input.as_bytes(), &*VERSIONS, &()).unwrap();
+                assert_eq!($value, de);
+            })*
+        }
+    };
+}
+
+declare_de_tests! {
+    deserialize_root_field Options::new() => {
+        A { b: 5 } => r#"{ "v": ["a:1"], "a": 5 }"#
+        A { b: 5 } => r#"{ "v": ["a:2"], "b": 5 }"#
+        A { b: 5 } => r#"{ "v": ["a:2", "a:1"], "a": 5 }"#
+    }
+}
+
+declare_de_tests! {
+    deserialize_envelope Options::new().envelope("data") => {
+        A { b: 5 } => r#"{ "v": ["a:1"], "data": { "a": 5 } }"#
+        A { b: 5 } => r#"{"v":["a:2"],"data":{"b":5}}"#
+    }
+}
+
+declare_de_tests! {
+    deserialize_header_key Options::new().header_key("versions") => {
+        A { b: 5 } => r#"{ "versions": ["a:1"], "a": 5 }"#
+    }
+}
+
+#[test]
+fn deserialize_errors() {
+    let deserialize = |input| serde_version::json::deserialize::<A, _, _>(input, &*VERSIONS, &());
+
+    match deserialize(r#"{ "b": 5, "v": ["a:2"] }"#) {
+        Err(DeserializeError::MissingVersionHeader(header)) => assert_eq!("v", header),
+        r => panic!("header must be the first field, got {:?}", r),
+    }
+    match deserialize(r#"{ "v": ["a:4"], "b": 5 }"#) {
+        Err(DeserializeError::AggregateError(
+            AggregateVersionMapError::UnknownVersionGroupURI(uri),
+        )) => assert_eq!("a:4", uri.to_string()),
+        r => panic!("unknown uri must fail, got {:?}", r),
+    }
+    match deserialize(r#"{ "v": ["a:3"], "b": 5 }"#) {
+        Err(DeserializeError::De(serde_version::Error::InvalidVersionError(err))) => {
+            assert_eq!(3, err.version)
+        }
+        r => panic!("unknown version must fail, got {:?}", r),
+    }
+    match deserialize(r#"{ "v": ["a:2"], "a": 5 }"#) {
+        Err(DeserializeError::Json(err)) => assert_eq!(1, err.line()),
+        r => panic!("invalid value must fail, got {:?}", r),
+    }
+    for input in &[
+        r#"{ "v": ["a"], "b": 5 }"#,
+        "[]",
+        r#"{ "v": ["a:2"], "b": 5 } 1"#,
+    ] {
+        match deserialize(input) {
+            Err(DeserializeError::Json(_)) => {}
+            r => panic!("{} must fail, got {:?}", input, r),
+        }
+    }
+}
+
+#[test]
+fn from_reader_errors() {
+    let from_reader = |input: &str| {
+        serde_version::json::from_reader::<_, A, _, _>(input.as_bytes(), &*VERSIONS, &())
+    };
+
+    match from_reader(r#"{ "b": 5, "v": ["a:2"] }"#) {
+        Err(DeserializeError::MissingVersionHeader(header)) => assert_eq!("v", header),
+        r => panic!("header must be the first field, got {:?}", r),
+    }
+    match from_reader(r#"{ "v": ["a:3"], "b": 5 }"#) {
+        Err(DeserializeError::De(serde_version::Error::InvalidVersionError(err))) => {
+            assert_eq!(3, err.version)
+        }
+        r => panic!("unknown version must fail, got {:?}", r),
+    }
+    match from_reader(r#"{ "v": ["a:2"], "b": 5 } {}"#) {
+        Err(DeserializeError::Json(_)) => {}
+        r => panic!("trailing characters must fail, got {:?}", r),
+    }
+}
+
+#[test]
+fn deserialize_envelope_errors() {
+    let options = Options::new().envelope("data");
+    for input in &[
+        r#"{ "v": ["a:2"] }"#,
+        r#"{ "v": ["a:2"], "b": 5 }"#,
+        r#"{ "v": ["a:2"], "data": { "b": 5 }, "extra": 1 }"#,
+    ] {
+        match options.deserialize::<A, _, _>(input, &*VERSIONS, &()) {
+            Err(DeserializeError::Json(_)) => {}
+            r => panic!("{} must fail, got {:?}", input, r),
+        }
+    }
+}
+
+#[test]
+fn serialize_works() {
+    assert_eq!(
+        r#"{"v":["a:2"],"b":5}"#,
+        serialize(&A { b: 5 }, &uris(&["a:2"])).unwrap()
+    );
+    assert_eq!(
+        r#"{"v":["a:2"],"data":{"b":5}}"#,
+        Options::new()
+            .envelope("data")
+            .serialize(&A { b: 5 }, &uris(&["a:2"]))
+            .unwrap()
+    );
+
+    let mut output = String::new();
+    let options = Options::new().header_key("versions");
+    options
+        .serialize_inplace(&mut output, &A { b: 5 }, &uris(&["a:1", "a:2"]))
+        .unwrap();
+    assert_eq!(r#"{"versions":["a:1","a:2"],"b":5}"#, output);
+    let de: A = options.deserialize(&output, &*VERSIONS, &()).unwrap();
+    assert_eq!(A { b: 5 }, de);
+
+    // The version header can only be merged in an object
+    assert!(serialize(&5, &uris(&["a:2"])).is_err());
+}
+
+#[test]
+fn records_works() {
+    // Records written by different versions are interleaved
+    let input = r#"{ "v": ["a:1"], "a": 5 }
+{ "v": ["a:2"], "b": 6 }
+
+{ "v": ["a:1"], "a": 7 }
+{"v":["a:2"],"b":8}
+"#;
+    let mut records = records(input.as_bytes(), &*VERSIONS, &());
+    let de = records.by_ref().collect::<Result<Vec<A>, _>>().unwrap();
+    assert_eq!(vec![A { b: 5 }, A { b: 6 }, A { b: 7 }, A { b: 8 }], de);
+    assert_eq!(2, records.version_headers());
+
+    let options = Options::new().envelope("data");
+    let mut input = String::new();
+    for (value, header) in [(5, &["a:2"][..]), (6, &["a:1", "a:2"])] {
+        input.push_str(&options.serialize(&A { b: value }, &uris(header)).unwrap());
+        input.push('\n');
+    }
+    let de = options
+        .records(input.as_bytes(), &*VERSIONS, &())
+        .collect::<Result<Vec<A>, _>>()
+        .unwrap();
+    assert_eq!(vec![A { b: 5 }, A { b: 6 }], de);
+}
+
+#[test]
+fn records_errors() {
+    // The invalid records don't stop the iteration
+    let input = b"{ \"v\": [\"a:2\"], \"b\": 5 }
+{ \"v\": [\"a:3\"], \"b\": 6 }
+{ \"b\": 7 }
+\xff
+{ \"v\": [\"a:2\"], \"b\": 8 } {}
+{ \"v\": [\"a:4\"], \"b\": 9 }
+{ \"v\": [\"a:2\"], \"b\": 10 }";
+    let mut records = records::<_, A, _, _>(&input[..], &*VERSIONS, &());
+
+    assert_eq!(A { b: 5 }, records.next().unwrap().unwrap());
+    match records.next().unwrap() {
+        Err(err) => match (err.line, err.error) {
+            (2, DeserializeError::De(serde_version::Error::InvalidVersionError(err))) => {
+                assert_eq!(3, err.version)
+            }
+            r => panic!("unknown version must fail, got {:?}", r),
+        },
+        r => panic!("unknown version must fail, got {:?}", r),
+    }
+    match records.next().unwrap() {
+        Err(err) => match (err.line, err.error) {
+            (3, DeserializeError::MissingVersionHeader(_)) => {}
+            r => panic!("missing header must fail, got {:?}", r),
+        },
+        r => panic!("missing header must fail, got {:?}", r),
+    }
+    for line in [4, 5] {
+        match records.next().unwrap() {
+            Err(err) => match (err.line, err.error) {
+                (l, DeserializeError::Json(_)) if l == line => {}
+                r => panic!("invalid line must fail, got {:?}", r),
+            },
+            r => panic!("invalid line must fail, got {:?}", r),
+        }
+    }
+    match records.next().unwrap() {
+        Err(err) => match (err.line, err.error) {
+            (
+                6,
+                DeserializeError::AggregateError(AggregateVersionMapError::UnknownVersionGroupURI(
+                    uri,
+                )),
+            ) => {
+                assert_eq!("a:4", uri.to_string())
+            }
+            r => panic!("unknown uri must fail, got {:?}", r),
+        },
+        r => panic!("unknown uri must fail, got {:?}", r),
+    }
+    assert_eq!(A { b: 10 }, records.next().unwrap().unwrap());
+    assert!(records.next().is_none());
+}
+
+#[cfg(feature = "diff-support")]
+#[test]
+fn diff_works() {
+    use serde_version::diff::diff;
+
+    let input = r#"{ "v": ["a:2"], "b": 5 }"#;
+    let migration =
+        diff::<A, _, _, _>(&Options::new(), input, &*VERSIONS, &(), &uris(&["a:2"])).unwrap();
+    // Only the formatting changes
+    assert!(!migration.has_changes());
+    assert!(!migration.unified_diff.is_empty());
+
+    match diff::<A, _, _, _>(
+        &Options::new(),
+        r#"{ "v": ["a:3"], "b": 5 }"#,
+        &*VERSIONS,
+        &(),
+        &uris(&["a:2"]),
+    ) {
+        Err(serde_version::json::DiffError::Deserialize(DeserializeError::De(_))) => {}
+        r => panic!("unknown version must fail, got {:?}", r),
+    }
+}
@@ -0,0 +1,280 @@
+use serde_version::manifest::{
+    ManifestError, ManifestVersionGroupResolver, ManifestVersionMap, VersionGroupManifest,
+};
+use serde_version::{
+    AggregateVersionMap, VersionGroupInheritanceError, VersionGroupResolver, VersionGroupURI,
+    VersionMap,
+};
+use std::path::Path;
+
+const PLUGIN_A: &str = r#"
+["plugin.a:1"]
+"plugin_a::Config" = 1
+"plugin_a::Item" = 1
+
+["plugin.a:2"]
+"plugin_a::Config" = 2
+"plugin_a::Item" = 1
+"#;
+
+const PLUGIN_B: &str = r#"
+["plugin.b:1"]
+"plugin_b::Config" = 3
+"#;
+
+fn uri(uri: &str) -> VersionGroupURI {
+    uri.parse().unwrap()
+}
+
+fn version<R: VersionGroupResolver>(resolver: &R, group: &str, type_id: &str) -> Option<usize> {
+    resolver
+        .resolve(&uri(group))
+        .and_then(|version_map| VersionMap::get(version_map, type_id))
+}
+
+#[test]
+fn read_toml_manifest() {
+    let manifest = VersionGroupManifest::from_toml_str(PLUGIN_A).unwrap();
+    assert_eq!(2, manifest.len());
+    let manifest = manifest.flatten().unwrap();
+    assert_eq!(
+        Some(1),
+        version(&manifest, "plugin.a:1", "plugin_a::Config")
+    );
+    assert_eq!(
+        Some(2),
+        version(&manifest, "plugin.a:2", "plugin_a::Config")
+    );
+    assert_eq!(None, version(&manifest, "plugin.a:3", "plugin_a::Config"));
+}
+
+#[cfg(feature = "json-support")]
+#[test]
+fn read_json_manifest() {
+    let manifest = VersionGroupManifest::from_json_str(
+        r#"{ "plugin.a:1": { "plugin_a::Config": 1, "plugin_a::Item": 1 } }"#,
+    )
+    .unwrap()
+    .flatten()
+    .unwrap();
+    assert_eq!(Some(1), version(&manifest, "plugin.a:1", "plugin_a::Item"));
+}
+
+#[test]
+fn reject_invalid_manifests() {
+    match VersionGroupManifest::from_toml_str("[\"plugin.a\"]\n\"plugin_a::Config\" = 1\n") {
+        Err(ManifestError::Toml(_)) => {}
+        r => panic!("invalid uri must fail, got {:?}", r),
+    }
+    match VersionGroupManifest::from_toml_str("[\"plugin.a:1\"]\n\"plugin_a::Config\" = -1\n") {
+        Err(ManifestError::Toml(_)) => {}
+        r => panic!("negative index must fail, got {:?}", r),
+    }
+    match VersionGroupManifest::from_toml_str("[\"plugin.a:1\"]\n\"\" = 1\n") {
+        Err(ManifestError::InvalidTypeKey { uri, type_id }) => {
+            assert_eq!("plugin.a:1", uri.to_string());
+            assert_eq!("", type_id);
+        }
+        r => panic!("empty type key must fail, got {:?}", r),
+    }
+
+    // Manifests read with any serde format are validated too
+    match VersionGroupManifest::from_deserializer(&mut ::toml::de::Deserializer::new(
+        "[\"plugin.a:1\"]\n\"\" = 1\n",
+    )) {
+        Err(err) => assert!(err.to_string().contains("Invalid type key")),
+        r => panic!("empty type key must fail, got {:?}", r),
+    }
+}
+
+#[test]
+fn read_type_name_keys() {
+    // `std::any::type_name` of generic types and references contain spaces
+    let manifest = VersionGroupManifest::from_toml_str(
+        "[\"plugin.a:1\"]\n\"plugin_a::Pair<(u8, &str)>\" = 1\n\"&'static str\" = 2\n",
+    )
+    .unwrap()
+    .flatten()
+    .unwrap();
+    assert_eq!(
+        Some(1),
+        version(&manifest, "plugin.a:1", "plugin_a::Pair<(u8, &str)>")
+    );
+    assert_eq!(Some(2), version(&manifest, "plugin.a:1", "&'static str"));
+}
+
+#[test]
+fn merge_manifests_of_sources() {
+    let mut resolver = ManifestVersionGroupResolver::new();
+    resolver
+        .insert_manifest("a", VersionGroupManifest::from_toml_str(PLUGIN_A).unwrap())
+        .unwrap();
+    resolver
+        .insert_manifest("b", VersionGroupManifest::from_toml_str(PLUGIN_B).unwrap())
+        .unwrap();
+
+    assert_eq!(Some("a"), resolver.source_of(&uri("plugin.a:2")));
+    assert_eq!(Some("b"), resolver.source_of(&uri("plugin.b:1")));
+
+    let version_map = ()
+        .aggregate_version_maps(
+            &vec![uri("plugin.a:2"), uri("plugin.b:1")].into(),
+            &resolver,
+        )
+        .unwrap();
+    assert_eq!(Some(2), VersionMap::get(&version_map, "plugin_a::Config"));
+    assert_eq!(Some(1), VersionMap::get(&version_map, "plugin_a::Item"));
+    assert_eq!(Some(3), VersionMap::get(&version_map, "plugin_b::Config"));
+}
+
+#[test]
+fn reload_manifest_of_source() {
+    let mut resolver = ManifestVersionGroupResolver::new();
+    resolver
+        .insert_manifest("a", VersionGroupManifest::from_toml_str(PLUGIN_A).unwrap())
+        .unwrap();
+
+    let reloaded: VersionGroupManifest = vec![(
+        uri("plugin.a:3"),
+        vec![("plugin_a::Config".to_owned(), 3)]
+            .into_iter()
+            .collect::<ManifestVersionMap>(),
+    )]
+    .into_iter()
+    .collect();
+    let previous = resolver.insert_manifest("a", reloaded).unwrap();
+    assert_eq!(Some(2), previous.map(|manifest| manifest.len()));
+
+    assert_eq!(None, version(&resolver, "plugin.a:2", "plugin_a::Config"));
+    assert_eq!(
+        Some(3),
+        version(&resolver, "plugin.a:3", "plugin_a::Config")
+    );
+
+    assert!(resolver.remove_manifest("a").unwrap().is_some());
+    assert_eq!(None, version(&resolver, "plugin.a:3", "plugin_a::Config"));
+    assert_eq!(0, resolver.sources().count());
+}
+
+#[test]
+fn reject_group_declared_by_two_sources() {
+    let mut resolver = ManifestVersionGroupResolver::new();
+    resolver
+        .insert_manifest("a", VersionGroupManifest::from_toml_str(PLUGIN_A).unwrap())
+        .unwrap();
+    match resolver.insert_manifest("b", VersionGroupManifest::from_toml_str(PLUGIN_A).unwrap()) {
+        Err(ManifestError::DuplicateGroup {
+            uri,
+            first_source,
+            second_source,
+        }) => {
+            assert_eq!("plugin.a:1", uri.to_string());
+            assert_eq!("a", first_source);
+            assert_eq!("b", second_source);
+        }
+        r => panic!("duplicate group must fail, got {:?}", r),
+    }
+    // The resolver is unchanged
+    assert_eq!(vec!["a"], resolver.sources().collect::<Vec<_>>());
+    assert_eq!(
+        Some(1),
+        version(&resolver, "plugin.a:1", "plugin_a::Config")
+    );
+}
+
+#[test]
+fn load_manifest_file() {
+    let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("load_manifest_file.toml");
+    std::fs::write(&path, PLUGIN_B).unwrap();
+
+    let mut resolver = ManifestVersionGroupResolver::new();
+    resolver.load_file("b", &path).unwrap();
+    assert_eq!(
+        Some(3),
+        version(&resolver, "plugin.b:1", "plugin_b::Config")
+    );
+
+    match resolver.load_file("b", path.with_extension("ini")) {
+        Err(ManifestError::UnknownFormat(_)) => {}
+        r => panic!("unknown extension must fail, got {:?}", r),
+    }
+}
+
+const PLUGIN_C: &str = r#"
+["plugin.c:1"]
+extends = "plugin.a:2"
+"plugin_a::Item" = 3
+"plugin_c::Config" = 1
+
+["plugin.c:2"]
+extends = "plugin.c:1"
+"plugin_c::Config" = 2
+"#;
+
+#[test]
+fn read_extended_groups() {
+    let manifest = VersionGroupManifest::from_toml_str(PLUGIN_C).unwrap();
+    assert_eq!(
+        Some(&uri("plugin.c:1")),
+        manifest.get(&uri("plugin.c:2")).unwrap().extends.as_ref()
+    );
+    // The parent of plugin.c:1 is declared in another manifest
+    match manifest.flatten() {
+        Err(VersionGroupInheritanceError::UnknownParent { uri, parent }) => {
+            assert_eq!("plugin.c:1", uri.to_string());
+            assert_eq!("plugin.a:2", parent.to_string());
+        }
+        r => panic!("unknown parent must fail, got {:?}", r),
+    }
+}
+
+#[test]
+fn extend_groups_of_other_sources() {
+    let mut resolver = ManifestVersionGroupResolver::new();
+    match resolver.insert_manifest("c", VersionGroupManifest::from_toml_str(PLUGIN_C).unwrap()) {
+        Err(ManifestError::Inheritance(VersionGroupInheritanceError::UnknownParent { .. })) => {}
+        r => panic!("unknown parent must fail, got {:?}", r),
+    }
+    assert_eq!(0, resolver.sources().count());
+
+    resolver
+        .insert_manifest("a", VersionGroupManifest::from_toml_str(PLUGIN_A).unwrap())
+        .unwrap();
+    resolver
+        .insert_manifest("c", VersionGroupManifest::from_toml_str(PLUGIN_C).unwrap())
+        .unwrap();
+    assert_eq!(
+        Some(2),
+        version(&resolver, "plugin.c:2", "plugin_a::Config")
+    );
+    assert_eq!(Some(3), version(&resolver, "plugin.c:2", "plugin_a::Item"));
+    assert_eq!(
+        Some(2),
+        version(&resolver, "plugin.c:2", "plugin_c::Config")
+    );
+    assert_eq!(Some(1), version(&resolver, "plugin.a:2", "plugin_a::Item"));
+
+    // plugin.c extends a group of the source a
+    match resolver.remove_manifest("a") {
+        Err(ManifestError::Inheritance(_)) => {}
+        r => panic!("removing a parent must fail, got {:?}", r),
+    }
+    assert!(resolver.remove_manifest("c").unwrap().is_some());
+    assert!(resolver.remove_manifest("a").unwrap().is_some());
+}
+
+#[test]
+fn reject_cycle_of_groups() {
+    let manifest = VersionGroupManifest::from_toml_str(
+        "[\"a:1\"]\nextends = \"b:1\"\n[\"b:1\"]\nextends = \"a:1\"\n",
+    )
+    .unwrap();
+    let mut resolver = ManifestVersionGroupResolver::new();
+    match resolver.insert_manifest("a", manifest) {
+        Err(ManifestError::Inheritance(error)) => assert_eq!(
+            "Cycle in the parents of version groups: a:1 -> b:1 -> a:1",
+            error.to_string()
+        ),
+        r => panic!("cycle must fail, got {:?}", r),
+    }
+}
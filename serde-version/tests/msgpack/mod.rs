@@ -0,0 +1,183 @@
+use serde_version::msgpack::{deserialize, from_reader, serialize, DeserializeError, Options};
+use serde_version::{AggregateVersionMapError, VersionGroupURIs};
+use std::net::Ipv4Addr;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "A")]
+struct Av1 {
+    a: u32,
+}
+
+#[derive(Serialize, Deserialize, DeserializeVersioned, PartialEq, Debug)]
+#[versions(v(index = 1, type = "Av1"), v(index = 2, self))]
+struct A {
+    b: u32,
+    address: Ipv4Addr,
+}
+
+impl From<Av1> for A {
+    fn from(v: Av1) -> Self {
+        Self {
+            b: v.a,
+            address: Ipv4Addr::LOCALHOST,
+        }
+    }
+}
+
+version_group_resolver_static! {
+    pub VERSIONS = {
+        ("a" , "1") => { A => 1, },
+        ("a" , "2") => { A => 2, },
+        ("a" , "3") => { A => 3, },
+    }
+}
+
+fn uris(uris: &[&str]) -> VersionGroupURIs {
+    uris.iter().map(|uri| uri.parse().unwrap()).collect()
+}
+
+fn a() -> A {
+    A {
+        b: 5,
+        address: Ipv4Addr::new(10, 0, 0, 1),
+    }
+}
+
+#[test]
+fn deserialize_array_envelope() {
+    // [["a:1"], [5]]
+    let input = [0x92, 0x91, 0xa3, b'a', b':', b'1', 0x91, 0x05];
+    let de: A = deserialize(&input, &*VERSIONS, &()).unwrap();
+    assert_eq!(A::from(Av1 { a: 5 }), de);
+
+    // [["a:1"], {"a": 5}]
+    let input = [0x92, 0x91, 0xa3, b'a', b':', b'1', 0x81, 0xa1, b'a', 0x05];
+    let de: A = deserialize(&input, &*VERSIONS, &()).unwrap();
+    assert_eq!(A::from(Av1 { a: 5 }), de);
+}
+
+#[test]
+fn from_reader_works() {
+    // Only the document is read
+    let mut input = serialize(&Av1 { a: 5 }, &uris(&["a:1"])).unwrap();
+    input.extend_from_slice(&[0xc0, 0xc0]);
+    let mut reader = &input[..];
+    let de: A = from_reader(&mut reader, &*VERSIONS, &()).unwrap();
+    assert_eq!(A::from(Av1 { a: 5 }), de);
+    assert_eq!(&[0xc0, 0xc0], reader);
+
+    match from_reader::<_, A, _, _>(&input[..3], &*VERSIONS, &()) {
+        Err(DeserializeError::MsgPack(_)) => {}
+        r => panic!("truncated document must fail, got {:?}", r),
+    }
+}
+
+#[test]
+fn roundtrip_works() {
+    for options in &[
+        Options::new(),
+        Options::new().struct_map(true),
+        Options::new().map_envelope("v", "data"),
+        Options::new()
+            .map_envelope("versions", "payload")
+            .struct_map(true),
+    ] {
+        let output = options.serialize(&a(), &uris(&["a:2"])).unwrap();
+        let de: A = options.deserialize(&output, &*VERSIONS, &()).unwrap();
+        assert_eq!(a(), de, "{:?}", options);
+        let de: A = options.from_reader(&output[..], &*VERSIONS, &()).unwrap();
+        assert_eq!(a(), de, "{:?}", options);
+
+        let output = options.serialize(&Av1 { a: 5 }, &uris(&["a:1"])).unwrap();
+        let de: A = options.deserialize(&output, &*VERSIONS, &()).unwrap();
+        assert_eq!(A::from(Av1 { a: 5 }), de, "{:?}", options);
+    }
+}
+
+#[test]
+fn serialize_works() {
+    assert_eq!(
+        vec![0x92, 0x91, 0xa3, b'a', b':', b'2', 0x92, 0x05, 0x94, 10, 0, 0, 1],
+        serialize(&a(), &uris(&["a:2"])).unwrap()
+    );
+    assert_eq!(
+        vec![
+            0x82, 0xa1, b'v', 0x91, 0xa3, b'a', b':', b'1', 0xa4, b'd', b'a', b't', b'a', 0x81,
+            0xa1, b'a', 0x05
+        ],
+        Options::new()
+            .map_envelope("v", "data")
+            .struct_map(true)
+            .serialize(&Av1 { a: 5 }, &uris(&["a:1"]))
+            .unwrap()
+    );
+}
+
+#[test]
+fn deserialize_errors() {
+    let input = serialize(&a(), &uris(&["a:4"])).unwrap();
+    match deserialize::<A, _, _>(&input, &*VERSIONS, &()) {
+        Err(DeserializeError::AggregateError(
+            AggregateVersionMapError::UnknownVersionGroupURI(uri),
+        )) => assert_eq!("a:4", uri.to_string()),
+        r => panic!("unknown uri must fail, got {:?}", r),
+    }
+    let input = serialize(&a(), &uris(&["a:3"])).unwrap();
+    match deserialize::<A, _, _>(&input, &*VERSIONS, &()) {
+        Err(DeserializeError::De(serde_version::Error::InvalidVersionError(err))) => {
+            assert_eq!(3, err.version)
+        }
+        r => panic!("unknown version must fail, got {:?}", r),
+    }
+
+    let map = Options::new().map_envelope("v", "data");
+    let input = Options::new()
+        .map_envelope("versions", "data")
+        .serialize(&a(), &uris(&["a:2"]))
+        .unwrap();
+    match map.deserialize::<A, _, _>(&input, &*VERSIONS, &()) {
+        Err(DeserializeError::MissingVersionHeader(key)) => assert_eq!("v", key),
+        r => panic!("missing header must fail, got {:?}", r),
+    }
+
+    let array = serialize(&a(), &uris(&["a:2"])).unwrap();
+    for (options, input) in [
+        (
+            Options::new(),
+            map.serialize(&a(), &uris(&["a:2"])).unwrap(),
+        ),
+        (map.clone(), array.clone()),
+        // [["a:2"]]
+        (Options::new(), vec![0x91, 0x91, 0xa3, b'a', b':', b'2']),
+        // [["a:2"], [5, [10, 0, 0, 1]], 1]
+        (Options::new(), {
+            let mut input = array.clone();
+            input[0] = 0x93;
+            input.push(0x01);
+            input
+        }),
+        (Options::new(), array[..array.len() - 1].to_vec()),
+    ] {
+        match options.deserialize::<A, _, _>(&input, &*VERSIONS, &()) {
+            Err(DeserializeError::MsgPack(_)) => {}
+            r => panic!("{:?} must fail, got {:?}", input, r),
+        }
+    }
+}
+
+#[test]
+fn upgrade_file_works() {
+    use serde_version::msgpack::upgrade_file;
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("data.msgpack");
+    std::fs::write(&path, serialize(&Av1 { a: 5 }, &uris(&["a:1"])).unwrap()).unwrap();
+
+    let report = upgrade_file::<A, _, _, _>(&path, &*VERSIONS, &(), &uris(&["a:2"])).unwrap();
+    assert!(report.changed);
+    let de: A = deserialize(&std::fs::read(&path).unwrap(), &*VERSIONS, &()).unwrap();
+    assert_eq!(A::from(Av1 { a: 5 }), de);
+
+    let report = upgrade_file::<A, _, _, _>(&path, &*VERSIONS, &(), &uris(&["a:2"])).unwrap();
+    assert!(!report.changed);
+}
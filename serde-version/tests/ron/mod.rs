@@ -0,0 +1,223 @@
+use serde_version::ron::{serialize, DeserializeError, Options};
+use serde_version::{AggregateVersionMapError, VersionGroupURIs};
+
+#[derive(Deserialize)]
+#[serde(rename = "A")]
+struct Av1 {
+    a: usize,
+}
+
+#[derive(Serialize, Deserialize, DeserializeVersioned, PartialEq, Debug)]
+#[versions(v(index = 1, type = "Av1"), v(index = 2, self))]
+struct A {
+    b: usize,
+}
+
+impl From<Av1> for A {
+    fn from(v: Av1) -> Self {
+        Self { b: v.a }
+    }
+}
+
+version_group_resolver_static! {
+    pub VERSIONS = {
+        ("a" , "1") => { A => 1, },
+        ("a" , "2") => { A => 2, },
+        ("a" , "3") => { A => 3, },
+    }
+}
+
+fn uris(uris: &[&str]) -> VersionGroupURIs {
+    uris.iter().map(|uri| uri.parse().unwrap()).collect()
+}
+
+macro_rules! declare_de_tests {
+    ($name:ident $options:expr => { $($value:expr => $ron:expr)* }) => {
+        #[test]
+        fn $name() {
+            $({
+                let input = $ron;
+                let de: A = $options.deserialize(input, &*VERSIONS, &()).unwrap();
+                assert_eq!($value, de);
+            })*
+        }
+    };
+}
+
+declare_de_tests! {
+    deserialize_leading_field Options::new() => {
+        A { b: 5 } => r#"(v: ["a:1"], a: 5)"#
+        A { b: 5 } => r#"(v: ["a:2"], b: 5)"#
+        A { b: 5 } => r#"(v: ["a:2", "a:1"], a: 5,)"#
+        A { b: 5 } => r#"A(v: ["a:2"], b: 5)"#
+        A { b: 5 } => "(\n    v: [\"a:2\"],\n    b: 5,\n)\n"
+    }
+}
+
+declare_de_tests! {
+    deserialize_header_key Options::new().header_key("versions") => {
+        A { b: 5 } => r#"(versions: ["a:1"], a: 5)"#
+    }
+}
+
+declare_de_tests! {
+    deserialize_prelude Options::new().prelude("versions") => {
+        A { b: 5 } => "#![versions(\"a:1\")]\n(a: 5)"
+        A { b: 5 } => "  #![ versions ( \"a:2\" , \"a:1\" , ) ]\n\n(a: 5)\n"
+        A { b: 5 } => "#![versions(\"a:2\")]\n#![enable(implicit_some)]\nA(b: 5)"
+        A { b: 5 } => "#![versions(\"\\u{61}:\\x31\")]\n(a: 5)"
+    }
+}
+
+#[test]
+fn deserialize_errors() {
+    let deserialize = |input| serde_version::ron::deserialize::<A, _, _>(input, &*VERSIONS, &());
+
+    match deserialize(r#"(b: 5, v: ["a:2"])"#) {
+        Err(DeserializeError::MissingVersionHeader(header)) => assert_eq!("v", header),
+        r => panic!("header must be the first field, got {:?}", r),
+    }
+    match deserialize(r#"(v: ["a:4"], b: 5)"#) {
+        Err(DeserializeError::AggregateError(
+            AggregateVersionMapError::UnknownVersionGroupURI(uri),
+        )) => assert_eq!("a:4", uri.to_string()),
+        r => panic!("unknown uri must fail, got {:?}", r),
+    }
+    match deserialize(r#"(v: ["a:3"], b: 5)"#) {
+        Err(DeserializeError::De(serde_version::Error::InvalidVersionError(err))) => {
+            assert_eq!(3, err.version)
+        }
+        r => panic!("unknown version must fail, got {:?}", r),
+    }
+    for input in &[r#"(v: ["a"], b: 5)"#, "[]", r#"(v: ["a:2"], b: 5) 1"#] {
+        match deserialize(input) {
+            Err(DeserializeError::Ron(_)) => {}
+            r => panic!("{} must fail, got {:?}", input, r),
+        }
+    }
+}
+
+#[test]
+fn deserialize_prelude_errors() {
+    let options = Options::new().prelude("versions");
+    let deserialize = |input| options.deserialize::<A, _, _>(input, &*VERSIONS, &());
+
+    for input in &[
+        "(b: 5)",
+        "#![enable(implicit_some)]\n(b: 5)",
+        "#![versionsX(\"a:2\")]\n(b: 5)",
+    ] {
+        match deserialize(input) {
+            Err(DeserializeError::MissingVersionHeader(header)) => {
+                assert_eq!("#![versions(...)]", header)
+            }
+            r => panic!("{} must fail, got {:?}", input, r),
+        }
+    }
+    for input in &[
+        "#![versions]\n(b: 5)",
+        "#![versions(a:2)]\n(b: 5)",
+        "#![versions(\"a\")]\n(b: 5)",
+        "#![versions(\"a:2\" \"a:1\")]\n(b: 5)",
+        "#![versions(\"a:2\")\n(b: 5)",
+        "#![versions(\"a:2)]\n(b: 5)",
+        "#![versions(\"a\\q:2\")]\n(b: 5)",
+    ] {
+        match deserialize(input) {
+            Err(DeserializeError::InvalidPrelude(_)) => {}
+            r => panic!("{} must fail, got {:?}", input, r),
+        }
+    }
+    match deserialize("#![versions(\"a:2\")]\n(a: 5)") {
+        Err(DeserializeError::De(serde_version::Error::DeserializeError(_))) => {}
+        r => panic!("invalid value must fail, got {:?}", r),
+    }
+}
+
+#[test]
+fn serialize_works() {
+    assert_eq!(
+        r#"(v:["a:2",],b:5,)"#,
+        serialize(&A { b: 5 }, &uris(&["a:2"])).unwrap()
+    );
+    assert_eq!(
+        "#![versions(\"a:1\", \"a:2\")]\n(b:5,)",
+        Options::new()
+            .prelude("versions")
+            .serialize(&A { b: 5 }, &uris(&["a:1", "a:2"]))
+            .unwrap()
+    );
+
+    let key = String::from("versions");
+    assert_eq!(
+        r#"(versions:["a:2",],b:5,)"#,
+        Options::new()
+            .header_key(key)
+            .serialize(&A { b: 5 }, &uris(&["a:2"]))
+            .unwrap()
+    );
+
+    // The version header can only be merged in a struct
+    assert!(serialize(&5, &uris(&["a:2"])).is_err());
+    // The key must be a field name
+    assert!(Options::new()
+        .header_key("my key")
+        .serialize(&A { b: 5 }, &uris(&["a:2"]))
+        .is_err());
+}
+
+#[test]
+fn serialize_pretty_roundtrip() {
+    let config = ron::ser::PrettyConfig {
+        indentor: "  ".to_owned(),
+        ..Default::default()
+    };
+    for options in &[
+        Options::new().pretty(config.clone()),
+        Options::new().header_key("versions").pretty(config.clone()),
+        Options::new().prelude("versions").pretty(config.clone()),
+    ] {
+        let output = options
+            .serialize(&A { b: 5 }, &uris(&["a:1", "a:2"]))
+            .unwrap();
+        let de: A = options.deserialize(&output, &*VERSIONS, &()).unwrap();
+        assert_eq!(A { b: 5 }, de);
+    }
+    assert_eq!(
+        "(\n  v: [\n    \"a:2\",\n  ],\n  b: 5,\n)",
+        Options::new()
+            .pretty(config)
+            .serialize(&A { b: 5 }, &uris(&["a:2"]))
+            .unwrap()
+    );
+}
+
+#[cfg(feature = "diff-support")]
+#[test]
+fn diff_works() {
+    use serde_version::diff::diff;
+
+    let migration = diff::<A, _, _, _>(
+        &Options::new(),
+        r#"(v: ["a:1"], a: 5)"#,
+        &*VERSIONS,
+        &(),
+        &uris(&["a:2"]),
+    )
+    .unwrap();
+    assert_eq!(vec!["b"], migration.added().collect::<Vec<_>>());
+    assert_eq!(vec!["a"], migration.removed().collect::<Vec<_>>());
+    assert_eq!(vec!["v[0]"], migration.changed().collect::<Vec<_>>());
+
+    let prelude = diff::<A, _, _, _>(
+        &Options::new().prelude("versions"),
+        "#![versions(\"a:1\")]\n(a: 5)",
+        &*VERSIONS,
+        &(),
+        &uris(&["a:2"]),
+    )
+    .unwrap();
+    assert_eq!(vec!["b"], prelude.added().collect::<Vec<_>>());
+    assert_eq!(vec!["a"], prelude.removed().collect::<Vec<_>>());
+    assert_eq!(vec!["versions[0]"], prelude.changed().collect::<Vec<_>>());
+}
@@ -0,0 +1,96 @@
+use serde_version::semver::{
+    parse_version, InvalidSemverError, NewerVersionPolicy, SemverVersionGroupResolver,
+};
+use serde_version::{VersionGroupResolver, VersionGroupURI, VersionMap};
+use std::any::type_name;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+struct A;
+struct B;
+
+version_group_resolver_static! {
+    VERSIONS = {
+        ("company", "1.2") => { A => 1, B => 1, },
+        ("company", "1.4") => { A => 2, B => 1, },
+        ("company", "1.7.1") => { A => 2, B => 2, },
+        ("company", "2") => { A => 3, B => 2, },
+    }
+}
+
+fn resolver(
+    policy: NewerVersionPolicy,
+) -> SemverVersionGroupResolver<std::collections::HashMap<&'static str, usize>> {
+    SemverVersionGroupResolver::from_groups(VERSIONS.clone(), policy).unwrap()
+}
+
+fn version_of_a<R: VersionGroupResolver>(resolver: &R, uri: &str) -> Option<usize> {
+    let uri: VersionGroupURI = uri.parse().unwrap();
+    resolver
+        .resolve(&uri)
+        .and_then(|version_map| VersionMap::get(version_map, type_name::<A>()))
+}
+
+#[test]
+fn parse_partial_versions() {
+    let version = |uri: &str| parse_version(&uri.parse().unwrap()).unwrap().to_string();
+    assert_eq!("1.0.0", version("company:1"));
+    assert_eq!("1.4.0", version("company:1.4"));
+    assert_eq!("1.4.7", version("company:1.4.7"));
+    assert_eq!("1.4.0-beta.1", version("company:1.4-beta.1"));
+
+    match parse_version(&"company:latest".parse().unwrap()) {
+        Err(InvalidSemverError { uri, .. }) => assert_eq!("company:latest", uri.to_string()),
+        Ok(v) => panic!("'latest' must not be parsed, got {}", v),
+    }
+}
+
+#[test]
+fn resolve_patch_versions() {
+    let resolver = resolver(NewerVersionPolicy::Error);
+    assert_eq!(Some(1), version_of_a(&resolver, "company:1.2"));
+    assert_eq!(Some(1), version_of_a(&resolver, "company:1.2.9"));
+    assert_eq!(Some(2), version_of_a(&resolver, "company:1.4.7"));
+    assert_eq!(Some(3), version_of_a(&resolver, "company:2.0.3"));
+}
+
+#[test]
+fn resolve_undeclared_minor_versions() {
+    let resolver = resolver(NewerVersionPolicy::Error);
+    // 1.5 is between declared groups: its schemas are the ones of 1.4
+    assert_eq!(Some(2), version_of_a(&resolver, "company:1.5.2"));
+    // 1.7.0 is before the first group of 1.7
+    assert_eq!(Some(2), version_of_a(&resolver, "company:1.7.0"));
+
+    assert_eq!(None, version_of_a(&resolver, "company:1.1"));
+    assert_eq!(None, version_of_a(&resolver, "company:0.9"));
+    assert_eq!(None, version_of_a(&resolver, "company:3"));
+    assert_eq!(None, version_of_a(&resolver, "other:1.4"));
+    assert_eq!(None, version_of_a(&resolver, "company:latest"));
+}
+
+#[test]
+fn resolve_newer_minor_versions() {
+    assert_eq!(
+        None,
+        version_of_a(&resolver(NewerVersionPolicy::Error), "company:1.8")
+    );
+    assert_eq!(
+        Some(2),
+        version_of_a(&resolver(NewerVersionPolicy::ClosestLower), "company:1.8")
+    );
+    assert_eq!(
+        Some(3),
+        version_of_a(&resolver(NewerVersionPolicy::ClosestLower), "company:2.1")
+    );
+
+    static WARNINGS: AtomicUsize = AtomicUsize::new(0);
+    fn warn(uri: &VersionGroupURI, used: &::semver::Version) {
+        assert_eq!("company:1.8.2", uri.to_string());
+        assert_eq!("1.7.1", used.to_string());
+        WARNINGS.fetch_add(1, Ordering::SeqCst);
+    }
+    let resolver = resolver(NewerVersionPolicy::Warn(warn));
+    assert_eq!(Some(2), version_of_a(&resolver, "company:1.8.2"));
+    assert_eq!(Some(2), version_of_a(&resolver, "company:1.7.3"));
+    assert_eq!(1, WARNINGS.load(Ordering::SeqCst));
+}
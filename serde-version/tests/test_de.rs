@@ -1,3 +1,6 @@
+// clippy reads the entries of `#[versions(...)]` as duplicated attributes
+#![allow(clippy::duplicated_attributes)]
+
 #[macro_use]
 extern crate serde_version_derive;
 
@@ -0,0 +1,2 @@
+#[cfg(feature = "diff-support")]
+pub mod diff;
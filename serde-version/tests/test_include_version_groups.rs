@@ -0,0 +1,125 @@
+// clippy reads the entries of `#[versions(...)]` as duplicated attributes
+#![allow(clippy::duplicated_attributes)]
+
+#[macro_use]
+extern crate serde_version_derive;
+
+use serde::Deserialize;
+use serde_version::{
+    DeclaredVersions, StaticVersionGroupResolver, VersionGroupResolver, VersionMap,
+};
+use std::any::type_name;
+
+#[derive(Deserialize)]
+#[serde(rename = "A")]
+struct Av1 {
+    #[allow(dead_code)]
+    a: usize,
+}
+
+#[derive(Deserialize, DeserializeVersioned)]
+#[versions(v(index = 1, type = "Av1"), v(index = 2, self))]
+struct A {
+    #[allow(dead_code)]
+    b: usize,
+}
+
+impl From<Av1> for A {
+    fn from(v: Av1) -> Self {
+        Self { b: v.a }
+    }
+}
+
+mod types {
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    #[serde(rename = "B")]
+    pub struct Bv1 {
+        #[allow(dead_code)]
+        a: usize,
+    }
+
+    #[derive(Deserialize, DeserializeVersioned)]
+    #[versions(v(index = 1, type = "Bv1"), v(index = 3, self))]
+    pub struct B {
+        #[allow(dead_code)]
+        b: usize,
+    }
+
+    impl From<Bv1> for B {
+        fn from(v: Bv1) -> Self {
+            Self { b: v.a }
+        }
+    }
+}
+
+include_version_groups!(pub VERSIONS = "tests/include_version_groups/groups.toml");
+
+fn version<T>(resolver: &StaticVersionGroupResolver, uri: &str) -> Option<usize> {
+    resolver
+        .resolve(&uri.parse().unwrap())
+        .and_then(|version_map| VersionMap::get(version_map, type_name::<T>()))
+}
+
+#[test]
+fn declared_versions() {
+    assert_eq!(
+        &[1, 2],
+        {
+            let mut versions = A::VERSIONS.to_vec();
+            versions.sort_unstable();
+            versions
+        }
+        .as_slice()
+    );
+}
+
+#[test]
+fn include_groups_as_static() {
+    assert_eq!(3, VERSIONS.len());
+    assert_eq!(Some(1), version::<A>(&VERSIONS, "company:1"));
+    assert_eq!(Some(2), version::<A>(&VERSIONS, "company:2"));
+    assert_eq!(Some(1), version::<types::B>(&VERSIONS, "company:2"));
+    assert_eq!(Some(3), version::<types::B>(&VERSIONS, "plugin:1"));
+    assert_eq!(None, version::<A>(&VERSIONS, "plugin:1"));
+}
+
+#[test]
+fn include_groups_as_expression() {
+    let resolver: StaticVersionGroupResolver =
+        include_version_groups!("tests/include_version_groups/groups.toml");
+    assert_eq!(*VERSIONS, resolver);
+}
+
+#[cfg(feature = "toml-support")]
+#[test]
+fn include_groups_as_type_names() {
+    use serde_version::manifest::VersionGroupManifest;
+    use std::collections::HashMap;
+
+    // The runtime manifest declaring the same groups is keyed by the full type names
+    let manifest = format!(
+        "[\"company:1\"]\n\"{a}\" = 1\n\"{b}\" = 1\n\n\
+         [\"company:2\"]\nextends = \"company:1\"\n\"{a}\" = 2\n\n\
+         [\"plugin:1\"]\n\"{b}\" = 3\n",
+        a = type_name::<A>(),
+        b = type_name::<types::B>(),
+    );
+    assert_eq!(
+        "test_include_version_groups::types::B",
+        type_name::<types::B>()
+    );
+    let runtime = VersionGroupManifest::from_toml_str(&manifest)
+        .unwrap()
+        .flatten()
+        .unwrap();
+    assert_eq!(VERSIONS.len(), runtime.len());
+    for (uri, version_map) in VERSIONS.iter() {
+        let expected: HashMap<String, usize> = version_map
+            .iter()
+            .map(|(type_id, version)| (type_id.to_string(), *version))
+            .collect();
+        assert_eq!(Some(&expected), runtime.get(uri));
+    }
+}
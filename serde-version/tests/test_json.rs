@@ -0,0 +1,13 @@
+// clippy reads the entries of `#[versions(...)]` as duplicated attributes
+#![allow(clippy::duplicated_attributes)]
+
+extern crate lazy_static;
+#[cfg_attr(feature = "json-support", macro_use)]
+extern crate serde;
+#[cfg_attr(feature = "json-support", macro_use)]
+extern crate serde_version;
+#[cfg_attr(feature = "json-support", macro_use)]
+extern crate serde_version_derive;
+
+#[cfg(feature = "json-support")]
+pub mod json;
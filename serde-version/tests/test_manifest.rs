@@ -0,0 +1,2 @@
+#[cfg(feature = "toml-support")]
+pub mod manifest;
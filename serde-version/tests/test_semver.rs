@@ -0,0 +1,5 @@
+#[cfg_attr(feature = "semver-support", macro_use)]
+extern crate serde_version;
+
+#[cfg(feature = "semver-support")]
+pub mod semver;
@@ -1,3 +1,6 @@
+// clippy reads the entries of `#[versions(...)]` as duplicated attributes
+#![allow(clippy::duplicated_attributes)]
+
 extern crate lazy_static;
 #[cfg_attr(feature = "toml-support", macro_use)]
 extern crate serde;
@@ -0,0 +1,257 @@
+#[macro_use]
+extern crate serde_version;
+
+use serde_version::{
+    flatten_version_groups, AggregatePolicy, AggregateVersionMap, AggregateVersionMapError,
+    AggregatedVersionMap, CachedAggregate, VersionConflictError, VersionGroupCycle,
+    VersionGroupInheritanceError, VersionGroupResolver, VersionGroupURI, VersionGroupURIs,
+    VersionMap,
+};
+use std::any::type_name;
+use std::cell::Cell;
+use std::collections::HashMap;
+
+struct A;
+struct B;
+struct C;
+
+version_group_resolver_static! {
+    pub VERSIONS = {
+        ("company", "1") => { A => 1, B => 1, },
+        ("company", "2") => { A => 2, B => 1, },
+        ("plugin", "1") => { C => 3, },
+        ("override", "1") => { B => 4, },
+    }
+}
+
+fn uris(uris: &[&str]) -> VersionGroupURIs {
+    uris.iter().map(|uri| uri.parse().unwrap()).collect()
+}
+
+#[test]
+fn resolve_static_groups() {
+    let version_map = VERSIONS.resolve(&"company:2".parse().unwrap()).unwrap();
+    assert_eq!(Some(2), VersionMap::get(&version_map, type_name::<A>()));
+    assert_eq!(Some(1), VersionMap::get(&version_map, type_name::<B>()));
+    assert_eq!(None, VersionMap::get(&version_map, type_name::<C>()));
+
+    assert!(VERSIONS.resolve(&"company:3".parse().unwrap()).is_none());
+}
+
+#[test]
+fn aggregate_groups() {
+    let version_map =
+        ().aggregate_version_maps(&uris(&["company:1", "plugin:1"]), &*VERSIONS)
+            .unwrap();
+    assert_eq!(Some(1), VersionMap::get(&version_map, type_name::<A>()));
+    assert_eq!(Some(1), VersionMap::get(&version_map, type_name::<B>()));
+    assert_eq!(Some(3), VersionMap::get(&version_map, type_name::<C>()));
+}
+
+#[test]
+fn aggregate_groups_later_uri_overrides() {
+    let version_map =
+        ().aggregate_version_maps(&uris(&["company:1", "override:1"]), &*VERSIONS)
+            .unwrap();
+    assert_eq!(Some(4), VersionMap::get(&version_map, type_name::<B>()));
+
+    let version_map =
+        ().aggregate_version_maps(&uris(&["override:1", "company:1"]), &*VERSIONS)
+            .unwrap();
+    assert_eq!(Some(1), VersionMap::get(&version_map, type_name::<B>()));
+}
+
+#[test]
+fn aggregate_unknown_group() {
+    let uri: VersionGroupURI = "plugin:2".parse().unwrap();
+    assert_eq!(
+        Err(AggregateVersionMapError::UnknownVersionGroupURI(uri)),
+        ().aggregate_version_maps(&uris(&["company:1", "plugin:2"]), &*VERSIONS)
+    );
+}
+
+#[test]
+fn aggregate_policies() {
+    let header = uris(&["company:1", "override:1", "company:2"]);
+    let version_of_b = |policy: AggregatePolicy| {
+        let version_map = policy.aggregate_version_maps(&header, &*VERSIONS).unwrap();
+        VersionMap::get(&version_map, type_name::<B>())
+    };
+
+    assert_eq!(Some(1), version_of_b(AggregatePolicy::FirstWins));
+    assert_eq!(Some(1), version_of_b(AggregatePolicy::LastWins));
+    assert_eq!(Some(4), version_of_b(AggregatePolicy::HighestVersionWins));
+}
+
+#[test]
+fn aggregate_policies_without_conflict() {
+    // The same version from several uris is not a conflict
+    let header = uris(&["company:1", "plugin:1", "company:1"]);
+    for policy in &[
+        AggregatePolicy::ErrorOnConflict,
+        AggregatePolicy::FirstWins,
+        AggregatePolicy::LastWins,
+        AggregatePolicy::HighestVersionWins,
+    ] {
+        let version_map = policy.aggregate_version_maps(&header, &*VERSIONS).unwrap();
+        assert_eq!(Some(1), VersionMap::get(&version_map, type_name::<A>()));
+        assert_eq!(Some(1), VersionMap::get(&version_map, type_name::<B>()));
+        assert_eq!(Some(3), VersionMap::get(&version_map, type_name::<C>()));
+    }
+}
+
+#[test]
+fn aggregate_conflict() {
+    let error = AggregatePolicy::ErrorOnConflict
+        .aggregate_version_maps(&uris(&["plugin:1", "company:1", "override:1"]), &*VERSIONS)
+        .unwrap_err();
+    assert_eq!(
+        AggregateVersionMapError::VersionConflict(Box::new(VersionConflictError {
+            type_id: type_name::<B>().to_owned(),
+            first_version: 1,
+            first_uri: "company:1".parse().unwrap(),
+            second_version: 4,
+            second_uri: "override:1".parse().unwrap(),
+        })),
+        error
+    );
+    assert_eq!(
+        format!(
+            "Conflicting versions for type {}: version 1 from company:1 and version 4 from override:1",
+            type_name::<B>()
+        ),
+        error.to_string()
+    );
+}
+
+version_group_resolver_static! {
+    EXTENDED = {
+        ("company", "1.3") => { A => 1, B => 1, C => 1, },
+        ("company", "1.4") extends "company:1.3" => { A => 2, },
+        ("company", "1.5") extends "company:1.4" => { C => 3, },
+    }
+}
+
+/// Count the aggregations
+#[derive(Default)]
+struct CountingAggregate(Cell<usize>);
+
+impl AggregateVersionMap for CountingAggregate {
+    fn aggregate_version_maps<VMR>(
+        &self,
+        uris: &VersionGroupURIs,
+        resolver: &VMR,
+    ) -> Result<AggregatedVersionMap, AggregateVersionMapError>
+    where
+        VMR: VersionGroupResolver,
+    {
+        self.0.set(self.0.get() + 1);
+        ().aggregate_version_maps(uris, resolver)
+    }
+}
+
+#[test]
+fn aggregate_cached() {
+    let counting = CountingAggregate::default();
+    let aggregate = CachedAggregate::new(&counting);
+    for header in [
+        &["company:1", "override:1"][..],
+        &["company:2"],
+        &["company:1", "override:1"],
+        &["company:2"],
+    ] {
+        let version_map = aggregate
+            .aggregate_version_maps(&uris(header), &*VERSIONS)
+            .unwrap();
+        assert_eq!(
+            ().aggregate_version_maps(&uris(header), &*VERSIONS),
+            Ok(version_map)
+        );
+    }
+    assert_eq!(2, counting.0.get());
+    assert_eq!(2, aggregate.len());
+
+    // The errors are not cached
+    for _ in 0..2 {
+        assert!(aggregate
+            .aggregate_version_maps(&uris(&["plugin:2"]), &*VERSIONS)
+            .is_err());
+    }
+    assert_eq!(4, counting.0.get());
+    assert_eq!(2, aggregate.len());
+
+    // The cached version maps are shared
+    let cached = aggregate
+        .version_map(&uris(&["company:2"]), &*VERSIONS)
+        .unwrap();
+    let lent = aggregate
+        .with_version_map(&uris(&["company:2"]), &*VERSIONS, |version_map| {
+            version_map as *const AggregatedVersionMap
+        })
+        .unwrap();
+    assert!(std::ptr::eq(&*cached, lent));
+    assert_eq!(4, counting.0.get());
+
+    aggregate.clear();
+    assert!(aggregate.is_empty());
+}
+
+#[test]
+fn resolve_extended_groups() {
+    let version_map = EXTENDED.resolve(&"company:1.5".parse().unwrap()).unwrap();
+    assert_eq!(Some(2), VersionMap::get(&version_map, type_name::<A>()));
+    assert_eq!(Some(1), VersionMap::get(&version_map, type_name::<B>()));
+    assert_eq!(Some(3), VersionMap::get(&version_map, type_name::<C>()));
+
+    let version_map = EXTENDED.resolve(&"company:1.3".parse().unwrap()).unwrap();
+    assert_eq!(Some(1), VersionMap::get(&version_map, type_name::<A>()));
+}
+
+fn group(
+    uri: &str,
+    parent: Option<&str>,
+    versions: &[(&'static str, usize)],
+) -> (
+    VersionGroupURI,
+    Option<VersionGroupURI>,
+    HashMap<&'static str, usize>,
+) {
+    (
+        uri.parse().unwrap(),
+        parent.map(|parent| parent.parse().unwrap()),
+        versions.iter().cloned().collect(),
+    )
+}
+
+#[test]
+fn flatten_unknown_parent() {
+    assert_eq!(
+        Err(VersionGroupInheritanceError::UnknownParent {
+            uri: "company:2".parse().unwrap(),
+            parent: "company:1".parse().unwrap(),
+        }),
+        flatten_version_groups(vec![group("company:2", Some("company:1"), &[("A", 2)])])
+    );
+}
+
+#[test]
+fn flatten_cycle() {
+    let error = flatten_version_groups(vec![
+        group("a:1", Some("c:1"), &[]),
+        group("b:1", Some("a:1"), &[]),
+        group("c:1", Some("b:1"), &[]),
+        group("d:1", None, &[]),
+    ])
+    .unwrap_err();
+    assert_eq!(
+        "Cycle in the parents of version groups: a:1 -> c:1 -> b:1 -> a:1",
+        error.to_string()
+    );
+
+    assert_eq!(
+        Err(VersionGroupInheritanceError::Cycle(VersionGroupCycle(
+            vec!["a:1".parse().unwrap(), "a:1".parse().unwrap()]
+        ))),
+        flatten_version_groups(vec![group("a:1", Some("a:1"), &[])])
+    );
+}
@@ -0,0 +1,234 @@
+use serde_version::toml::edit::{upgrade, upgrade_file, Renames};
+use serde_version::toml::Options;
+use serde_version::VersionGroupURIs;
+
+#[derive(Deserialize)]
+#[serde(rename = "Config")]
+struct Configv1 {
+    name: String,
+    size: u32,
+    #[allow(dead_code)]
+    legacy: String,
+    server: Serverv1,
+}
+
+#[derive(Deserialize)]
+struct Serverv1 {
+    host: String,
+    port: u16,
+}
+
+#[derive(Serialize, Deserialize, DeserializeVersioned, PartialEq, Debug)]
+#[versions(v(index = 1, type = "Configv1"), v(index = 2, self))]
+struct Config {
+    name: String,
+    capacity: u32,
+    enabled: bool,
+    server: Server,
+    limits: Limits,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct Limits {
+    connections: u32,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct Server {
+    host: String,
+    port: u16,
+    timeout: u32,
+}
+
+impl From<Configv1> for Config {
+    fn from(v: Configv1) -> Self {
+        Self {
+            name: v.name,
+            capacity: v.size,
+            enabled: true,
+            server: Server {
+                host: v.server.host,
+                port: v.server.port,
+                timeout: 30,
+            },
+            limits: Limits { connections: 10 },
+        }
+    }
+}
+
+version_group_resolver_static! {
+    pub VERSIONS = {
+        ("c" , "1") => { Config => 1, },
+        ("c" , "2") => { Config => 2, },
+    }
+}
+
+fn uris(uris: &[&str]) -> VersionGroupURIs {
+    uris.iter().map(|uri| uri.parse().unwrap()).collect()
+}
+
+const V1: &str = r#"# My config, edited by hand
+v = ["c:1"] # managed by the tool
+
+name   = "my config" # the displayed name
+size = 5
+legacy = "unused"
+
+# Where to connect
+[server]
+host = 'localhost' # local only
+port = 8080
+"#;
+
+const V2: &str = r#"# My config, edited by hand
+v = ["c:2"] # managed by the tool
+
+name   = "my config" # the displayed name
+capacity = 5
+enabled = true
+
+# Where to connect
+[server]
+host = 'localhost' # local only
+port = 8080
+timeout = 30
+
+[limits]
+connections = 10
+"#;
+
+#[test]
+fn upgrade_preserves_formatting() {
+    let output = upgrade::<Config, _, _>(V1, &*VERSIONS, &(), &uris(&["c:2"])).unwrap();
+    assert_eq!(V2, output);
+
+    // An up to date document is left as is
+    let output = upgrade::<Config, _, _>(V2, &*VERSIONS, &(), &uris(&["c:2"])).unwrap();
+    assert_eq!(V2, output);
+}
+
+#[test]
+fn upgrade_keeps_inline_tables() {
+    let input = r#"v = ["c:1"]
+name = "my config"
+size = 5
+legacy = "unused"
+server = { host = "localhost", port = 8080 } # inline
+"#;
+    let output = upgrade::<Config, _, _>(input, &*VERSIONS, &(), &uris(&["c:2"])).unwrap();
+    // `capacity` is a new key even with the value of the removed `size`
+    assert_eq!(
+        r#"v = ["c:2"]
+name = "my config"
+server = { host = "localhost", port = 8080, timeout = 30 } # inline
+capacity = 5
+enabled = true
+
+[limits]
+connections = 10
+"#,
+        output
+    );
+    let de: Config = serde_version::toml::deserialize(&output, &*VERSIONS, &()).unwrap();
+    assert_eq!(30, de.server.timeout);
+}
+
+#[test]
+fn upgrade_renames_keys() {
+    let input = r#"v = ["c:1"]
+name = "my config"
+# The number of slots
+size = 5 # at most 10
+legacy = "unused"
+server = { host = "localhost", port = 8080 }
+"#;
+    let output = Options::new()
+        .upgrade_preserving::<Config, _, _>(
+            input,
+            &*VERSIONS,
+            &(),
+            &uris(&["c:2"]),
+            &Renames::new().key("size", "capacity"),
+        )
+        .unwrap();
+    assert_eq!(
+        r#"v = ["c:2"]
+name = "my config"
+# The number of slots
+capacity = 5 # at most 10
+server = { host = "localhost", port = 8080, timeout = 30 }
+enabled = true
+
+[limits]
+connections = 10
+"#,
+        output
+    );
+}
+
+#[test]
+fn upgrade_comment_header() {
+    let options = Options::new().comment_header("serde-version");
+    let input = r#"# Config
+#  serde-version: c:1
+
+name = "my config"
+size = 5
+legacy = "unused"
+
+[server]
+host = "localhost"
+port = 8080
+"#;
+    let output = options
+        .upgrade_preserving::<Config, _, _>(
+            input,
+            &*VERSIONS,
+            &(),
+            &uris(&["c:2"]),
+            &Renames::new(),
+        )
+        .unwrap();
+    assert_eq!(
+        r#"# Config
+#  serde-version: c:2
+
+name = "my config"
+capacity = 5
+enabled = true
+
+[server]
+host = "localhost"
+port = 8080
+timeout = 30
+
+[limits]
+connections = 10
+"#,
+        output
+    );
+}
+
+#[test]
+fn upgrade_file_works() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.toml");
+    std::fs::write(&path, V1).unwrap();
+
+    let report = upgrade_file::<Config, _, _, _>(&path, &*VERSIONS, &(), &uris(&["c:2"])).unwrap();
+    assert!(report.changed);
+    assert_eq!(V2, std::fs::read_to_string(&path).unwrap());
+
+    let report = upgrade_file::<Config, _, _, _>(&path, &*VERSIONS, &(), &uris(&["c:2"])).unwrap();
+    assert!(!report.changed);
+}
+
+#[test]
+fn upgrade_errors() {
+    match upgrade::<Config, _, _>("v = [\"c:3\"]\n", &*VERSIONS, &(), &uris(&["c:2"])) {
+        Err(serde_version::toml::UpgradeError::Deserialize(
+            serde_version::toml::DeserializeError::AggregateError(_),
+        )) => {}
+        r => panic!("unknown uri must fail, got {:?}", r),
+    }
+}
@@ -1,4 +1,5 @@
 use serde_version::toml::serialize;
+use serde_version::VersionGroupURI;
 use std::convert::TryInto;
 
 #[derive(Deserialize)]
@@ -52,6 +53,11 @@ version_group_resolver_static! {
     }
 }
 
+#[derive(Deserialize)]
+struct VersionsHeader {
+    v: Vec<Versions>,
+}
+
 version_group_enum! {
     #[derive(Deserialize)]
     enum Versions {
@@ -68,7 +74,7 @@ macro_rules! declare_de_tests {
         fn $name() {
             $({
                 let input = $toml;
-                let de: Container = serde_version::toml::deserialize(input, &*VERSIONS, &()).unwrap();
+                let de: A = serde_version::toml::deserialize(input, &*VERSIONS, &()).unwrap();
                 assert_eq!($value, de);
             })*
         }
@@ -77,23 +83,114 @@ macro_rules! declare_de_tests {
 
 declare_de_tests! {
     deserialize_works {
-        Container { a: A { b: 5 }, b: B { b: 3 } } => r#"v = ["a:1", "b:2"]
-[a]
+        A { b: 5 } => r#"v = ["a:1", "b:2"]
 a = 5
-
-[b]
-b = 3
 "#
-        Container { a: A { b: 5 }, b: B { b: 3 } } => r#"v = ["a:2", "b:2"]
-[a]
+        A { b: 5 } => r#"v = ["a:2", "b:2"]
 b = 5
+"#
+        A { b: 5 } => r#"v = ["b:1", "a:1"]
+a = 5
+"#
+    }
+}
 
-[b]
-b = 3
+declare_de_tests! {
+    deserialize_header_anywhere_in_root_table {
+        A { b: 5 } => r#"# Comment before the header
+
+v = ["a:1"]
+a = 5
+"#
+        A { b: 5 } => r#"b = 5
+v = ["a:2"]
+"#
+        A { b: 5 } => r#"b = 5 # ]
+  v = [
+    "b:1", # ]
+    "a:2",
+  ]
 "#
     }
 }
 
+version_group_resolver_static! {
+    pub BRACKETS = {
+        ("a[0]" , "1]") => { A => 1, },
+    }
+}
+
+#[test]
+fn deserialize_uri_with_brackets() {
+    let input = r#"v = ["a[0]:1]"]
+a = 5
+"#;
+    let de: A = serde_version::toml::deserialize(input, &*BRACKETS, &()).unwrap();
+    assert_eq!(A { b: 5 }, de);
+}
+
+#[test]
+fn deserialize_invalid_header() {
+    use serde_version::toml::DeserializeError;
+
+    let deserialize = |input| serde_version::toml::deserialize::<A, _, _>(input, &*VERSIONS, &());
+    match deserialize("b = 5\n") {
+        Err(DeserializeError::MissingVersionHeader(header)) => assert_eq!("v", header),
+        r => panic!("missing header must fail, got {:?}", r.map(|_| ())),
+    }
+    // The header must be in the root table
+    match deserialize("b = 5\n[a]\nv = [\"a:2\"]\n") {
+        Err(DeserializeError::MissingVersionHeader(header)) => assert_eq!("v", header),
+        r => panic!("header in a table must fail, got {:?}", r.map(|_| ())),
+    }
+    match deserialize("v = \"a:2\"\nb = 5\n") {
+        Err(DeserializeError::InvalidVersionHeader(_)) => {}
+        r => panic!("header must be an array, got {:?}", r.map(|_| ())),
+    }
+    match deserialize("v = [\"a\"]\nb = 5\n") {
+        Err(DeserializeError::InvalidVersionHeader(_)) => {}
+        r => panic!("invalid uri must fail, got {:?}", r.map(|_| ())),
+    }
+    match deserialize("v = [\"a:2\"\nb = 5\n") {
+        Err(DeserializeError::Toml(_)) => {}
+        r => panic!("invalid toml must fail, got {:?}", r.map(|_| ())),
+    }
+    match deserialize("") {
+        Err(DeserializeError::MissingVersionHeader(header)) => assert_eq!("v", header),
+        r => panic!("empty document must fail, got {:?}", r.map(|_| ())),
+    }
+}
+
+#[test]
+fn deserialize_unknown_uri() {
+    let input = r#"v = ["a:3"]
+b = 5
+"#;
+    match serde_version::toml::deserialize::<A, _, _>(input, &*VERSIONS, &()) {
+        Err(serde_version::toml::DeserializeError::AggregateError(
+            serde_version::AggregateVersionMapError::UnknownVersionGroupURI(uri),
+        )) => assert_eq!("a:3", uri.to_string()),
+        Err(e) => panic!("unexpected error: {}", e),
+        Ok(_) => panic!("unknown uri must fail"),
+    }
+}
+
+#[test]
+fn version_group_enum_works() {
+    let versions: Vec<Versions> = ::toml::from_str::<VersionsHeader>(r#"v = ["av1", "bv2"]"#)
+        .unwrap()
+        .v;
+    let uris: Vec<VersionGroupURI> = versions.into_iter().map(Into::into).collect();
+    assert_eq!(
+        vec![
+            "a:1".parse::<VersionGroupURI>().unwrap(),
+            "b:2".parse().unwrap()
+        ],
+        uris
+    );
+    assert_eq!("a:2", Versions::A2.uri().to_string());
+}
+
 #[test]
 fn serialize_works() {
     let v = serialize(
@@ -115,3 +212,344 @@ b = 3
         v
     );
 }
+
+#[derive(Serialize, Deserialize, DeserializeVersioned, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+#[versions(v(index = 2, self))]
+struct Document {
+    b: usize,
+    v: String,
+    meta: Meta,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+struct Meta {
+    author: String,
+}
+
+version_group_resolver_static! {
+    pub DOCUMENT_VERSIONS = {
+        ("doc" , "2") => { Document => 2, },
+    }
+}
+
+fn document() -> Document {
+    Document {
+        b: 5,
+        v: "not a header".to_owned(),
+        meta: Meta {
+            author: "me".to_owned(),
+        },
+    }
+}
+
+#[test]
+fn header_key_in_table() {
+    use serde_version::toml::Options;
+
+    let options = Options::new()
+        .header_table("meta.serde")
+        .header_key("version");
+    let input = r#"b = 5
+v = "not a header"
+
+[meta]
+author = "me"
+
+[meta.serde]
+version = ["doc:2"]
+"#;
+    let de: Document = options
+        .deserialize(input, &*DOCUMENT_VERSIONS, &())
+        .unwrap();
+    assert_eq!(document(), de);
+
+    let output = options
+        .serialize(&document(), &vec!["doc:2".parse().unwrap()].into())
+        .unwrap();
+    assert_eq!(input, output);
+
+    match options.deserialize::<Document, _, _>(
+        "b = 5\n[meta]\nauthor = \"me\"\n",
+        &*DOCUMENT_VERSIONS,
+        &(),
+    ) {
+        Err(serde_version::toml::DeserializeError::MissingVersionHeader(header)) => {
+            assert_eq!("meta.serde.version", header)
+        }
+        r => panic!("missing header must fail, got {:?}", r),
+    }
+}
+
+#[test]
+fn header_key_in_root_table() {
+    use serde_version::toml::Options;
+
+    let options = Options::new().header_key("version");
+    let input = r#"version = ["doc:2"]
+b = 5
+v = "not a header"
+
+[meta]
+author = "me"
+"#;
+    let de: Document = options
+        .deserialize(input, &*DOCUMENT_VERSIONS, &())
+        .unwrap();
+    assert_eq!(document(), de);
+    let output = options
+        .serialize(&document(), &vec!["doc:2".parse().unwrap()].into())
+        .unwrap();
+    assert_eq!(input, output);
+}
+
+#[derive(Deserialize, DeserializeVersioned, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+#[versions(v(index = 2, self))]
+struct Borrowed<'a> {
+    name: &'a str,
+    #[serde(borrow)]
+    meta: BorrowedMeta<'a>,
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+struct BorrowedMeta<'a> {
+    #[serde(borrow)]
+    author: std::borrow::Cow<'a, str>,
+}
+
+version_group_resolver_static! {
+    pub BORROWED_VERSIONS = {
+        ("doc" , "2") => { Borrowed => 2, },
+    }
+}
+
+#[test]
+fn deserialize_borrowed() {
+    use serde_version::toml::Options;
+    use std::borrow::Cow;
+
+    let input = r#"v = ["doc:2"]
+name = "my config"
+
+[meta]
+author = "me"
+"#;
+    let de: Borrowed = serde_version::toml::deserialize(input, &*BORROWED_VERSIONS, &()).unwrap();
+    assert_eq!("my config", de.name);
+    assert!(matches!(de.meta.author, Cow::Borrowed("me")));
+
+    let input = r#"name = "my config"
+
+[meta]
+author = "me"
+
+[meta.serde]
+version = ["doc:2"]
+"#;
+    let de: Borrowed = Options::new()
+        .header_table("meta.serde")
+        .header_key("version")
+        .deserialize(input, &*BORROWED_VERSIONS, &())
+        .unwrap();
+    assert_eq!("my config", de.name);
+    assert!(matches!(de.meta.author, Cow::Borrowed("me")));
+}
+
+#[test]
+fn comment_header() {
+    use serde_version::toml::{Options, DEFAULT_COMMENT_HEADER_MARKER};
+
+    let options = Options::new().comment_header(DEFAULT_COMMENT_HEADER_MARKER);
+    let input = r#"# serde-version: doc:2
+b = 5
+v = "not a header"
+
+[meta]
+author = "me"
+"#;
+    let de: Document = options
+        .deserialize(input, &*DOCUMENT_VERSIONS, &())
+        .unwrap();
+    assert_eq!(document(), de);
+    let output = options
+        .serialize(&document(), &vec!["doc:2".parse().unwrap()].into())
+        .unwrap();
+    assert_eq!(input, output);
+
+    let de: A = options
+        .deserialize(
+            "# A document\n\n#serde-version: a:1, b:2\na = 5\n",
+            &*VERSIONS,
+            &(),
+        )
+        .unwrap();
+    assert_eq!(A { b: 5 }, de);
+
+    // Only the leading comments are read
+    match options.deserialize::<A, _, _>("a = 5\n# serde-version: a:1\n", &*VERSIONS, &()) {
+        Err(serde_version::toml::DeserializeError::MissingVersionHeader(header)) => {
+            assert_eq!("# serde-version:", header)
+        }
+        r => panic!("missing header must fail, got {:?}", r),
+    }
+    match options.deserialize::<A, _, _>("# serde-version: a\na = 5\n", &*VERSIONS, &()) {
+        Err(serde_version::toml::DeserializeError::InvalidCommentHeader(_)) => {}
+        r => panic!("invalid uri must fail, got {:?}", r),
+    }
+}
+
+#[test]
+fn upgrade_file_works() {
+    use serde_version::toml::{upgrade_file, Options, UpgradeError};
+    use serde_version::upgrade::UpgradeOptions;
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.toml");
+    let current_uris = vec!["a:2".parse().unwrap()].into();
+    std::fs::write(&path, "v = [\"a:1\"]\na = 5\n").unwrap();
+
+    let report = upgrade_file::<A, _, _, _>(&path, &*VERSIONS, &(), &current_uris).unwrap();
+    assert!(report.changed);
+    assert_eq!(None, report.backup);
+    assert_eq!(
+        serialize(&A { b: 5 }, &current_uris).unwrap(),
+        std::fs::read_to_string(&path).unwrap()
+    );
+
+    // An upgraded file is already up to date
+    let report = upgrade_file::<A, _, _, _>(&path, &*VERSIONS, &(), &current_uris).unwrap();
+    assert!(!report.changed);
+
+    // An up to date file is left untouched, with its comments
+    let commented = "# edited by hand\nv = [\"a:2\"]\nb = 5 # the value\n";
+    std::fs::write(&path, commented).unwrap();
+    let report = upgrade_file::<A, _, _, _>(&path, &*VERSIONS, &(), &current_uris).unwrap();
+    assert!(!report.changed);
+    assert_eq!(commented, std::fs::read_to_string(&path).unwrap());
+
+    // With a backup of the original file
+    std::fs::write(&path, "v = [\"a:1\"]\na = 6\n").unwrap();
+    let report = UpgradeOptions::new()
+        .backup("bak")
+        .upgrade_file_as::<A, _, _, _, _>(&Options::new(), &path, &*VERSIONS, &(), &current_uris)
+        .unwrap();
+    assert!(report.changed);
+    assert_eq!(
+        "v = [\"a:1\"]\na = 6\n",
+        std::fs::read_to_string(report.backup.unwrap()).unwrap()
+    );
+
+    std::fs::write(&path, "v = [\"a:3\"]\nb = 5\n").unwrap();
+    match upgrade_file::<A, _, _, _>(&path, &*VERSIONS, &(), &current_uris) {
+        Err(UpgradeError::Deserialize(serde_version::toml::DeserializeError::AggregateError(
+            _,
+        ))) => {}
+        r => panic!("unknown uri must fail, got {:?}", r),
+    }
+    // The error converts into a `failure::Error`
+    let upgrade = || -> Result<_, failure::Error> {
+        Ok(upgrade_file::<A, _, _, _>(
+            &path,
+            &*VERSIONS,
+            &(),
+            &current_uris,
+        )?)
+    };
+    let error = upgrade().unwrap_err();
+    assert!(error.as_fail().cause().is_some());
+    assert_eq!(
+        "v = [\"a:3\"]\nb = 5\n",
+        std::fs::read_to_string(&path).unwrap()
+    );
+
+    std::fs::write(&path, b"v = [\"a:2\"]\nb = \xff\n").unwrap();
+    match upgrade_file::<A, _, _, _>(&path, &*VERSIONS, &(), &current_uris) {
+        Err(UpgradeError::Io(err)) => assert_eq!(std::io::ErrorKind::InvalidData, err.kind()),
+        r => panic!("invalid utf-8 must fail, got {:?}", r),
+    }
+}
+
+#[cfg(feature = "toml-edit-support")]
+mod edit;
+
+#[test]
+fn upgrade_dir_works() {
+    use serde_version::toml::{upgrade_dir, DeserializeError, UpgradeError};
+
+    let dir = tempfile::tempdir().unwrap();
+    let current_uris = vec!["a:2".parse().unwrap()].into();
+    std::fs::create_dir(dir.path().join("sub")).unwrap();
+    std::fs::write(dir.path().join("old.toml"), "v = [\"a:1\"]\na = 5\n").unwrap();
+    std::fs::write(
+        dir.path().join("sub/current.toml"),
+        "v = [\"a:2\"]\nb = 5\n",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.path().join("sub/unknown.toml"),
+        "v = [\"a:3\"]\nb = 5\n",
+    )
+    .unwrap();
+    std::fs::write(dir.path().join("notes.txt"), "v = [\"a:1\"]\na = 5\n").unwrap();
+
+    let report = upgrade_dir::<A, _, _, _>(dir.path(), &*VERSIONS, &(), &current_uris);
+    assert_eq!(3, report.files.len());
+
+    let old = &report.files[0];
+    assert_eq!(dir.path().join("old.toml"), old.path);
+    assert!(old.migrated());
+    assert_eq!(Some(vec!["a:1".parse().unwrap()].into()), old.uris);
+    assert_eq!(
+        Some([("test_toml::toml::A".to_owned(), 1)].into_iter().collect()),
+        old.version_map
+    );
+    assert_eq!(
+        serialize(&A { b: 5 }, &current_uris).unwrap(),
+        std::fs::read_to_string(&old.path).unwrap()
+    );
+
+    let current = &report.files[1];
+    assert_eq!(dir.path().join("sub/current.toml"), current.path);
+    assert!(!current.migrated());
+    assert!(current.result.is_ok());
+
+    let unknown = &report.files[2];
+    assert_eq!(dir.path().join("sub/unknown.toml"), unknown.path);
+    assert_eq!(Some(vec!["a:3".parse().unwrap()].into()), unknown.uris);
+    match &unknown.result {
+        Err(UpgradeError::Deserialize(DeserializeError::AggregateError(_))) => {}
+        r => panic!("unknown version group must fail, got {:?}", r),
+    }
+    assert_eq!(
+        "v = [\"a:1\"]\na = 5\n",
+        std::fs::read_to_string(dir.path().join("notes.txt")).unwrap()
+    );
+}
+
+#[cfg(feature = "diff-support")]
+#[test]
+fn diff_works() {
+    let input = "v = [\"a:1\"]\na = 5\n";
+    let diff = serde_version::diff::diff::<A, _, _, _>(
+        &serde_version::toml::Options::new(),
+        input,
+        &*VERSIONS,
+        &(),
+        &vec!["a:2".parse().unwrap()].into(),
+    )
+    .unwrap();
+    assert_eq!(
+        vec!["~ v[0]: \"a:1\" -> \"a:2\"", "- a = 5", "+ b = 5"],
+        diff.changes
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        "--- original\n+++ migrated\n@@ -1,2 +1,2 @@\n-v = [\"a:1\"]\n-a = 5\n+v = [\"a:2\"]\n+b = 5\n",
+        diff.unified_diff
+    );
+}
@@ -0,0 +1,337 @@
+use serde_version::upgrade::{
+    write_atomic, FileFilter, UpgradeError, UpgradeOptions, UpgradeReport,
+};
+use serde_version::{AggregateVersionMap, StaticVersionGroupResolver, VersionGroupURIs};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+type Error = UpgradeError<std::fmt::Error, std::fmt::Error>;
+
+fn file_names(dir: &Path) -> Vec<String> {
+    let mut names: Vec<_> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn upgrade_file_with_works() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.txt");
+    fs::write(&path, "v1").unwrap();
+
+    let report = UpgradeOptions::new()
+        .upgrade_file_with(&path, |input| -> Result<_, Error> {
+            assert_eq!(b"v1", input);
+            Ok(Some(b"v2".to_vec()))
+        })
+        .unwrap();
+    assert_eq!(
+        UpgradeReport {
+            changed: true,
+            backup: None,
+        },
+        report
+    );
+    assert_eq!("v2", fs::read_to_string(&path).unwrap());
+    assert_eq!(vec!["config.txt"], file_names(dir.path()));
+
+    // The file is not rewritten when it is up to date
+    let report = UpgradeOptions::new()
+        .backup("bak")
+        .upgrade_file_with(&path, |_| -> Result<_, Error> { Ok(None) })
+        .unwrap();
+    assert_eq!(
+        UpgradeReport {
+            changed: false,
+            backup: None,
+        },
+        report
+    );
+    assert_eq!(vec!["config.txt"], file_names(dir.path()));
+}
+
+#[test]
+fn upgrade_file_with_backup() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.txt");
+    fs::write(&path, "v1").unwrap();
+
+    let report = UpgradeOptions::new()
+        .backup("orig")
+        .upgrade_file_with(&path, |_| -> Result<_, Error> { Ok(Some(b"v2".to_vec())) })
+        .unwrap();
+    let backup = dir.path().join("config.txt.orig");
+    assert_eq!(
+        UpgradeReport {
+            changed: true,
+            backup: Some(backup.clone()),
+        },
+        report
+    );
+    assert_eq!("v2", fs::read_to_string(&path).unwrap());
+    assert_eq!("v1", fs::read_to_string(&backup).unwrap());
+}
+
+#[test]
+fn upgrade_file_with_errors() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.txt");
+
+    match UpgradeOptions::new().upgrade_file_with(&path, |_| -> Result<_, Error> { unreachable!() })
+    {
+        Err(UpgradeError::Io(err)) => assert_eq!(std::io::ErrorKind::NotFound, err.kind()),
+        r => panic!("missing file must fail, got {:?}", r),
+    }
+
+    // The file is left untouched
+    fs::write(&path, "v1").unwrap();
+    match UpgradeOptions::new()
+        .backup("bak")
+        .upgrade_file_with(&path, |_| -> Result<Option<Vec<u8>>, Error> {
+            Err(UpgradeError::Deserialize(std::fmt::Error))
+        }) {
+        Err(UpgradeError::Deserialize(_)) => {}
+        r => panic!("upgrade errors must be kept, got {:?}", r),
+    }
+    assert_eq!("v1", fs::read_to_string(&path).unwrap());
+    assert_eq!(vec!["config.txt"], file_names(dir.path()));
+}
+
+#[test]
+fn write_atomic_works() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.txt");
+
+    write_atomic(&path, b"first").unwrap();
+    assert_eq!("first", fs::read_to_string(&path).unwrap());
+    write_atomic(&path, b"second").unwrap();
+    assert_eq!("second", fs::read_to_string(&path).unwrap());
+    assert_eq!(vec!["config.txt"], file_names(dir.path()));
+
+    // The temporary file is removed when the rename fails
+    let target = dir.path().join("directory");
+    fs::create_dir(&target).unwrap();
+    fs::write(target.join("entry"), "").unwrap();
+    assert!(write_atomic(&target, b"contents").is_err());
+    assert_eq!(vec!["config.txt", "directory"], file_names(dir.path()));
+}
+
+#[test]
+fn write_atomic_skips_stale_temporary_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.txt");
+    // Left by an interrupted write of this process
+    for count in 0..4 {
+        let stale = format!(".config.txt.{}.{}.tmp", std::process::id(), count);
+        fs::write(dir.path().join(stale), "stale").unwrap();
+    }
+
+    write_atomic(&path, b"contents").unwrap();
+    assert_eq!("contents", fs::read_to_string(&path).unwrap());
+}
+
+#[cfg(unix)]
+#[test]
+fn write_atomic_keeps_symlinks() {
+    let dir = tempfile::tempdir().unwrap();
+    let target = dir.path().join("target.txt");
+    let link = dir.path().join("link.txt");
+    fs::write(&target, "first").unwrap();
+    std::os::unix::fs::symlink(&target, &link).unwrap();
+
+    write_atomic(&link, b"second").unwrap();
+    assert!(fs::symlink_metadata(&link)
+        .unwrap()
+        .file_type()
+        .is_symlink());
+    assert_eq!("second", fs::read_to_string(&target).unwrap());
+}
+
+#[cfg(unix)]
+#[test]
+fn write_atomic_keeps_permissions() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.txt");
+    fs::write(&path, "first").unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+
+    write_atomic(&path, b"second").unwrap();
+    assert_eq!(
+        0o600,
+        fs::metadata(&path).unwrap().permissions().mode() & 0o777
+    );
+}
+
+/// Upgrade the files `<uri>\n<contents>` to `a:2`
+fn upgrade_text(path: &Path, aggregate: &impl AggregateVersionMap) -> Result<UpgradeReport, Error> {
+    let mut resolver = StaticVersionGroupResolver::new();
+    resolver.insert("a:1".parse().unwrap(), [("A", 1)].into_iter().collect());
+    resolver.insert("a:2".parse().unwrap(), [("A", 2)].into_iter().collect());
+
+    UpgradeOptions::new().upgrade_file_with(path, |input| {
+        let input = std::str::from_utf8(input).unwrap();
+        let (uri, contents) = input
+            .split_once('\n')
+            .ok_or(UpgradeError::Deserialize(std::fmt::Error))?;
+        let uris: VersionGroupURIs = vec![uri.parse().unwrap()].into();
+        aggregate
+            .aggregate_version_maps(&uris, &resolver)
+            .map_err(|_| UpgradeError::Deserialize(std::fmt::Error))?;
+        if uri == "a:2" {
+            return Ok(None);
+        }
+        Ok(Some(format!("a:2\n{}", contents).into_bytes()))
+    })
+}
+
+#[test]
+fn upgrade_dir_with_works() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::create_dir_all(dir.path().join("sub/deep")).unwrap();
+    fs::write(dir.path().join("first.txt"), "a:1\nfirst").unwrap();
+    fs::write(dir.path().join("sub/second.txt"), "a:2\nsecond").unwrap();
+    fs::write(dir.path().join("sub/deep/third.txt"), "a:3\nthird").unwrap();
+    fs::write(dir.path().join("sub/deep/fourth.txt"), "fourth").unwrap();
+    fs::write(dir.path().join("sub/ignored.md"), "a:1\nignored").unwrap();
+
+    let report = UpgradeOptions::new().upgrade_dir_with(
+        dir.path(),
+        &FileFilter::extension("txt"),
+        &(),
+        |path, aggregate| upgrade_text(path, aggregate),
+    );
+    let paths: Vec<_> = report.files.iter().map(|file| file.path.clone()).collect();
+    assert_eq!(
+        vec![
+            dir.path().join("first.txt"),
+            dir.path().join("sub/deep/fourth.txt"),
+            dir.path().join("sub/deep/third.txt"),
+            dir.path().join("sub/second.txt"),
+        ],
+        paths
+    );
+
+    let first = &report.files[0];
+    assert!(first.migrated());
+    assert_eq!(Some(vec!["a:1".parse().unwrap()].into()), first.uris);
+    assert_eq!(
+        Some([("A".to_owned(), 1)].into_iter().collect()),
+        first.version_map
+    );
+    assert_eq!("a:2\nfirst", fs::read_to_string(&first.path).unwrap());
+
+    // A file without version header
+    let fourth = &report.files[1];
+    assert_eq!(None, fourth.uris);
+    assert!(fourth.result.is_err());
+
+    // The version header is reported even if it is unknown
+    let third = &report.files[2];
+    assert_eq!(Some(vec!["a:3".parse().unwrap()].into()), third.uris);
+    assert_eq!(None, third.version_map);
+    assert!(third.result.is_err());
+    assert_eq!("a:3\nthird", fs::read_to_string(&third.path).unwrap());
+
+    let second = &report.files[3];
+    assert!(!second.migrated());
+    assert_eq!(
+        Some([("A".to_owned(), 2)].into_iter().collect()),
+        second.version_map
+    );
+
+    let paths = |files: &mut dyn Iterator<Item = &serde_version::upgrade::FileReport<_, _>>| {
+        files
+            .map(|file| file.path.clone())
+            .collect::<Vec<PathBuf>>()
+    };
+    assert_eq!(vec![first.path.clone()], paths(&mut report.migrated()));
+    assert_eq!(vec![second.path.clone()], paths(&mut report.unchanged()));
+    assert_eq!(
+        vec![fourth.path.clone(), third.path.clone()],
+        paths(&mut report.failed())
+    );
+    assert!(report.has_errors());
+    assert_eq!(
+        "a:1\nignored",
+        fs::read_to_string(dir.path().join("sub/ignored.md")).unwrap()
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn upgrade_dir_with_skips_symlinks() {
+    let dir = tempfile::tempdir().unwrap();
+    let outside = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("first.txt"), "a:1\nfirst").unwrap();
+    fs::write(outside.path().join("second.txt"), "a:1\nsecond").unwrap();
+    std::os::unix::fs::symlink(dir.path().join("first.txt"), dir.path().join("link.txt")).unwrap();
+    std::os::unix::fs::symlink(outside.path(), dir.path().join("linked")).unwrap();
+
+    let report = UpgradeOptions::new().upgrade_dir_with(
+        dir.path(),
+        &FileFilter::extension("txt"),
+        &(),
+        |path, aggregate| upgrade_text(path, aggregate),
+    );
+    let paths: Vec<_> = report.files.iter().map(|file| file.path.clone()).collect();
+    assert_eq!(vec![dir.path().join("first.txt")], paths);
+    assert!(report.files[0].migrated());
+    assert_eq!(
+        "a:1\nsecond",
+        fs::read_to_string(outside.path().join("second.txt")).unwrap()
+    );
+}
+
+#[test]
+fn upgrade_dir_with_errors() {
+    let dir = tempfile::tempdir().unwrap();
+    let missing = dir.path().join("missing");
+
+    let report = UpgradeOptions::new().upgrade_dir_with(
+        &missing,
+        &FileFilter::extension("txt"),
+        &(),
+        |path, aggregate| upgrade_text(path, aggregate),
+    );
+    assert_eq!(1, report.files.len());
+    assert_eq!(missing, report.files[0].path);
+    match &report.files[0].result {
+        Err(UpgradeError::Io(err)) => assert_eq!(std::io::ErrorKind::NotFound, err.kind()),
+        r => panic!("missing directory must fail, got {:?}", r),
+    }
+
+    let report = UpgradeOptions::new().upgrade_dir_with(
+        dir.path(),
+        &FileFilter::extension("txt"),
+        &(),
+        |path, aggregate| upgrade_text(path, aggregate),
+    );
+    assert!(report.files.is_empty());
+    assert!(!report.has_errors());
+}
+
+#[cfg(feature = "glob-support")]
+#[test]
+fn file_filter_glob() {
+    let filter = FileFilter::glob("assets/**/*.toml").unwrap();
+    assert!(filter.matches(Path::new("assets/config.toml")));
+    assert!(filter.matches(Path::new("assets/levels/first.toml")));
+    assert!(!filter.matches(Path::new("config.toml")));
+    assert!(!filter.matches(Path::new("assets/config.json")));
+
+    assert!(FileFilter::glob("assets/[").is_err());
+}
+
+#[test]
+fn file_filter_extension() {
+    let filter = FileFilter::extension("toml");
+    assert!(filter.matches(Path::new("config.toml")));
+    assert!(filter.matches(Path::new("assets/config.toml")));
+    assert!(!filter.matches(Path::new("config.toml.bak")));
+    assert!(!filter.matches(Path::new("toml")));
+}
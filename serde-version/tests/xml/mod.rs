@@ -0,0 +1,210 @@
+use serde_version::xml::{deserialize, serialize, DeserializeError, Options};
+use serde_version::{AggregateVersionMapError, VersionGroupURIs};
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "A")]
+struct Av1 {
+    a: u32,
+}
+
+#[derive(Serialize, Deserialize, DeserializeVersioned, PartialEq, Debug)]
+#[versions(v(index = 1, type = "Av1"), v(index = 2, self))]
+struct A {
+    b: u32,
+    #[serde(rename = "@name")]
+    name: String,
+}
+
+impl From<Av1> for A {
+    fn from(v: Av1) -> Self {
+        Self {
+            b: v.a,
+            name: "default".to_owned(),
+        }
+    }
+}
+
+version_group_resolver_static! {
+    pub VERSIONS = {
+        ("a" , "1") => { A => 1, },
+        ("a" , "2") => { A => 2, },
+        ("a" , "3") => { A => 3, },
+        ("b" , "1") => { },
+    }
+}
+
+fn uris(uris: &[&str]) -> VersionGroupURIs {
+    uris.iter().map(|uri| uri.parse().unwrap()).collect()
+}
+
+fn a() -> A {
+    A {
+        b: 5,
+        name: "my <name>".to_owned(),
+    }
+}
+
+#[test]
+fn serialize_works() {
+    let output = serialize(&a(), &uris(&["a:2", "b:1"])).unwrap();
+    assert_eq!(
+        concat!(
+            r#"<A xmlns:sv="https://github.com/fredericvauchelles/serde-version""#,
+            r#" sv:v="a:2 b:1" name="my &lt;name&gt;"><b>5</b></A>"#
+        ),
+        output
+    );
+
+    let output = Options::new()
+        .header_attribute("meta:versions")
+        .namespace("urn:meta")
+        .serialize(&Av1 { a: 5 }, &uris(&["a:1"]))
+        .unwrap();
+    assert_eq!(
+        r#"<A xmlns:meta="urn:meta" meta:versions="a:1"><a>5</a></A>"#,
+        output
+    );
+}
+
+#[test]
+fn roundtrip_works() {
+    for options in &[
+        Options::new(),
+        Options::new().header_attribute("version"),
+        Options::new().namespace("urn:serde-version"),
+    ] {
+        let output = options.serialize(&a(), &uris(&["a:2"])).unwrap();
+        let de: A = options.deserialize(&output, &*VERSIONS, &()).unwrap();
+        assert_eq!(a(), de, "{:?}", options);
+
+        let output = options.serialize(&Av1 { a: 5 }, &uris(&["a:1"])).unwrap();
+        let de: A = options.deserialize(&output, &*VERSIONS, &()).unwrap();
+        assert_eq!(A::from(Av1 { a: 5 }), de, "{:?}", options);
+    }
+}
+
+#[test]
+fn deserialize_works() {
+    let input = r#"<?xml version="1.0"?>
+<!-- exported document -->
+<doc xmlns:sv="https://github.com/fredericvauchelles/serde-version" sv:v=" b:1
+  a:1 "><a>5</a></doc>"#;
+    let de: A = deserialize(input, &*VERSIONS, &()).unwrap();
+    assert_eq!(A::from(Av1 { a: 5 }), de);
+
+    // The namespace is found whatever its prefix
+    let input = r#"<doc xmlns:x="urn:meta" name="n" x:versions="a:2"><b>5</b></doc>"#;
+    let de: A = Options::new()
+        .header_attribute("meta:versions")
+        .namespace("urn:meta")
+        .deserialize(input, &*VERSIONS, &())
+        .unwrap();
+    assert_eq!(
+        A {
+            b: 5,
+            name: "n".to_owned()
+        },
+        de
+    );
+}
+
+#[derive(Deserialize, DeserializeVersioned, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+#[versions(v(index = 1, self))]
+struct Strict {
+    b: u32,
+}
+
+version_group_resolver_static! {
+    pub STRICT_VERSIONS = {
+        ("s" , "1") => { Strict => 1, },
+    }
+}
+
+#[test]
+fn deserialize_skips_header_attributes() {
+    let input = r#"<doc xmlns:sv="https://github.com/fredericvauchelles/serde-version" sv:v="s:1"><b>5</b></doc>"#;
+    let de: Strict = deserialize(input, &*STRICT_VERSIONS, &()).unwrap();
+    assert_eq!(Strict { b: 5 }, de);
+
+    let input = r#"<doc xmlns:x="urn:meta" x:versions="s:1"><b>5</b></doc>"#;
+    let de: Strict = Options::new()
+        .header_attribute("meta:versions")
+        .namespace("urn:meta")
+        .deserialize(input, &*STRICT_VERSIONS, &())
+        .unwrap();
+    assert_eq!(Strict { b: 5 }, de);
+
+    let input = r#"<doc version="s:1"><b>5</b></doc>"#;
+    let de: Strict = Options::new()
+        .header_attribute("version")
+        .deserialize(input, &*STRICT_VERSIONS, &())
+        .unwrap();
+    assert_eq!(Strict { b: 5 }, de);
+}
+
+#[test]
+fn deserialize_errors() {
+    match deserialize::<A, _, _>("<doc><a>5</a></doc>", &*VERSIONS, &()) {
+        Err(DeserializeError::MissingVersionHeader(attribute)) => assert_eq!("sv:v", attribute),
+        r => panic!("missing header must fail, got {:?}", r),
+    }
+    // The default prefix is not bound to the default namespace
+    match deserialize::<A, _, _>(r#"<doc sv:v="a:1"><a>5</a></doc>"#, &*VERSIONS, &()) {
+        Err(DeserializeError::MissingVersionHeader(attribute)) => assert_eq!("sv:v", attribute),
+        r => panic!("undeclared prefix must fail, got {:?}", r),
+    }
+    // The prefix is bound to another namespace
+    match Options::new().namespace("urn:meta").deserialize::<A, _, _>(
+        r#"<doc xmlns:sv="urn:other" sv:v="a:1"><a>5</a></doc>"#,
+        &*VERSIONS,
+        &(),
+    ) {
+        Err(DeserializeError::MissingVersionHeader(attribute)) => assert_eq!("sv:v", attribute),
+        r => panic!("header in another namespace must fail, got {:?}", r),
+    }
+    match deserialize::<A, _, _>(
+        r#"<doc xmlns:sv="https://github.com/fredericvauchelles/serde-version" sv:v="a"><a>5</a></doc>"#,
+        &*VERSIONS,
+        &(),
+    ) {
+        Err(DeserializeError::InvalidVersionHeader(_)) => {}
+        r => panic!("invalid header must fail, got {:?}", r),
+    }
+    match deserialize::<A, _, _>(
+        r#"<doc xmlns:sv="https://github.com/fredericvauchelles/serde-version" sv:v="a:4"><a>5</a></doc>"#,
+        &*VERSIONS,
+        &(),
+    ) {
+        Err(DeserializeError::AggregateError(
+            AggregateVersionMapError::UnknownVersionGroupURI(uri),
+        )) => assert_eq!("a:4", uri.to_string()),
+        r => panic!("unknown uri must fail, got {:?}", r),
+    }
+    match deserialize::<A, _, _>(
+        r#"<doc xmlns:sv="https://github.com/fredericvauchelles/serde-version" sv:v="a:3"><a>5</a></doc>"#,
+        &*VERSIONS,
+        &(),
+    ) {
+        Err(DeserializeError::De(serde_version::Error::InvalidVersionError(err))) => {
+            assert_eq!(3, err.version)
+        }
+        r => panic!("unknown version must fail, got {:?}", r),
+    }
+    match deserialize::<A, _, _>(
+        r#"<doc xmlns:sv="https://github.com/fredericvauchelles/serde-version" sv:v="a:2" name="n"><b>five</b></doc>"#,
+        &*VERSIONS,
+        &(),
+    ) {
+        Err(DeserializeError::De(serde_version::Error::DeserializeError(_))) => {}
+        r => panic!("invalid body must fail, got {:?}", r),
+    }
+    match deserialize::<A, _, _>(
+        r#"<doc xmlns:sv="https://github.com/fredericvauchelles/serde-version" sv:v="a:2></doc>"#,
+        &*VERSIONS,
+        &(),
+    ) {
+        Err(DeserializeError::Xml(_)) => {}
+        r => panic!("invalid document must fail, got {:?}", r),
+    }
+}
@@ -0,0 +1,183 @@
+use serde_version::yaml::{serialize, DeserializeError, Options};
+use serde_version::{AggregateVersionMapError, VersionGroupURIs};
+
+#[derive(Deserialize)]
+#[serde(rename = "A")]
+struct Av1 {
+    a: usize,
+}
+
+#[derive(Serialize, Deserialize, DeserializeVersioned, PartialEq, Debug)]
+#[versions(v(index = 1, type = "Av1"), v(index = 2, self))]
+struct A {
+    b: usize,
+}
+
+impl From<Av1> for A {
+    fn from(v: Av1) -> Self {
+        Self { b: v.a }
+    }
+}
+
+version_group_resolver_static! {
+    pub VERSIONS = {
+        ("a" , "1") => { A => 1, },
+        ("a" , "2") => { A => 2, },
+    }
+}
+
+fn uris(uris: &[&str]) -> VersionGroupURIs {
+    uris.iter().map(|uri| uri.parse().unwrap()).collect()
+}
+
+macro_rules! declare_de_tests {
+    ($name:ident $options:expr => { $($value:expr => $yaml:expr)* }) => {
+        #[test]
+        fn $name() {
+            $({
+                let input = $yaml;
+                let de: A = $options.deserialize(input, &*VERSIONS, &()).unwrap();
+                assert_eq!($value, de);
+            })*
+        }
+    };
+}
+
+declare_de_tests! {
+    deserialize_root_key Options::new() => {
+        A { b: 5 } => "v: [\"a:1\"]\na: 5\n"
+        A { b: 5 } => "# comment\nb: 5\nv:\n  - a:2\n"
+        A { b: 5 } => "v: [\"a:2\", \"a:1\"]\na: 5\n"
+    }
+}
+
+declare_de_tests! {
+    deserialize_header_document Options::new().header_document() => {
+        A { b: 5 } => "v: [\"a:1\"]\n---\na: 5\n"
+        A { b: 5 } => "---\nv:\n  - a:2\n---\nb: 5\n"
+    }
+}
+
+declare_de_tests! {
+    deserialize_header_key Options::new().header_key("versions") => {
+        A { b: 5 } => "versions: [\"a:1\"]\na: 5\n"
+    }
+}
+
+#[test]
+fn deserialize_errors() {
+    let deserialize = |input| serde_version::yaml::deserialize::<A, _, _>(input, &*VERSIONS, &());
+
+    match deserialize("b: 5\n") {
+        Err(DeserializeError::MissingVersionHeader(header)) => assert_eq!("v", header),
+        r => panic!("missing header must fail, got {:?}", r),
+    }
+    match deserialize("- a:2\n") {
+        Err(DeserializeError::MissingVersionHeader(_)) => {}
+        r => panic!("a sequence must fail, got {:?}", r),
+    }
+    match deserialize("v: a:2\nb: 5\n") {
+        Err(DeserializeError::InvalidVersionHeader(_)) => {}
+        r => panic!("header must be a sequence, got {:?}", r),
+    }
+    match deserialize("v: [\"a:3\"]\nb: 5\n") {
+        Err(DeserializeError::AggregateError(
+            AggregateVersionMapError::UnknownVersionGroupURI(uri),
+        )) => assert_eq!("a:3", uri.to_string()),
+        r => panic!("unknown uri must fail, got {:?}", r),
+    }
+    match deserialize("v: [\"a:2\"]\nb: [5\n") {
+        Err(DeserializeError::Yaml(_)) => {}
+        r => panic!("invalid yaml must fail, got {:?}", r),
+    }
+    match deserialize("v: [\"a:2\"]\na: 5\n") {
+        Err(DeserializeError::De(_)) => {}
+        r => panic!("invalid value must fail, got {:?}", r),
+    }
+}
+
+#[test]
+fn deserialize_header_document_errors() {
+    let options = Options::new().header_document();
+    let deserialize = |input| options.deserialize::<A, _, _>(input, &*VERSIONS, &());
+
+    match deserialize("") {
+        Err(DeserializeError::MissingVersionHeader(_)) => {}
+        r => panic!("empty stream must fail, got {:?}", r),
+    }
+    match deserialize("v: [\"a:2\"]\n") {
+        Err(DeserializeError::MissingPayload) => {}
+        r => panic!("missing payload must fail, got {:?}", r),
+    }
+    match deserialize("v: [\"a:2\"]\nb: 5\n---\nb: 5\n") {
+        Err(DeserializeError::InvalidVersionHeader(_)) => {}
+        r => panic!("header document must only contain the header, got {:?}", r),
+    }
+    match deserialize("v: [\"a:2\"]\n---\nb: 5\n---\nb: 6\n") {
+        Err(DeserializeError::UnexpectedDocument) => {}
+        r => panic!("extra document must fail, got {:?}", r),
+    }
+}
+
+#[test]
+fn serialize_works() {
+    let output = serialize(&A { b: 5 }, &uris(&["a:2"])).unwrap();
+    assert_eq!("v:\n- a:2\nb: 5\n", output);
+    let de: A = serde_version::yaml::deserialize(&output, &*VERSIONS, &()).unwrap();
+    assert_eq!(A { b: 5 }, de);
+
+    let options = Options::new().header_key("versions").header_document();
+    let output = options
+        .serialize(&A { b: 5 }, &uris(&["a:1", "a:2"]))
+        .unwrap();
+    assert_eq!("versions:\n- a:1\n- a:2\n---\nb: 5\n", output);
+    let de: A = options.deserialize(&output, &*VERSIONS, &()).unwrap();
+    assert_eq!(A { b: 5 }, de);
+
+    // The version header can only be merged in a mapping
+    assert!(serialize(&5, &uris(&["a:2"])).is_err());
+}
+
+#[cfg(feature = "diff-support")]
+#[test]
+fn diff_works() {
+    let options = Options::new().header_document();
+    let input = "v:\n- a:1\n---\na: 5\n";
+    let diff =
+        serde_version::diff::diff::<A, _, _, _>(&options, input, &*VERSIONS, &(), &uris(&["a:2"]))
+            .unwrap();
+    assert_eq!(
+        vec!["[0].v[0]", "[1].b"],
+        diff.changes
+            .iter()
+            .map(|change| change.path())
+            .filter(|path| !path.ends_with(".a"))
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(vec!["[1].a"], diff.removed().collect::<Vec<_>>());
+}
+
+#[derive(Deserialize, DeserializeVersioned, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+#[versions(v(index = 2, self))]
+struct Borrowed<'a> {
+    name: &'a str,
+    #[serde(borrow)]
+    author: std::borrow::Cow<'a, str>,
+}
+
+version_group_resolver_static! {
+    pub BORROWED_VERSIONS = {
+        ("doc" , "2") => { Borrowed => 2, },
+    }
+}
+
+#[test]
+fn deserialize_borrowed() {
+    use std::borrow::Cow;
+
+    let input = "name: my config\nv: [\"doc:2\"]\nauthor: me\n";
+    let de: Borrowed = serde_version::yaml::deserialize(input, &*BORROWED_VERSIONS, &()).unwrap();
+    assert_eq!("my config", de.name);
+    assert!(matches!(de.author, Cow::Borrowed("me")));
+}
@@ -19,12 +19,12 @@ pub mod symbols {
 }
 
 pub mod attr {
-    use super::symbols::{DEFAULT, INDEX, SELF, TYPE, VERSION, VERSION_SHORTHAND, VERSIONS};
+    use super::super::util::get_serde_version_meta_items;
+    use super::symbols::{DEFAULT, INDEX, SELF, TYPE, VERSION, VERSIONS, VERSION_SHORTHAND};
     use proc_macro_util::prelude::{Attr, Ctxt};
     use quote::ToTokens;
     use std::collections::HashMap;
     use syn::{Meta, NestedMeta};
-    use super::super::util::get_serde_version_meta_items;
 
     pub struct Container {
         versions: Option<Versions>,
@@ -69,7 +69,7 @@ pub mod attr {
                                                         }
                                                     }
                                                     _ => {
-                                                        error_message = Some(format!("'type' expect a string value, received {}", pair.lit.clone().into_token_stream().to_string()));
+                                                        error_message = Some(format!("'type' expect a string value, received {}", pair.lit.clone().into_token_stream()));
                                                         break;
                                                     }
                                                 };
@@ -84,7 +84,7 @@ pub mod attr {
                                                         }
                                                     }
                                                     _ => {
-                                                        error_message = Some(format!("'index' expect an integer value, received {}", pair.lit.clone().into_token_stream().to_string()));
+                                                        error_message = Some(format!("'index' expect an integer value, received {}", pair.lit.clone().into_token_stream()));
                                                         break;
                                                     }
                                                 };
@@ -162,7 +162,7 @@ pub mod attr {
                     error_message = error_message.or_else(|| {
                         if self_version_defined {
                             versions.set(
-                                &item,
+                                item,
                                 Versions {
                                     versions: parsed_versions,
                                 },
@@ -242,11 +242,11 @@ impl<'a> Container<'a> {
 
 #[cfg(test)]
 mod tests {
+    use crate::ast::attr::PathOrSelf;
+    use crate::ast::Container;
     use proc_macro_util::prelude::Ctxt;
     use quote::ToTokens;
     use std::collections::HashMap;
-    use crate::ast::attr::PathOrSelf;
-    use crate::ast::Container;
 
     #[test]
     fn parse_container() {
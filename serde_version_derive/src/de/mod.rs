@@ -14,12 +14,17 @@ pub fn expand_derive_deserialize_versioned(
         Some(versions) => {
             let de_impl_generics = {
                 let mut generics = cont.generics.clone();
-                generics.params = Some(syn::GenericParam::Lifetime(syn::LifetimeDef::new(
-                    syn::Lifetime::new("'de", Span::call_site()),
-                )))
-                .into_iter()
-                .chain(generics.params)
-                .collect();
+                let mut de_lifetime =
+                    syn::LifetimeDef::new(syn::Lifetime::new("'de", Span::call_site()));
+                // The borrowed fields are borrowed from the input
+                de_lifetime.bounds = generics
+                    .lifetimes()
+                    .map(|def| def.lifetime.clone())
+                    .collect();
+                generics.params = Some(syn::GenericParam::Lifetime(de_lifetime))
+                    .into_iter()
+                    .chain(generics.params)
+                    .collect();
                 generics
             };
             let ident = &cont.ident;
@@ -38,13 +43,7 @@ pub fn expand_derive_deserialize_versioned(
 
             let last_version = *versions
                 .iter()
-                .find(|(_, v)| {
-                    if let PathOrSelf::SelfType = &v.path {
-                        true
-                    } else {
-                        false
-                    }
-                })
+                .find(|(_, v)| matches!(&v.path, PathOrSelf::SelfType))
                 // The self type is always described in the version attribute
                 // This is enforced when building the Container
                 .unwrap()
@@ -118,7 +117,7 @@ pub fn expand_derive_deserialize_versioned(
                         Some(quote! {
                             Some(#version_number) => std::result::Result::map(
                                 <::core::marker::PhantomData<#path> as _serde_version::DeserializeVersionedSeed<'_>>::next_key(
-                                    ::core::marker::PhantomData, 
+                                    ::core::marker::PhantomData,
                                     __map_access,
                                     __version_map
                                 ),
@@ -154,7 +153,14 @@ pub fn expand_derive_deserialize_versioned(
                 })
                 .collect::<Vec<_>>();
 
+            let version_indices = versions.keys();
+            let (impl_generics, type_generics, type_where_clause) = cont.generics.split_for_impl();
+
             let code = quote! {
+                impl #impl_generics _serde_version::DeclaredVersions for #ident #type_generics #type_where_clause {
+                    const VERSIONS: &'static [usize] = &[#(#version_indices),*];
+                }
+
                 impl #de_impl_generics _serde_version::DeserializeVersioned<'de> for #ident #ty_generics {
                     fn deserialize_versioned<__D, __VM: _serde_version::VersionMap>(
                         __deserializer: __D,
@@ -0,0 +1,289 @@
+use proc_macro2::TokenStream;
+use std::collections::BTreeMap;
+use std::path::Path;
+use syn::parse::{Parse, ParseStream};
+
+/// Separator between the api group and the version of a uri
+const VERSION_GROUP_URI_SEPARATOR: char = ':';
+
+/// `include_version_groups!("path")` or `include_version_groups!(pub NAME = "path")`
+pub struct Input {
+    item: Option<(syn::Visibility, syn::Ident)>,
+    path: syn::LitStr,
+}
+
+impl Parse for Input {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let item = if input.peek(syn::LitStr) {
+            None
+        } else {
+            let vis = input.parse()?;
+            let ident = input.parse()?;
+            input.parse::<syn::Token![=]>()?;
+            Some((vis, ident))
+        };
+        let path = input.parse()?;
+        Ok(Input { item, path })
+    }
+}
+
+/// Key of the parent of a group
+const EXTENDS_KEY: &str = "extends";
+
+/// A group of the manifest: its parent and the version of each type path
+#[derive(Debug, Default)]
+struct Group {
+    extends: Option<String>,
+    versions: BTreeMap<String, usize>,
+}
+
+/// Groups of the manifest, by version group uri
+type Manifest = BTreeMap<String, Group>;
+
+pub fn expand_include_version_groups(input: Input) -> Result<TokenStream, syn::Error> {
+    let span = input.path.span();
+    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR")
+        .map_err(|_| syn::Error::new(span, "CARGO_MANIFEST_DIR is not defined"))?;
+    let path = Path::new(&manifest_dir).join(input.path.value());
+    let content = std::fs::read_to_string(&path)
+        .map_err(|err| syn::Error::new(span, format!("Can't read {}: {}", path.display(), err)))?;
+    let manifest = parse_manifest(&path, &content).map_err(|err| syn::Error::new(span, err))?;
+    let groups = expand_groups(&manifest).map_err(|err| syn::Error::new(span, err))?;
+
+    // Rebuild when the manifest changes
+    let path = path.to_string_lossy().into_owned();
+    let track = quote! {
+        const _: &str = include_str!(#path);
+    };
+
+    Ok(match input.item {
+        Some((vis, ident)) => quote! {
+            #track
+            ::serde_version::lazy_static! {
+                #vis static ref #ident: ::serde_version::StaticVersionGroupResolver = #groups;
+            }
+        },
+        None => quote! {
+            {
+                #track
+                #groups
+            }
+        },
+    })
+}
+
+type RawManifest = BTreeMap<String, BTreeMap<String, serde_json::Value>>;
+
+fn parse_manifest(path: &Path, content: &str) -> Result<Manifest, String> {
+    let manifest: Result<RawManifest, String> =
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("toml") => toml::from_str(content).map_err(|err| err.to_string()),
+            Some("json") => serde_json::from_str(content).map_err(|err| err.to_string()),
+            _ => Err("expected a '.toml' or '.json' manifest".to_string()),
+        };
+    manifest
+        .and_then(parse_groups)
+        .map_err(|err| format!("Invalid manifest {}: {}", path.display(), err))
+}
+
+fn parse_groups(manifest: RawManifest) -> Result<Manifest, String> {
+    let mut groups = Manifest::new();
+    for (uri, entries) in manifest {
+        let mut group = Group::default();
+        for (key, value) in entries {
+            if key == EXTENDS_KEY {
+                match value.as_str() {
+                    Some(parent) => group.extends = Some(parent.to_owned()),
+                    None => return Err(format!("the parent of {} must be a uri", uri)),
+                }
+                continue;
+            }
+            match value.as_u64() {
+                Some(index) => {
+                    group.versions.insert(key, index as usize);
+                }
+                None => {
+                    return Err(format!(
+                        "the version of {} in {} must be a positive integer",
+                        key, uri
+                    ))
+                }
+            }
+        }
+        groups.insert(uri, group);
+    }
+    Ok(groups)
+}
+
+/// Check that the parents are declared and that the groups do not extend each other
+fn check_parents(manifest: &Manifest) -> Result<(), String> {
+    for uri in manifest.keys() {
+        let mut chain = vec![uri];
+        while let Some(parent) = &manifest[chain[chain.len() - 1]].extends {
+            if !manifest.contains_key(parent) {
+                return Err(format!(
+                    "Version group {} extends the unknown version group {}",
+                    chain[chain.len() - 1],
+                    parent
+                ));
+            }
+            if let Some(start) = chain.iter().position(|child| *child == parent) {
+                chain.push(parent);
+                let cycle: Vec<&str> = chain[start..].iter().map(|uri| uri.as_str()).collect();
+                return Err(format!(
+                    "Cycle in the parents of version groups: {}",
+                    cycle.join(" -> ")
+                ));
+            }
+            chain.push(parent);
+        }
+    }
+    Ok(())
+}
+
+/// Expand to a `StaticVersionGroupResolver` expression, with compile time checks of the versions
+fn expand_groups(manifest: &Manifest) -> Result<TokenStream, String> {
+    check_parents(manifest)?;
+
+    let mut checks = Vec::new();
+    let mut groups = Vec::new();
+    for (uri, group) in manifest {
+        let (api_group, version) = split_uri(uri)?;
+        if let Some(parent) = &group.extends {
+            split_uri(parent)?;
+        }
+        let parent = group.extends.iter();
+
+        let mut types = Vec::new();
+        for (ty, index) in &group.versions {
+            let ty_path = syn::parse_str::<syn::Type>(ty).map_err(|_| {
+                format!(
+                    "Invalid type {:?} in version group {}, expected a type path",
+                    ty, uri
+                )
+            })?;
+            let message = format!(
+                "Version {} of type {} in version group {} is not declared in #[versions(...)]",
+                index, ty, uri
+            );
+            checks.push(quote! {
+                const _: () = ::std::assert!(
+                    ::serde_version::declares_version(
+                        <#ty_path as ::serde_version::DeclaredVersions>::VERSIONS,
+                        #index
+                    ),
+                    #message
+                );
+            });
+            types.push(quote! {
+                (::std::any::type_name::<#ty_path>(), #index),
+            });
+        }
+
+        groups.push(quote! {
+            (
+                ::serde_version::static_version_group_uri(#api_group, #version),
+                ::serde_version::static_version_group_parent(&[#(#parent),*]),
+                ::serde_version::collect_static_version_map(::std::vec![#(#types)*]),
+            ),
+        });
+    }
+
+    Ok(quote! {
+        {
+            #(#checks)*
+            ::serde_version::collect_static_resolver(::std::vec![#(#groups)*])
+        }
+    })
+}
+
+fn split_uri(uri: &str) -> Result<(&str, &str), String> {
+    match uri.rfind(VERSION_GROUP_URI_SEPARATOR) {
+        Some(index) if index > 0 && index + 1 < uri.len() => Ok((&uri[..index], &uri[index + 1..])),
+        _ => Err(format!(
+            "Invalid version group uri {:?}, expected 'api_group:version'",
+            uri
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{expand_groups, parse_manifest, Manifest};
+    use std::path::Path;
+
+    fn manifest(content: &str) -> Manifest {
+        parse_manifest(Path::new("groups.toml"), content).unwrap()
+    }
+
+    #[test]
+    fn parse_manifests() {
+        let manifest = manifest(
+            r#"
+["company:1"]
+A = 1
+"module::B" = 2
+"#,
+        );
+        assert_eq!(Some(&2), manifest["company:1"].versions.get("module::B"));
+
+        let json = parse_manifest(
+            Path::new("groups.json"),
+            r#"{ "company:2": { "extends": "company:1", "A": 1 } }"#,
+        )
+        .unwrap();
+        assert_eq!(Some(&1), json["company:2"].versions.get("A"));
+        assert_eq!(Some("company:1"), json["company:2"].extends.as_deref());
+
+        assert!(parse_manifest(Path::new("groups.ini"), "").is_err());
+        assert!(parse_manifest(Path::new("groups.toml"), "[\"company:1\"]\nA = -1").is_err());
+        assert!(parse_manifest(Path::new("groups.toml"), "[\"company:1\"]\nextends = 1").is_err());
+    }
+
+    #[test]
+    fn expand_valid_groups() {
+        let tokens = expand_groups(&manifest("[\"company:1.2\"]\n\"module::A\" = 3\n"))
+            .unwrap()
+            .to_string();
+        assert!(tokens.contains("static_version_group_uri (\"company\" , \"1.2\")"));
+        assert!(tokens.contains("type_name :: < module :: A > () , 3usize"));
+    }
+
+    #[test]
+    fn reject_invalid_groups() {
+        assert_eq!(
+            Err("Invalid version group uri \"company\", expected 'api_group:version'".to_owned()),
+            expand_groups(&manifest("[company]\nA = 1\n")).map(|_| ())
+        );
+        assert_eq!(
+            Err("Invalid type \"A B\" in version group company:1, expected a type path".to_owned()),
+            expand_groups(&manifest("[\"company:1\"]\n\"A B\" = 1\n")).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn expand_extended_groups() {
+        let tokens = expand_groups(&manifest(
+            "[\"company:1\"]\nA = 1\n[\"company:2\"]\nextends = \"company:1\"\nA = 2\n",
+        ))
+        .unwrap()
+        .to_string();
+        assert!(tokens.contains("static_version_group_parent (& [\"company:1\"])"));
+        assert!(tokens.contains("static_version_group_parent (& [])"));
+    }
+
+    #[test]
+    fn reject_invalid_parents() {
+        assert_eq!(
+            Err("Version group company:2 extends the unknown version group company:1".to_owned()),
+            expand_groups(&manifest("[\"company:2\"]\nextends = \"company:1\"\n")).map(|_| ())
+        );
+        assert_eq!(
+            Err("Cycle in the parents of version groups: a:1 -> b:1 -> a:1".to_owned()),
+            expand_groups(&manifest(
+                "[\"a:1\"]\nextends = \"b:1\"\n[\"b:1\"]\nextends = \"a:1\"\n"
+            ))
+            .map(|_| ())
+        );
+    }
+}
@@ -7,6 +7,7 @@ extern crate quote;
 
 mod ast;
 mod de;
+mod include_version_groups;
 mod util;
 
 #[proc_macro_derive(DeserializeVersioned, attributes(versions, serde))]
@@ -17,6 +18,44 @@ pub fn derive_deserialize_versioned(input: proc_macro::TokenStream) -> proc_macr
         .into()
 }
 
+/// Declare a static `VersionGroupResolver` from a manifest file
+///
+/// The manifest is a TOML or JSON file mapping version group uris to the version index of
+/// each type of the group. The path of the manifest is relative to `CARGO_MANIFEST_DIR` and
+/// its type keys are type paths resolved where the macro is used:
+/// ```toml
+/// ["my.company:1.0"]
+/// "config::Config" = 1
+/// "config::Item" = 2
+///
+/// ["my.company:1.1"]
+/// extends = "my.company:1.0"
+/// "config::Item" = 3
+/// ```
+///
+/// A group can `extends` another group of the manifest and list only the types whose
+/// version changed. An unknown parent or a cycle of parents fails the compilation.
+///
+/// The type paths are only the keys of the file: the resolver is keyed by the
+/// `std::any::type_name` of each type, like the runtime manifests of
+/// `serde_version::manifest`. `"config::Config"` included from the crate `my_app` is
+/// served as `my_app::config::Config`, which is the key to use in a runtime manifest
+/// declaring the same groups.
+///
+/// `include_version_groups!("groups.toml")` expands to a `StaticVersionGroupResolver` and
+/// `include_version_groups!(pub VERSIONS = "groups.toml")` declares a lazy static,
+/// like `version_group_resolver_static!`.
+///
+/// The types must derive `DeserializeVersioned` and declare in their `#[versions(...)]` each
+/// version index used by the manifest, otherwise the compilation fails.
+#[proc_macro]
+pub fn include_version_groups(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as include_version_groups::Input);
+    include_version_groups::expand_include_version_groups(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
 fn to_compile_errors(errors: Vec<syn::Error>) -> proc_macro2::TokenStream {
     let compile_errors = errors.iter().map(syn::Error::to_compile_error);
     quote!(#(#compile_errors)*)